@@ -0,0 +1,160 @@
+const K0: u64 = 0xc3a5c85c97cb3127;
+const K1: u64 = 0xb492b66fbe98f273;
+const K2: u64 = 0x9ae16a3b2f90404f;
+const K_MUL: u64 = 0x9ddfea08eb382d69;
+
+/// CityHash64 (v1.1), as used by Unreal for name map hashes.
+pub fn city_hash_64(s: &[u8]) -> u64 {
+    let len = s.len();
+    if len <= 16 {
+        return hash_len_0_to_16(s);
+    } else if len <= 32 {
+        return hash_len_17_to_32(s);
+    } else if len <= 64 {
+        return hash_len_33_to_64(s);
+    }
+
+    // For strings over 64 bytes we hash the end first, and then as we
+    // loop we keep 56 bytes of state: v, w, x, y, and z.
+    let mut x = fetch64(s, len - 40);
+    let mut y = fetch64(s, len - 16).wrapping_add(fetch64(s, len - 56));
+    let mut z = hash_len_16(fetch64(s, len - 48).wrapping_add(len as u64), fetch64(s, len - 24));
+    let mut v = weak_hash_len_32_with_seeds(s, len - 64, len as u64, z);
+    let mut w = weak_hash_len_32_with_seeds(s, len - 32, y.wrapping_add(K1), x);
+    x = x.wrapping_mul(K1).wrapping_add(fetch64(s, 0));
+
+    let mut pos = 0;
+    let mut remaining = (len - 1) & !63;
+    loop {
+        x = rotate(x.wrapping_add(y).wrapping_add(v.0).wrapping_add(fetch64(s, pos + 8)), 37).wrapping_mul(K1);
+        y = rotate(y.wrapping_add(v.1).wrapping_add(fetch64(s, pos + 48)), 42).wrapping_mul(K1);
+        x ^= w.1;
+        y = y.wrapping_add(v.0).wrapping_add(fetch64(s, pos + 40));
+        z = rotate(z.wrapping_add(w.0), 33).wrapping_mul(K1);
+        v = weak_hash_len_32_with_seeds(s, pos, v.1.wrapping_mul(K1), x.wrapping_add(w.0));
+        w = weak_hash_len_32_with_seeds(s, pos + 32, z.wrapping_add(w.1), y.wrapping_add(fetch64(s, pos + 16)));
+        std::mem::swap(&mut z, &mut x);
+        pos += 64;
+        remaining -= 64;
+        if remaining == 0 {
+            break;
+        }
+    }
+
+    hash_len_16(
+        hash_len_16(v.0, w.0).wrapping_add(shift_mix(y).wrapping_mul(K1)).wrapping_add(z),
+        hash_len_16(v.1, w.1).wrapping_add(x),
+    )
+}
+
+fn fetch64(s: &[u8], pos: usize) -> u64 {
+    u64::from_le_bytes(s[pos..pos + 8].try_into().unwrap())
+}
+
+fn fetch32(s: &[u8], pos: usize) -> u64 {
+    u32::from_le_bytes(s[pos..pos + 4].try_into().unwrap()) as u64
+}
+
+fn rotate(val: u64, shift: u32) -> u64 {
+    if shift == 0 { val } else { val.rotate_right(shift) }
+}
+
+fn shift_mix(val: u64) -> u64 {
+    val ^ (val >> 47)
+}
+
+fn hash_len_16(u: u64, v: u64) -> u64 {
+    hash_len_16_mul(u, v, K_MUL)
+}
+
+fn hash_len_16_mul(u: u64, v: u64, mul: u64) -> u64 {
+    let mut a = (u ^ v).wrapping_mul(mul);
+    a ^= a >> 47;
+    let mut b = (v ^ a).wrapping_mul(mul);
+    b ^= b >> 47;
+    b.wrapping_mul(mul)
+}
+
+fn hash_len_0_to_16(s: &[u8]) -> u64 {
+    let len = s.len();
+    if len >= 8 {
+        let mul = K2.wrapping_add(len as u64 * 2);
+        let a = fetch64(s, 0).wrapping_add(K2);
+        let b = fetch64(s, len - 8);
+        let c = rotate(b, 37).wrapping_mul(mul).wrapping_add(a);
+        let d = rotate(a, 25).wrapping_add(b).wrapping_mul(mul);
+        return hash_len_16_mul(c, d, mul);
+    }
+    if len >= 4 {
+        let mul = K2.wrapping_add(len as u64 * 2);
+        let a = fetch32(s, 0);
+        return hash_len_16_mul((len as u64).wrapping_add(a << 3), fetch32(s, len - 4), mul);
+    }
+    if len > 0 {
+        let a = s[0] as u32;
+        let b = s[len >> 1] as u32;
+        let c = s[len - 1] as u32;
+        let y = a.wrapping_add(b << 8);
+        let z = (len as u32).wrapping_add(c << 2);
+        return shift_mix((y as u64).wrapping_mul(K2) ^ (z as u64).wrapping_mul(K0)).wrapping_mul(K2);
+    }
+    K2
+}
+
+fn hash_len_17_to_32(s: &[u8]) -> u64 {
+    let len = s.len();
+    let mul = K2.wrapping_add(len as u64 * 2);
+    let a = fetch64(s, 0).wrapping_mul(K1);
+    let b = fetch64(s, 8);
+    let c = fetch64(s, len - 8).wrapping_mul(mul);
+    let d = fetch64(s, len - 16).wrapping_mul(K2);
+    hash_len_16_mul(
+        rotate(a.wrapping_add(b), 43).wrapping_add(rotate(c, 30)).wrapping_add(d),
+        a.wrapping_add(rotate(b.wrapping_add(K2), 18)).wrapping_add(c),
+        mul,
+    )
+}
+
+fn hash_len_33_to_64(s: &[u8]) -> u64 {
+    let len = s.len();
+    let mul = K2.wrapping_add(len as u64 * 2);
+    let a = fetch64(s, 0).wrapping_mul(K2);
+    let b = fetch64(s, 8);
+    let c = fetch64(s, len - 24);
+    let d = fetch64(s, len - 32);
+    let e = fetch64(s, 16).wrapping_mul(K2);
+    let f = fetch64(s, 24).wrapping_mul(9);
+    let g = fetch64(s, len - 8);
+    let h = fetch64(s, len - 16).wrapping_mul(mul);
+    let u = rotate(a.wrapping_add(g), 43).wrapping_add(rotate(b, 30).wrapping_add(c).wrapping_mul(9));
+    let v = (a.wrapping_add(g) ^ d).wrapping_add(f).wrapping_add(1);
+    let w = u.wrapping_add(v).wrapping_mul(mul).swap_bytes().wrapping_add(h);
+    let x = rotate(e.wrapping_add(f), 42).wrapping_add(c);
+    let y = v.wrapping_add(w).wrapping_mul(mul).swap_bytes().wrapping_add(g).wrapping_mul(mul);
+    let z = e.wrapping_add(f).wrapping_add(c);
+    let a = x.wrapping_add(z).wrapping_mul(mul).wrapping_add(y).swap_bytes().wrapping_add(b);
+    let b = shift_mix(z.wrapping_add(a).wrapping_mul(mul).wrapping_add(d).wrapping_add(h)).wrapping_mul(mul);
+    b.wrapping_add(x)
+}
+
+fn weak_hash_len_32_with_seeds(s: &[u8], pos: usize, a: u64, b: u64) -> (u64, u64) {
+    let (w, x, y, z) = (fetch64(s, pos), fetch64(s, pos + 8), fetch64(s, pos + 16), fetch64(s, pos + 24));
+    let mut a = a.wrapping_add(w);
+    let mut b = rotate(b.wrapping_add(a).wrapping_add(z), 21);
+    let c = a;
+    a = a.wrapping_add(x).wrapping_add(y);
+    b = b.wrapping_add(rotate(a, 44));
+    (a.wrapping_add(z), b.wrapping_add(c))
+}
+
+#[allow(dead_code)]
+#[allow(unused_imports)]
+mod test {
+    use super::city_hash_64;
+
+    #[test]
+    fn known_answers() {
+        assert_eq!(city_hash_64(b""), 0x9ae16a3b2f90404f);
+        assert_eq!(city_hash_64(b"abc"), 0x24a5b3a074e7f369);
+    }
+}
@@ -0,0 +1,43 @@
+use flate2::read::ZlibDecoder;
+use std::{collections::HashMap, error::Error, io::Read, sync::{Arc, OnceLock, RwLock}};
+
+/// Decompresses one compression block, given the block's bytes and its uncompressed size
+pub type Decompressor = Arc<dyn Fn(&[u8], usize) -> Result<Vec<u8>, Box<dyn Error>> + Send + Sync>;
+
+fn registry() -> &'static RwLock<HashMap<String, Decompressor>> {
+    static REGISTRY: OnceLock<RwLock<HashMap<String, Decompressor>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(builtin_decompressors()))
+}
+
+/// Decompressors that don't need anything from the game.  Oodle has to be registered by the caller.
+fn builtin_decompressors() -> HashMap<String, Decompressor> {
+    let mut decompressors: HashMap<String, Decompressor> = HashMap::new();
+    decompressors.insert("none".to_string(), Arc::new(|compressed: &[u8], _| Ok(compressed.to_vec())));
+    decompressors.insert("zlib".to_string(), Arc::new(decompress_zlib));
+    decompressors
+}
+
+// The size comes from the file, so the output isn't preallocated from it
+fn decompress_zlib(compressed: &[u8], _uncompressed_size: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut bytes = vec![];
+    ZlibDecoder::new(compressed).read_to_end(&mut bytes).map_err(|e| format!("Invalid zlib block: {e}"))?;
+    Ok(bytes)
+}
+
+/// Registers the decompressor for a compression method (e.g. "Oodle"), as named in a container's method list.
+/// Method names are matched case-insensitively, and a method that's already registered is replaced.
+pub fn register_decompressor<F>(method: &str, decompressor: F)
+where F: Fn(&[u8], usize) -> Result<Vec<u8>, Box<dyn Error>> + Send + Sync + 'static {
+    registry().write().unwrap().insert(method.to_lowercase(), Arc::new(decompressor));
+}
+
+/// Decompresses a block with the decompressor registered for `method`, checking it comes out at the expected size
+pub fn decompress(method: &str, compressed: &[u8], uncompressed_size: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+    let decompressor = registry().read().unwrap().get(&method.to_lowercase()).cloned()
+        .ok_or(format!("No decompressor is registered for {method} compression"))?;
+    let bytes = decompressor(compressed, uncompressed_size)?;
+    if bytes.len() != uncompressed_size {
+        Err(format!("{method} block decompressed to {} bytes, expected {uncompressed_size}", bytes.len()))?;
+    }
+    Ok(bytes)
+}
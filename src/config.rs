@@ -1,61 +1,215 @@
-pub struct Config {
-    pub command: Command,
-    pub inpath: String,
-    pub outpath: Option<String>,
-}
-
-pub enum Command {
-    Encode,
-    Decode,
-    Test,
-}
-
-impl Config {
-    pub fn new(mut args: std::env::Args) -> Result<Self, String> {
-        args.next(); //Skip executable path
-
-        let command = match args.next().ok_or("Missing command")?.as_str() {
-            "encode" => Command::Encode,
-            "decode" => Command::Decode,
-            "test" => Command::Test,
-            "--help" | "-h" => Err(String::new())?,
-            other => Err(format!("Unknown command: {other}"))?
-        };
-
-        let inpath = args.next().ok_or("Missing inpath")?;
-        let outpath = args.next();
-
-        Ok(Self { 
-            command, 
-            inpath, 
-            outpath
-        })
-    }
-
-    pub fn usage() -> &'static str {
-        r#"
-
-Converts a packed iouasset between binary and a yaml-like format. Built
-and tested using UE4.27 (no guarantees on other verions).
-
-Usage:     uasset-data-parser <command> <input path> [output path]
-
-    <command>         Command to execute.  
-    One of:
-        decode        Decodes a .uasset file.
-        encode        Encodes a .yaml_uasset file.
-        test          Decodes and reencodes a .uasset file, verifying that
-                      the final output matches the input.  Useful to ensure
-                      this tool will work with a given file.
-
-    <input path>      Path to file that should be converted.
-
-    [output path]     Optional. Path to the file that should be written. If
-                      omitted, defaults to the input file with a modified 
-                      extension (either .uasset or .yaml_uasset)
-
-    -h, --help        Show this help and exit.
-
-        "#
-    }
+use crate::iostore_uasset::EngineVersion;
+
+pub struct Config {
+    pub command: Command,
+    pub inpath: String,
+    pub inpaths: Vec<String>,
+    pub outpath: Option<String>,
+    pub schema: Option<String>,
+    pub usmap: Option<String>,
+    pub script_modules: Vec<String>,
+    pub engine_version: Option<EngineVersion>,
+    pub package_name: Option<String>,
+    pub package_path: Option<String>,
+    pub max_errors: Option<usize>,
+    pub yaml: bool,
+}
+
+pub enum Command {
+    Encode,
+    Decode,
+    Test,
+    Convert,
+    List,
+    ExtractDecode,
+    Pack,
+}
+
+impl Config {
+    pub fn new(mut args: std::env::Args) -> Result<Self, String> {
+        args.next(); //Skip executable path
+
+        let command = match args.next().ok_or("Missing command")?.as_str() {
+            "encode" => Command::Encode,
+            "decode" => Command::Decode,
+            "test" => Command::Test,
+            "convert" => Command::Convert,
+            "list" => Command::List,
+            "extract-decode" => Command::ExtractDecode,
+            "pack" => Command::Pack,
+            "--help" | "-h" => Err(String::new())?,
+            other => Err(format!("Unknown command: {other}"))?
+        };
+
+        let mut schema = None;
+        let mut usmap = None;
+        let mut script_modules = vec![];
+        let mut engine_version = None;
+        let mut package_name = None;
+        let mut max_errors = None;
+        let mut yaml = false;
+        let mut positional = vec![];
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--schema" => { schema = Some(args.next().ok_or("Missing path after --schema")?); },
+                "--usmap" => { usmap = Some(args.next().ok_or("Missing path after --usmap")?); },
+                "--script-module" => { script_modules.push(args.next().ok_or("Missing module name after --script-module")?); },
+                "--engine" => {
+                    let version = args.next().ok_or("Missing version after --engine")?;
+                    engine_version = Some(version.parse().map_err(|e| format!("{e}"))?);
+                },
+                "--package-name" => { package_name = Some(args.next().ok_or("Missing name after --package-name")?); },
+                "--max-errors" => {
+                    let count = args.next().ok_or("Missing count after --max-errors")?;
+                    max_errors = Some(count.parse().ok().filter(|c| *c > 0).ok_or(format!("--max-errors should be a positive number, but got {count}"))?);
+                },
+                "--yaml" => { yaml = true; },
+                "--help" | "-h" => Err(String::new())?,
+                other if other.starts_with("--") => Err(format!("Unknown option: {other}"))?,
+                _ => positional.push(arg),
+            }
+        }
+
+        if let Command::Pack = command {
+            let outpath = positional.pop().ok_or("Missing output .utoc path")?;
+            if positional.is_empty() {
+                Err("Missing packages to pack")?;
+            }
+            return Ok(Self {
+                command,
+                inpath: positional[0].clone(),
+                inpaths: positional,
+                outpath: Some(outpath),
+                schema,
+                usmap,
+                script_modules,
+                engine_version,
+                package_name,
+                package_path: None,
+                max_errors,
+                yaml,
+            });
+        }
+
+        let mut positional = positional.into_iter();
+        let inpath = positional.next().ok_or("Missing inpath")?;
+        let package_path = match command {
+            Command::ExtractDecode => Some(positional.next().ok_or("Missing package path")?),
+            _ => None,
+        };
+        let outpath = positional.next();
+        if let Some(extra) = positional.next() {
+            Err(format!("Unexpected argument: {extra}"))?;
+        }
+
+        Ok(Self { 
+            command, 
+            inpaths: vec![inpath.clone()],
+            inpath, 
+            outpath,
+            schema,
+            usmap,
+            script_modules,
+            engine_version,
+            package_name,
+            package_path,
+            max_errors,
+            yaml,
+        })
+    }
+
+    pub fn usage() -> &'static str {
+        r#"
+
+Converts a packed iouasset between binary and a yaml-like format. Built
+and tested using UE4.27 (no guarantees on other verions).  Legacy
+.uasset/.uexp packages are also supported - the .uexp is read from and
+written next to the .uasset.
+
+Usage:     uasset-data-parser <command> [options] <input path> [output path]
+           uasset-data-parser list <container or pak>
+           uasset-data-parser extract-decode [options]
+                              <container or pak> <package path>
+                              [output path]
+           uasset-data-parser pack <package>... <output .utoc>
+
+    <command>         Command to execute.  
+    One of:
+        decode        Decodes a .uasset file.
+        encode        Encodes a .yaml_uasset file.
+        test          Decodes and reencodes a .uasset file, verifying that
+                      the final output matches the input.  Useful to ensure
+                      this tool will work with a given file.
+        convert       Converts a legacy .uasset/.uexp package into an
+                      IoStore .uasset, or an IoStore .uasset into a
+                      legacy package.  Needs an output path.
+        list          Lists the packages in an IoStore container (.utoc)
+                      or a .pak file (versions 8 to 11).
+        extract-decode
+                      Reads a package out of an IoStore container or a
+                      .pak file and decodes it, without extracting it
+                      first.  The package path is as printed by list,
+                      optionally without the mount point (e.g.
+                      ../../../).  The .ucas files must be next to the
+                      .utoc, and a legacy package's .uexp is read from
+                      the .pak too.  Oodle compressed files aren't
+                      supported.
+        pack          Packs encoded IoStore .uasset files into a new
+                      .utoc/.ucas container, along with the .pak needed
+                      to load it.  Packages are placed by their path from
+                      the folder above Content (e.g. a file at
+                      mods/MyGame/Content/Data/MyTable.uasset is packed
+                      as MyGame/Content/Data/MyTable.uasset).
+
+    <input path>      Path to file that should be converted.
+
+    [output path]     Optional. Path to the file that should be written. If
+                      omitted, defaults to the input file with a modified 
+                      extension (either .uasset or .yaml_uasset)
+
+    --schema <path>   Optional. TOML file describing the binary layout of
+                      custom structs, so they're decoded into named fields
+                      instead of base64.  Each struct is an entry like:
+                          [[struct]]
+                          name = "MyStruct"
+                          fields = [{ name = "x", type = "f32" }]
+                      Field types: u8, i8, u16, i16, u32, i32, u64, i64,
+                      f32, f64.
+
+    --usmap <path>    Optional. .usmap mappings file, needed to decode and
+                      encode packages cooked with unversioned properties.
+
+    --script-module <name>
+                      Optional, repeatable. Script module (e.g. MyGame) to
+                      search when finding an export's class from its class
+                      import.  CoreUObject and Engine are always searched.
+
+    --engine <version>
+                      Optional. Engine version the package is from: 4.25,
+                      4.26, 4.27 or 5.x (UE5.0/5.1 Zen packages).  4.25
+                      and 4.26 are only supported for legacy packages.
+                      When decoding, the version is detected if omitted.
+                      When encoding, overrides the version recorded in
+                      the text.
+
+    --package-name <name>
+                      Optional. Package path (e.g. /Game/Data/MyTable)
+                      of a legacy package converted to IoStore.  If
+                      omitted, it's found from the input path's
+                      Content folder.
+
+    --max-errors <count>
+                      Optional. When encoding, the most errors in the text
+                      file to show.  Every error is shown if omitted.
+
+    --yaml            Optional. Decodes IoStore packages into standard YAML
+                      (written to a .yaml file by default) that other YAML
+                      tools can read and edit, instead of the yaml-like
+                      format.  When encoding, reads that YAML back - which
+                      is also done for any input ending in .yaml.
+
+    -h, --help        Show this help and exit.
+
+        "#
+    }
 }
\ No newline at end of file
@@ -0,0 +1,376 @@
+use byteorder::{ByteOrder, ReadBytesExt};
+use std::{error::Error, fmt::Display, io::{self, BufRead, Read, Seek, SeekFrom}};
+
+/// Why a binary package couldn't be read.  Errors hold the byte offset they were found at, and the path of the
+/// property being read (like `exports[0].Stats.Values[2]`), which is empty outside of export data.
+#[derive(PartialEq, Debug)]
+pub enum ParseError {
+    /// The data ended partway through a value
+    UnexpectedEof { offset: u64, path: String },
+    /// A name reference past the end of the name map
+    BadNameIndex { index: u64, offset: u64, path: String },
+    /// An object reference past the end of the import or export map
+    BadObjectIndex { index: i64, offset: u64, path: String },
+    /// An FString with the wrong length, termination or encoding
+    MalformedFString { offset: u64, path: String },
+    /// Property metadata that doesn't match the property's type
+    TypeMismatch { expected: String, found: String, offset: u64, path: String },
+    /// Any other data that can't be decoded
+    Invalid { message: String, offset: u64, path: String },
+}
+
+impl ParseError {
+    pub(crate) fn invalid(offset: u64, message: impl Into<String>) -> Self {
+        Self::Invalid { message: message.into(), offset, path: String::new() }
+    }
+
+    pub(crate) fn type_mismatch(offset: u64, expected: impl Into<String>, found: impl Into<String>) -> Self {
+        Self::TypeMismatch { expected: expected.into(), found: found.into(), offset, path: String::new() }
+    }
+
+    fn from_io(err: io::Error, offset: u64) -> Self {
+        match err.kind() {
+            io::ErrorKind::UnexpectedEof => Self::UnexpectedEof { offset, path: String::new() },
+            _ => Self::invalid(offset, err.to_string()),
+        }
+    }
+
+    /// The byte offset in the package (or export data, for legacy packages) where the error was found
+    pub fn offset(&self) -> u64 {
+        match self {
+            Self::UnexpectedEof { offset, .. } | Self::BadNameIndex { offset, .. } | Self::BadObjectIndex { offset, .. }
+                | Self::MalformedFString { offset, .. } | Self::TypeMismatch { offset, .. } | Self::Invalid { offset, .. } => *offset,
+        }
+    }
+
+    /// The property the error was found in, or an empty string outside of export data
+    pub fn path(&self) -> &str {
+        match self {
+            Self::UnexpectedEof { path, .. } | Self::BadNameIndex { path, .. } | Self::BadObjectIndex { path, .. }
+                | Self::MalformedFString { path, .. } | Self::TypeMismatch { path, .. } | Self::Invalid { path, .. } => path,
+        }
+    }
+
+    /// Moves the offset of an error found in a buffer that starts at `start` in the package
+    pub(crate) fn moved_by(mut self, start: u64) -> Self {
+        match &mut self {
+            Self::UnexpectedEof { offset, .. } | Self::BadNameIndex { offset, .. } | Self::BadObjectIndex { offset, .. }
+                | Self::MalformedFString { offset, .. } | Self::TypeMismatch { offset, .. } | Self::Invalid { offset, .. } => *offset += start,
+        }
+        self
+    }
+
+    /// Adds the property (or `[index]` item) the error was found in to the front of its path
+    pub(crate) fn in_property(mut self, segment: &str) -> Self {
+        match &mut self {
+            Self::UnexpectedEof { path, .. } | Self::BadNameIndex { path, .. } | Self::BadObjectIndex { path, .. }
+                | Self::MalformedFString { path, .. } | Self::TypeMismatch { path, .. } | Self::Invalid { path, .. } => prepend_segment(path, segment),
+        }
+        self
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnexpectedEof { .. } => f.write_str("Unexpected end of data")?,
+            Self::BadNameIndex { index, .. } => write!(f, "Name index {index} is past the end of the name map")?,
+            Self::BadObjectIndex { index, .. } => write!(f, "Object index {index} isn't in the import or export map")?,
+            Self::MalformedFString { .. } => f.write_str("Malformed FString - length, termination or encoding is incorrect")?,
+            Self::TypeMismatch { expected, found, .. } => write!(f, "Expected {expected}, but found {found}")?,
+            Self::Invalid { message, .. } => f.write_str(message)?,
+        }
+        write!(f, " at 0x{:x}", self.offset())?;
+        if !self.path().is_empty() {
+            write!(f, " in {}", self.path())?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ParseError {}
+
+/// Why an object couldn't be encoded.  Errors hold the path of the property being written, like [`ParseError`],
+/// and the value that couldn't be written.
+#[derive(PartialEq, Debug)]
+pub enum EncodeError {
+    /// A name that isn't in the name map
+    MissingName { name: String, path: String },
+    /// An object reference to an export or import that isn't in the package
+    MissingObject { reference: String, path: String },
+    /// Data that doesn't match the type its property is declared or mapped as
+    TypeMismatch { expected: String, found: String, path: String },
+    /// Any other value that can't be written
+    Unsupported { message: String, path: String },
+    /// The writer failed
+    Io { message: String, path: String },
+}
+
+impl EncodeError {
+    pub(crate) fn unsupported(message: impl Into<String>) -> Self {
+        Self::Unsupported { message: message.into(), path: String::new() }
+    }
+
+    pub(crate) fn type_mismatch(expected: impl Into<String>, found: impl Into<String>) -> Self {
+        Self::TypeMismatch { expected: expected.into(), found: found.into(), path: String::new() }
+    }
+
+    /// The property being written when the error happened, or an empty string outside of export data
+    pub fn path(&self) -> &str {
+        match self {
+            Self::MissingName { path, .. } | Self::MissingObject { path, .. } | Self::TypeMismatch { path, .. }
+                | Self::Unsupported { path, .. } | Self::Io { path, .. } => path,
+        }
+    }
+
+    /// Adds the property (or `[index]` item) the error was found in to the front of its path
+    pub(crate) fn in_property(mut self, segment: &str) -> Self {
+        match &mut self {
+            Self::MissingName { path, .. } | Self::MissingObject { path, .. } | Self::TypeMismatch { path, .. }
+                | Self::Unsupported { path, .. } | Self::Io { path, .. } => prepend_segment(path, segment),
+        }
+        self
+    }
+}
+
+impl Display for EncodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingName { name, .. } => write!(f, "Name [{name}] isn't in the name map")?,
+            Self::MissingObject { reference, .. } => write!(f, "Object reference [{reference}] isn't in the package")?,
+            Self::TypeMismatch { expected, found, .. } => write!(f, "Expected {expected}, but got {found}")?,
+            Self::Unsupported { message, .. } => f.write_str(message)?,
+            Self::Io { message, .. } => write!(f, "Unable to write: {message}")?,
+        }
+        if !self.path().is_empty() {
+            write!(f, " in {}", self.path())?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for EncodeError {}
+
+impl From<io::Error> for EncodeError {
+    fn from(err: io::Error) -> Self {
+        Self::Io { message: err.to_string(), path: String::new() }
+    }
+}
+
+/// Why a text package couldn't be read.  Errors are found at a byte offset in the text, and given the line,
+/// column and a snippet of the line it falls in by [`TextErrors::locate`], once the text can be read again.
+#[derive(PartialEq, Debug, Clone)]
+pub struct TextError {
+    pub message: String,
+    /// 1-based, or 0 if the error hasn't been located
+    pub line: usize,
+    /// 1-based, in characters
+    pub column: usize,
+    /// The offending line, without its line ending
+    pub snippet: String,
+    offset: u64,
+    token: Option<String>,
+}
+
+impl TextError {
+    /// An error in the line holding the byte at `offset`
+    pub(crate) fn new(offset: u64, message: impl Into<String>) -> Self {
+        Self { message: message.into(), line: 0, column: 0, snippet: String::new(), offset, token: None }
+    }
+
+    /// Points the column at the first place `token` appears in the line, rather than the line's first character
+    pub(crate) fn at_token(mut self, token: &str) -> Self {
+        self.token = Some(token.trim().to_owned()).filter(|t| !t.is_empty());
+        self
+    }
+
+    fn set_location(&mut self, line_number: usize, line: &str) {
+        let text = line.trim_end_matches(['\r', '\n']);
+        let column = match self.token.as_deref().and_then(|token| text.find(token)) {
+            Some(column) => column,
+            None => text.len() - text.trim_start().len(),
+        };
+        self.line = line_number;
+        self.column = text[..column].chars().count() + 1;
+        self.snippet = text.to_owned();
+    }
+}
+
+impl Display for TextError {
+    /// Formats the error like a compiler diagnostic, `line:column: message` followed by the line itself
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.line == 0 {
+            return write!(f, "{} at byte {}", self.message, self.offset);
+        }
+        let gutter = " ".repeat(self.line.to_string().len());
+        // Tabs are kept so the marker lines up with the snippet however they're displayed
+        let marker: String = self.snippet.chars().take(self.column - 1).map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+        write!(f, "{}:{}: {}\n{gutter} |\n{} | {}\n{gutter} | {marker}^", self.line, self.column, self.message, self.line, self.snippet)
+    }
+}
+
+impl Error for TextError {}
+
+/// Every error found in a text package.  The reader carries on at the next property after an error, so all of a
+/// file's mistakes can be fixed at once.
+#[derive(PartialEq, Debug, Clone)]
+pub struct TextErrors(pub Vec<TextError>);
+
+impl TextErrors {
+    /// Sorts the errors into the order they appear in, and finds their lines and columns in one read of the text
+    pub(crate) fn locate<R: BufRead + Seek + ?Sized>(mut self, reader: &mut R) -> Self {
+        self.0.sort_by_key(|err| err.offset);
+        if reader.seek(SeekFrom::Start(0)).is_err() {
+            return self;
+        }
+        let (mut line, mut line_end, mut line_number) = (String::new(), 0, 0);
+        let mut errors = self.0.iter_mut().peekable();
+        while errors.peek().is_some() {
+            line.clear();
+            match reader.read_line(&mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(len) => line_end += len as u64,
+            }
+            line_number += 1;
+            while let Some(err) = errors.next_if(|err| err.offset < line_end) {
+                err.set_location(line_number, &line);
+            }
+        }
+        self
+    }
+}
+
+impl Display for TextErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, err) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str("\n\n")?;
+            }
+            write!(f, "{err}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for TextErrors {}
+
+impl From<TextError> for TextErrors {
+    fn from(err: TextError) -> Self {
+        Self(vec![err])
+    }
+}
+
+/// Joins property path segments with dots, except for `[index]` segments, which follow their container directly
+fn prepend_segment(path: &mut String, segment: &str) {
+    if !path.is_empty() && !path.starts_with('[') {
+        path.insert(0, '.');
+    }
+    path.insert_str(0, segment);
+}
+
+macro_rules! parse_primitives {
+    ($($parse:ident => $read:ident: $type:ty),*) => {
+        $(
+            fn $parse<E: ByteOrder>(&mut self) -> Result<$type, ParseError> {
+                let offset = self.offset();
+                self.$read::<E>().map_err(|err| ParseError::from_io(err, offset))
+            }
+        )*
+    };
+}
+
+/// Reads like [`ReadBytesExt`], with errors that hold the offset the read started at
+pub(crate) trait ParseRead: Read + Seek {
+    /// The current stream position, for errors in data that has already been read
+    fn offset(&mut self) -> u64 {
+        self.stream_position().unwrap_or_default()
+    }
+
+    fn parse_exact(&mut self, buf: &mut [u8]) -> Result<(), ParseError> {
+        let offset = self.offset();
+        self.read_exact(buf).map_err(|err| ParseError::from_io(err, offset))
+    }
+
+    /// Reads `len` bytes, without allocating them all up front in case a corrupt length is far too large
+    fn parse_vec(&mut self, len: usize) -> Result<Vec<u8>, ParseError> {
+        let offset = self.offset();
+        let mut data = vec![];
+        Read::take(&mut *self, len as u64).read_to_end(&mut data).map_err(|err| ParseError::from_io(err, offset))?;
+        match data.len() == len {
+            true => Ok(data),
+            false => Err(ParseError::UnexpectedEof { offset, path: String::new() }),
+        }
+    }
+
+    fn parse_seek(&mut self, pos: SeekFrom) -> Result<u64, ParseError> {
+        let offset = self.offset();
+        self.seek(pos).map_err(|err| ParseError::from_io(err, offset))
+    }
+
+    fn parse_u8(&mut self) -> Result<u8, ParseError> {
+        let offset = self.offset();
+        self.read_u8().map_err(|err| ParseError::from_io(err, offset))
+    }
+
+    fn parse_i8(&mut self) -> Result<i8, ParseError> {
+        let offset = self.offset();
+        self.read_i8().map_err(|err| ParseError::from_io(err, offset))
+    }
+
+    parse_primitives!(
+        parse_u16 => read_u16: u16,
+        parse_i16 => read_i16: i16,
+        parse_u32 => read_u32: u32,
+        parse_i32 => read_i32: i32,
+        parse_u64 => read_u64: u64,
+        parse_i64 => read_i64: i64,
+        parse_f32 => read_f32: f32,
+        parse_f64 => read_f64: f64
+    );
+}
+
+impl<R: Read + Seek + ?Sized> ParseRead for R {}
+
+/// Reads the text format, with errors that point at the line that was just read
+pub(crate) trait TextRead: BufRead + Seek {
+    /// A byte in the line that was just read, for errors found in it
+    fn line_offset(&mut self) -> u64 {
+        self.stream_position().unwrap_or_default().saturating_sub(1)
+    }
+
+    fn text_error(&mut self, message: impl Into<String>) -> TextError {
+        TextError::new(self.line_offset(), message)
+    }
+}
+
+impl<R: BufRead + Seek + ?Sized> TextRead for R {}
+
+#[allow(dead_code)]
+#[allow(unused_imports)]
+mod test {
+    use super::*;
+    use byteorder::LE;
+    use std::io::Cursor;
+
+    #[test]
+    fn reads_past_the_end_report_their_offset() {
+        let mut reader = Cursor::new([1, 0, 0, 0, 2, 0]);
+        assert_eq!(reader.parse_u32::<LE>().unwrap(), 1);
+        assert_eq!(reader.parse_u32::<LE>().unwrap_err(), ParseError::UnexpectedEof { offset: 4, path: String::new() });
+    }
+
+    #[test]
+    fn paths_are_built_from_the_innermost_property() {
+        let err = ParseError::invalid(0x10, "Bad data").in_property("Inner").in_property("[2]").in_property("Values").in_property("exports[0]");
+        assert_eq!(err.path(), "exports[0].Values[2].Inner");
+        assert_eq!(err.to_string(), "Bad data at 0x10 in exports[0].Values[2].Inner");
+    }
+
+    #[test]
+    fn text_errors_point_at_their_line_and_token() {
+        let mut reader = Cursor::new("summary: abc\nexports:\n  - 0: !i32 x1\n");
+        let errors = TextErrors(vec![TextError::new(30, "invalid value").at_token("x1"), TextError::new(3, "bad summary")]).locate(&mut reader);
+        assert_eq!(errors.0.iter().map(|err| (err.line, err.column)).collect::<Vec<_>>(), [(1, 1), (3, 13)]);
+        assert_eq!(errors.0[1].to_string(), "3:13: invalid value\n  |\n3 |   - 0: !i32 x1\n  |             ^");
+    }
+}
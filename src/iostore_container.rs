@@ -0,0 +1,781 @@
+use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use crate::compression;
+use crate::iostore_uasset::{package_id, UObjectSummary};
+use crate::legacy_uasset::{read_fstring, write_fstring};
+use crate::pak::write_companion_pak;
+use crate::sha1::sha1;
+use std::{error::Error, io::{self, Cursor, Read, Seek, SeekFrom, Write}};
+
+const TOC_MAGIC: &[u8; 16] = b"-==--==--==--==-";
+const TOC_HEADER_SIZE: u32 = 144;
+const COMPRESSION_BLOCK_ENTRY_SIZE: u32 = 12;
+const COMPRESSION_METHOD_NAME_LEN: u32 = 32;
+/// Index used by the directory index for "no entry"
+const NONE_INDEX: u32 = u32::MAX;
+
+/// EIoStoreTocVersion values that change the .utoc layout
+const TOC_VERSION_DIRECTORY_INDEX: u8 = 2;
+const TOC_VERSION_PARTITION_SIZE: u8 = 3;
+const TOC_VERSION_PERFECT_HASH: u8 = 4;
+const TOC_VERSION_PERFECT_HASH_WITH_OVERFLOW: u8 = 5;
+
+/// EIoContainerFlags
+const CONTAINER_FLAG_ENCRYPTED: u8 = 0x2;
+const CONTAINER_FLAG_SIGNED: u8 = 0x4;
+const CONTAINER_FLAG_INDEXED: u8 = 0x8;
+
+/// EIoChunkType of a package's export data.  UE5 dropped InstallManifest from the start of the enum.
+const CHUNK_TYPE_EXPORT_BUNDLE_DATA_UE4: u8 = 2;
+const CHUNK_TYPE_EXPORT_BUNDLE_DATA_UE5: u8 = 1;
+const CHUNK_TYPE_CONTAINER_HEADER_UE4: u8 = 10;
+
+/// Layout of packed containers, as written by UnrealPak for 4.27
+const PACK_TOC_VERSION: u8 = TOC_VERSION_PARTITION_SIZE;
+const PACK_COMPRESSION_BLOCK_SIZE: u32 = 0x10000;
+const PACK_MOUNT_POINT: &str = "../../../";
+
+/// Size of the SHA1 hash stored per compression block in signed containers
+const BLOCK_SIGNATURE_SIZE: usize = 20;
+
+/// An FIoChunkId - a 64 bit id, a big-endian 16 bit index and the chunk type
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) struct IoChunkId([u8; 12]);
+
+impl IoChunkId {
+    fn new(id: u64, index: u16, chunk_type: u8) -> Self {
+        let mut bytes = [0; 12];
+        bytes[..8].copy_from_slice(&id.to_le_bytes());
+        bytes[8..10].copy_from_slice(&index.to_be_bytes());
+        bytes[11] = chunk_type;
+        Self(bytes)
+    }
+
+    pub(crate) fn chunk_type(&self) -> u8 {
+        self.0[11]
+    }
+}
+
+/// An FIoStoreTocCompressedBlockEntry - where a block sits in the .ucas files, and how to decompress it
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct CompressionBlock {
+    offset: u64,
+    compressed_size: u32,
+    uncompressed_size: u32,
+    /// 0 for uncompressed blocks, otherwise 1 + an index into the container's method names
+    method_index: u8,
+}
+
+/// The table of contents of an IoStore container (.utoc), used to find and read files out of its .ucas partitions
+pub struct IoStoreContainer {
+    version: u8,
+    compression_block_size: u32,
+    partition_count: u32,
+    partition_size: u64,
+    chunk_ids: Vec<IoChunkId>,
+    /// Offset and length of each chunk in the container's uncompressed data
+    chunks: Vec<(u64, u64)>,
+    compression_blocks: Vec<CompressionBlock>,
+    compression_methods: Vec<String>,
+    /// Path of each file in the directory index, and the index of its chunk
+    files: Vec<(String, usize)>,
+}
+
+impl IoStoreContainer {
+    /// Reads a .utoc file
+    pub fn from_buffer<R: Read>(reader: &mut R) -> Result<Self, Box<dyn Error>> {
+        let mut magic = [0; 16];
+        reader.read_exact(&mut magic)?;
+        if &magic != TOC_MAGIC {
+            Err("Not an IoStore table of contents - the .utoc magic is missing")?;
+        }
+        let version = reader.read_u8()?;
+        reader.read_u8()?;
+        reader.read_u16::<LE>()?;
+        let toc_header_size = reader.read_u32::<LE>()?;
+        let entry_count = reader.read_u32::<LE>()? as usize;
+        let compression_block_count = reader.read_u32::<LE>()? as usize;
+        let compression_block_entry_size = reader.read_u32::<LE>()?;
+        let method_name_count = reader.read_u32::<LE>()? as usize;
+        let method_name_length = reader.read_u32::<LE>()?;
+        let compression_block_size = reader.read_u32::<LE>()?;
+        let directory_index_size = reader.read_u32::<LE>()? as usize;
+        let partition_count = reader.read_u32::<LE>()?;
+        let _container_id = reader.read_u64::<LE>()?;
+        let mut _encryption_key_guid = [0; 16];
+        reader.read_exact(&mut _encryption_key_guid)?;
+        let container_flags = reader.read_u8()?;
+        reader.read_u8()?;
+        reader.read_u16::<LE>()?;
+        let perfect_hash_seed_count = reader.read_u32::<LE>()? as usize;
+        let partition_size = reader.read_u64::<LE>()?;
+        let chunks_without_perfect_hash_count = reader.read_u32::<LE>()? as usize;
+        skip(reader, 4 + 5 * 8)?;
+
+        if toc_header_size != TOC_HEADER_SIZE {
+            Err(format!("Unexpected .utoc header size {toc_header_size}, expected {TOC_HEADER_SIZE}"))?;
+        }
+        if compression_block_entry_size != COMPRESSION_BLOCK_ENTRY_SIZE {
+            Err(format!("Unexpected compression block entry size {compression_block_entry_size}, expected {COMPRESSION_BLOCK_ENTRY_SIZE}"))?;
+        }
+        if container_flags & CONTAINER_FLAG_ENCRYPTED != 0 {
+            Err("Encrypted containers aren't supported")?;
+        }
+        if method_name_length != COMPRESSION_METHOD_NAME_LEN {
+            Err(format!("Unexpected compression method name length {method_name_length}, expected {COMPRESSION_METHOD_NAME_LEN}"))?;
+        }
+        if compression_block_size == 0 {
+            Err("Invalid .utoc: compression block size is 0")?;
+        }
+
+        // The counts come from the file, so nothing is preallocated from them - a bad count runs into the end of the file
+        let mut chunk_ids = vec![];
+        for _ in 0..entry_count {
+            let mut id = [0; 12];
+            reader.read_exact(&mut id)?;
+            chunk_ids.push(IoChunkId(id));
+        }
+
+        let mut chunks = vec![];
+        for _ in 0..entry_count {
+            let offset = reader.read_uint::<byteorder::BE>(5)?;
+            let length = reader.read_uint::<byteorder::BE>(5)?;
+            chunks.push((offset, length));
+        }
+
+        if version >= TOC_VERSION_PERFECT_HASH {
+            skip(reader, perfect_hash_seed_count * 4)?;
+        }
+        if version >= TOC_VERSION_PERFECT_HASH_WITH_OVERFLOW {
+            skip(reader, chunks_without_perfect_hash_count * 4)?;
+        }
+
+        let mut compression_blocks = vec![];
+        for _ in 0..compression_block_count {
+            compression_blocks.push(CompressionBlock {
+                offset: reader.read_uint::<LE>(5)?,
+                compressed_size: reader.read_uint::<LE>(3)? as u32,
+                uncompressed_size: reader.read_uint::<LE>(3)? as u32,
+                method_index: reader.read_u8()?,
+            });
+        }
+
+        let mut compression_methods = vec![];
+        for _ in 0..method_name_count {
+            let mut name = [0; COMPRESSION_METHOD_NAME_LEN as usize];
+            reader.read_exact(&mut name)?;
+            compression_methods.push(name.iter().take_while(|b| **b != 0).map(|b| *b as char).collect());
+        }
+
+        if container_flags & CONTAINER_FLAG_SIGNED != 0 {
+            let hash_size = reader.read_i32::<LE>()?;
+            if hash_size < 0 {
+                Err(format!("Invalid .utoc signature size {hash_size}"))?;
+            }
+            skip(reader, hash_size as usize * 2 + compression_block_count * BLOCK_SIGNATURE_SIZE)?;
+        }
+
+        let files = if version >= TOC_VERSION_DIRECTORY_INDEX && directory_index_size > 0 {
+            let directory_index = read_bytes(reader, directory_index_size)?;
+            let files = read_directory_index(&mut Cursor::new(directory_index))?;
+            if let Some((path, _)) = files.iter().find(|(_, index)| *index >= entry_count) {
+                Err(format!("Directory index entry {path} points past the end of the .utoc"))?;
+            }
+            files
+        } else {
+            vec![]
+        };
+
+        let (partition_count, partition_size) = if version >= TOC_VERSION_PARTITION_SIZE && partition_size > 0 {
+            (partition_count.max(1), partition_size)
+        } else {
+            (1, u64::MAX)
+        };
+
+        Ok(Self {
+            version,
+            compression_block_size,
+            partition_count,
+            partition_size,
+            chunk_ids,
+            chunks,
+            compression_blocks,
+            compression_methods,
+            files,
+        })
+    }
+
+    /// Number of .ucas files the container is split into - partition 0 is `{name}.ucas`, and partition n `{name}_s{n}.ucas`
+    pub fn partition_count(&self) -> u32 {
+        self.partition_count
+    }
+
+    /// Paths of the files in the container's directory index, starting with its mount point
+    pub fn files(&self) -> impl Iterator<Item = &str> {
+        self.files.iter().map(|(path, _)| path.as_str())
+    }
+
+    /// Paths of the packages in the container - files holding export data, rather than e.g. bulk data
+    pub fn packages(&self) -> impl Iterator<Item = &str> {
+        // The chunk types changed along with the toc layout in UE5
+        let export_bundle_data = match self.version {
+            v if v >= TOC_VERSION_PERFECT_HASH => CHUNK_TYPE_EXPORT_BUNDLE_DATA_UE5,
+            _ => CHUNK_TYPE_EXPORT_BUNDLE_DATA_UE4,
+        };
+        self.files.iter()
+            .filter(move |(_, chunk_index)| self.chunk_ids[*chunk_index].chunk_type() == export_bundle_data)
+            .map(|(path, _)| path.as_str())
+    }
+
+    /// Reads a file out of the container's .ucas partitions, given in partition order.  The path can be given with or
+    /// without the mount point.
+    pub fn read_file<R: Read + Seek>(&self, path: &str, partitions: &mut [R]) -> Result<Vec<u8>, Box<dyn Error>> {
+        let (_, chunk_index) = self.files.iter().find(|(file, _)| path_matches(file, path))
+            .ok_or(format!("{path} isn't in the container"))?;
+        self.read_chunk(*chunk_index, partitions)
+    }
+
+    /// Reads and decompresses every block the chunk overlaps, then cuts the chunk out of them
+    pub(crate) fn read_chunk<R: Read + Seek>(&self, chunk_index: usize, partitions: &mut [R]) -> Result<Vec<u8>, Box<dyn Error>> {
+        let (offset, length) = self.chunks[chunk_index];
+        if length == 0 {
+            return Ok(vec![]);
+        }
+        let block_size = self.compression_block_size as u64;
+        let first_block = (offset / block_size) as usize;
+        let last_block = ((offset + length - 1) / block_size) as usize;
+        let blocks = self.compression_blocks.get(first_block..=last_block)
+            .ok_or(format!("Chunk {chunk_index} runs past the container's compression blocks"))?;
+
+        let mut bytes = vec![];
+        for block in blocks {
+            let partition = (block.offset / self.partition_size) as usize;
+            let partition_count = partitions.len();
+            let reader = partitions.get_mut(partition)
+                .ok_or(format!("Chunk {chunk_index} is in partition {partition}, but only {partition_count} were given"))?;
+            reader.seek(SeekFrom::Start(block.offset % self.partition_size))?;
+            let mut compressed = vec![0; block.compressed_size as usize];
+            reader.read_exact(&mut compressed)?;
+
+            let method = match block.method_index {
+                0 => "None",
+                i => self.compression_methods.get(i as usize - 1).ok_or(format!("Invalid compression method index {i}"))?,
+            };
+            bytes.extend(compression::decompress(method, &compressed, block.uncompressed_size as usize)?);
+        }
+
+        let start = (offset % block_size) as usize;
+        let chunk = bytes.get(start..start + length as usize).ok_or(format!("Chunk {chunk_index} runs past the end of its blocks"))?;
+        Ok(chunk.to_vec())
+    }
+}
+
+impl IoStoreContainer {
+    /// Packs encoded UE4 packages into a new container named `name` (its file stem, e.g. MyMod_P), writing the .utoc,
+    /// .ucas and the empty .pak the engine needs to mount it.  Each package is given with its path under the
+    /// ../../../ mount point, e.g. MyGame/Content/Data/MyTable.uasset.  Chunks are stored uncompressed.
+    pub fn pack<U: Write, C: Write, P: Write>(name: &str, packages: &[(String, Vec<u8>)], utoc: &mut U, ucas: &mut C, pak: &mut P) -> Result<(), Box<dyn Error>> {
+        let container_id = package_id(name);
+        let mut chunk_ids = vec![];
+        let mut chunk_data = vec![];
+        let mut files = vec![];
+        let mut package_ids = vec![];
+        let mut store_entries = vec![];
+        for (path, bytes) in packages {
+            let summary = UObjectSummary::from_buffer::<_, LE>(&mut Cursor::new(bytes), None)
+                .map_err(|e| format!("Unable to read the package summary of {path}: {e}"))?;
+            if summary.engine_version().is_zen() {
+                Err(format!("{path} is a {} package - only UE4 containers can be packed", summary.engine_version()))?;
+            }
+            let package_name = summary.package_name()?;
+            let package_id = package_id(package_name);
+            if package_ids.contains(&package_id) {
+                Err(format!("{package_name} is packed more than once"))?;
+            }
+
+            files.push((path.trim_start_matches('/').to_string(), chunk_ids.len() as u32));
+            chunk_ids.push(IoChunkId::new(package_id, 0, CHUNK_TYPE_EXPORT_BUNDLE_DATA_UE4));
+            chunk_data.push(bytes.as_slice());
+            package_ids.push(package_id);
+            store_entries.push((bytes.len() as u64, summary.export_map().len() as i32, summary.export_bundle_count() as i32, summary.imported_package_ids()?));
+        }
+
+        // FContainerHeader, whose store entries are followed by the imported package ids they point to
+        const STORE_ENTRY_SIZE: usize = 32;
+        let mut entries = vec![];
+        let mut imported_package_ids = vec![];
+        for (i, (export_bundles_size, export_count, export_bundle_count, imports)) in store_entries.iter().enumerate() {
+            entries.write_u64::<LE>(*export_bundles_size)?;
+            entries.write_i32::<LE>(*export_count)?;
+            entries.write_i32::<LE>(*export_bundle_count)?;
+            entries.write_u32::<LE>(0)?; // Load order
+            entries.write_u32::<LE>(0)?;
+            entries.write_u32::<LE>(imports.len() as u32)?;
+            let view_position = i * STORE_ENTRY_SIZE + STORE_ENTRY_SIZE - 4;
+            let data_position = store_entries.len() * STORE_ENTRY_SIZE + imported_package_ids.len() * 8;
+            entries.write_u32::<LE>(match imports.is_empty() {
+                true => 0,
+                false => (data_position - view_position) as u32,
+            })?;
+            imported_package_ids.extend(imports);
+        }
+        for package_id in imported_package_ids {
+            entries.write_u64::<LE>(package_id)?;
+        }
+        let mut container_header = vec![];
+        container_header.write_u64::<LE>(container_id)?;
+        container_header.write_u32::<LE>(packages.len() as u32)?;
+        container_header.write_i32::<LE>(0)?; // Names
+        container_header.write_i32::<LE>(0)?; // Name hashes
+        container_header.write_i32::<LE>(package_ids.len() as i32)?;
+        for package_id in &package_ids {
+            container_header.write_u64::<LE>(*package_id)?;
+        }
+        container_header.write_i32::<LE>(entries.len() as i32)?;
+        container_header.write_all(&entries)?;
+        container_header.write_i32::<LE>(0)?; // Culture package map
+        container_header.write_i32::<LE>(0)?; // Package redirects
+        chunk_ids.push(IoChunkId::new(container_id, 0, CHUNK_TYPE_CONTAINER_HEADER_UE4));
+        chunk_data.push(container_header.as_slice());
+
+        // Every chunk starts on a new block
+        let block_size = PACK_COMPRESSION_BLOCK_SIZE as usize;
+        let mut chunks = vec![];
+        let mut compression_blocks = vec![];
+        let mut ucas_offset = 0;
+        for data in &chunk_data {
+            chunks.push(((compression_blocks.len() * block_size) as u64, data.len() as u64));
+            for block in data.chunks(block_size) {
+                ucas.write_all(block)?;
+                compression_blocks.push(CompressionBlock {
+                    offset: ucas_offset,
+                    compressed_size: block.len() as u32,
+                    uncompressed_size: block.len() as u32,
+                    method_index: 0,
+                });
+                ucas_offset += block.len() as u64;
+            }
+        }
+
+        let mut directory_index = vec![];
+        write_directory_index(&mut directory_index, PACK_MOUNT_POINT, &files)?;
+
+        // FIoStoreTocHeader
+        utoc.write_all(TOC_MAGIC)?;
+        utoc.write_u8(PACK_TOC_VERSION)?;
+        utoc.write_u8(0)?;
+        utoc.write_u16::<LE>(0)?;
+        utoc.write_u32::<LE>(TOC_HEADER_SIZE)?;
+        utoc.write_u32::<LE>(chunk_ids.len() as u32)?;
+        utoc.write_u32::<LE>(compression_blocks.len() as u32)?;
+        utoc.write_u32::<LE>(COMPRESSION_BLOCK_ENTRY_SIZE)?;
+        utoc.write_u32::<LE>(0)?; // Compression method count
+        utoc.write_u32::<LE>(COMPRESSION_METHOD_NAME_LEN)?;
+        utoc.write_u32::<LE>(PACK_COMPRESSION_BLOCK_SIZE)?;
+        utoc.write_u32::<LE>(directory_index.len() as u32)?;
+        utoc.write_u32::<LE>(1)?; // Partition count
+        utoc.write_u64::<LE>(container_id)?;
+        utoc.write_all(&[0; 16])?; // Encryption key guid
+        utoc.write_u8(CONTAINER_FLAG_INDEXED)?;
+        utoc.write_u8(0)?;
+        utoc.write_u16::<LE>(0)?;
+        utoc.write_u32::<LE>(0)?;
+        utoc.write_u64::<LE>(u64::MAX)?; // Partition size
+        utoc.write_all(&[0; 48])?;
+
+        for chunk_id in &chunk_ids {
+            utoc.write_all(&chunk_id.0)?;
+        }
+        for (offset, length) in &chunks {
+            utoc.write_uint::<byteorder::BE>(*offset, 5)?;
+            utoc.write_uint::<byteorder::BE>(*length, 5)?;
+        }
+        for block in &compression_blocks {
+            utoc.write_uint::<LE>(block.offset, 5)?;
+            utoc.write_uint::<LE>(block.compressed_size as u64, 3)?;
+            utoc.write_uint::<LE>(block.uncompressed_size as u64, 3)?;
+            utoc.write_u8(block.method_index)?;
+        }
+        utoc.write_all(&directory_index)?;
+        // FIoStoreTocEntryMeta - a SHA1 chunk hash padded to 32 bytes, and flags
+        for data in &chunk_data {
+            utoc.write_all(&sha1(data))?;
+            utoc.write_all(&[0; 12])?;
+            utoc.write_u8(0)?;
+        }
+
+        write_companion_pak(pak, PACK_MOUNT_POINT)
+    }
+}
+
+/// Whether a path from an index matches one given by the user, who may leave off the mount point (e.g. ../../../)
+pub(crate) fn path_matches(indexed_path: &str, path: &str) -> bool {
+    let relative = |p: &str| p.trim_start_matches("../").trim_start_matches('/').to_string();
+    indexed_path == path || relative(indexed_path) == relative(path)
+}
+
+fn skip<R: Read>(reader: &mut R, len: usize) -> Result<(), Box<dyn Error>> {
+    let skipped = io::copy(&mut reader.take(len as u64), &mut io::sink())?;
+    if skipped != len as u64 {
+        Err("Unexpected end of .utoc")?;
+    }
+    Ok(())
+}
+
+/// Reads `len` bytes through `take`, so a length past the end of the .utoc is an error rather than an allocation
+fn read_bytes<R: Read>(reader: &mut R, len: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut bytes = vec![];
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        Err("Unexpected end of .utoc")?;
+    }
+    Ok(bytes)
+}
+
+/// Writes an FIoDirectoryIndexResource for files given by their path under the mount point and chunk index
+fn write_directory_index<W: Write>(writer: &mut W, mount_point: &str, files: &[(String, u32)]) -> Result<(), Box<dyn Error>> {
+    fn string_index(strings: &mut Vec<String>, string: &str) -> u32 {
+        match strings.iter().position(|s| s == string) {
+            Some(index) => index as u32,
+            None => {
+                strings.push(string.to_string());
+                strings.len() as u32 - 1
+            }
+        }
+    }
+
+    struct Directory {
+        name: u32,
+        children: Vec<usize>,
+        files: Vec<(u32, u32)>,
+    }
+    let mut strings = vec![];
+    let mut directories = vec![Directory { name: NONE_INDEX, children: vec![], files: vec![] }];
+    for (path, chunk_index) in files {
+        let (directory_path, file_name) = path.rsplit_once('/').unwrap_or(("", path));
+        let mut directory = 0;
+        for part in directory_path.split('/').filter(|p| !p.is_empty()) {
+            let name = string_index(&mut strings, part);
+            directory = match directories[directory].children.iter().find(|c| directories[**c].name == name) {
+                Some(child) => *child,
+                None => {
+                    let child = directories.len();
+                    directories.push(Directory { name, children: vec![], files: vec![] });
+                    directories[directory].children.push(child);
+                    child
+                }
+            };
+        }
+        let name = string_index(&mut strings, file_name);
+        directories[directory].files.push((name, *chunk_index));
+    }
+
+    let mut next_siblings = vec![NONE_INDEX; directories.len()];
+    for directory in &directories {
+        for siblings in directory.children.windows(2) {
+            next_siblings[siblings[0]] = siblings[1] as u32;
+        }
+    }
+
+    write_fstring::<_, LE>(writer, mount_point)?;
+    writer.write_u32::<LE>(directories.len() as u32)?;
+    let mut file_count = 0;
+    for (directory, next_sibling) in directories.iter().zip(next_siblings) {
+        writer.write_u32::<LE>(directory.name)?;
+        writer.write_u32::<LE>(directory.children.first().map(|c| *c as u32).unwrap_or(NONE_INDEX))?;
+        writer.write_u32::<LE>(next_sibling)?;
+        writer.write_u32::<LE>(if directory.files.is_empty() { NONE_INDEX } else { file_count })?;
+        file_count += directory.files.len() as u32;
+    }
+    writer.write_u32::<LE>(file_count)?;
+    let mut file_index = 0;
+    for directory in &directories {
+        for (i, (name, chunk_index)) in directory.files.iter().enumerate() {
+            file_index += 1;
+            writer.write_u32::<LE>(*name)?;
+            writer.write_u32::<LE>(if i + 1 == directory.files.len() { NONE_INDEX } else { file_index })?;
+            writer.write_u32::<LE>(*chunk_index)?;
+        }
+    }
+    writer.write_u32::<LE>(strings.len() as u32)?;
+    for string in strings {
+        write_fstring::<_, LE>(writer, &string)?;
+    }
+    Ok(())
+}
+
+/// Reads an FIoDirectoryIndexResource, returning the full path of every file and its chunk index
+fn read_directory_index<R: Read + Seek>(reader: &mut R) -> Result<Vec<(String, usize)>, Box<dyn Error>> {
+    let (mount_point, _) = read_fstring::<_, LE>(reader)?;
+
+    // (name, first child, next sibling, first file)
+    let directory_count = reader.read_u32::<LE>()? as usize;
+    let mut directories = vec![];
+    for _ in 0..directory_count {
+        directories.push((reader.read_u32::<LE>()?, reader.read_u32::<LE>()?, reader.read_u32::<LE>()?, reader.read_u32::<LE>()?));
+    }
+
+    // (name, next file, chunk index)
+    let file_count = reader.read_u32::<LE>()? as usize;
+    let mut file_entries = vec![];
+    for _ in 0..file_count {
+        file_entries.push((reader.read_u32::<LE>()?, reader.read_u32::<LE>()?, reader.read_u32::<LE>()?));
+    }
+
+    let string_count = reader.read_u32::<LE>()? as usize;
+    let mut strings = vec![];
+    for _ in 0..string_count {
+        strings.push(read_fstring::<_, LE>(reader)?.0);
+    }
+    let string = |index: u32| strings.get(index as usize).ok_or(format!("Invalid directory index string {index}"));
+
+    let mut files = vec![];
+    if directories.is_empty() {
+        return Ok(files);
+    }
+    // Every entry is visited once, so anything past that means the index loops back on itself
+    let mut visits = 0;
+    let mut pending = vec![(0u32, mount_point)];
+    while let Some((directory_index, parent_path)) = pending.pop() {
+        visits += 1;
+        if visits > directories.len() + file_entries.len() {
+            Err("Invalid directory index: entries form a loop")?;
+        }
+        let (name, first_child, _, first_file) = *directories.get(directory_index as usize)
+            .ok_or(format!("Invalid directory index entry {directory_index}"))?;
+        let path = match name {
+            NONE_INDEX => parent_path,
+            name => format!("{parent_path}{}/", string(name)?),
+        };
+
+        let mut file_index = first_file;
+        while file_index != NONE_INDEX {
+            visits += 1;
+            if visits > directories.len() + file_entries.len() {
+                Err("Invalid directory index: entries form a loop")?;
+            }
+            let (name, next_file, chunk_index) = *file_entries.get(file_index as usize)
+                .ok_or(format!("Invalid directory index file {file_index}"))?;
+            files.push((format!("{path}{}", string(name)?), chunk_index as usize));
+            file_index = next_file;
+        }
+
+        let mut child_index = first_child;
+        while child_index != NONE_INDEX {
+            pending.push((child_index, path.clone()));
+            child_index = directories.get(child_index as usize).ok_or(format!("Invalid directory index entry {child_index}"))?.2;
+            if pending.len() > directories.len() {
+                Err("Invalid directory index: entries form a loop")?;
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[allow(dead_code)]
+#[allow(unused_imports)]
+mod test {
+    use byteorder::{WriteBytesExt, BE, LE};
+    use flate2::{write::ZlibEncoder, Compression};
+    use std::io::{Cursor, Read, Write};
+
+    use super::{read_directory_index, write_directory_index, IoStoreContainer, CHUNK_TYPE_CONTAINER_HEADER_UE4, NONE_INDEX, TOC_MAGIC};
+    use crate::iostore_uasset::{package_id, test::get_test_object, IoUObject};
+    use crate::legacy_uasset::write_fstring;
+
+    const BLOCK_SIZE: usize = 0x20;
+
+    /// A version 3 (4.27) .utoc and its .ucas, holding ../../../Game/Data/DT_Test.uasset and a .ubulk next to it.
+    /// Blocks alternate between zlib and uncompressed.
+    fn get_test_container() -> (Vec<u8>, Vec<u8>, Vec<u8>, Vec<u8>) {
+        let package: Vec<u8> = (0..0x30).collect();
+        let bulk_data: Vec<u8> = (0..0x10).map(|i| 0xff - i).collect();
+
+        let mut ucas = vec![];
+        let mut blocks = vec![];
+        for (i, block) in package.chunks(BLOCK_SIZE).chain(bulk_data.chunks(BLOCK_SIZE)).enumerate() {
+            let offset = ucas.len();
+            if i % 2 == 0 {
+                let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+                encoder.write_all(block).unwrap();
+                ucas.extend(encoder.finish().unwrap());
+                blocks.push((offset, ucas.len() - offset, block.len(), 1u8));
+            } else {
+                ucas.extend(block);
+                blocks.push((offset, block.len(), block.len(), 0u8));
+            }
+        }
+
+        let mut directory_index = vec![];
+        write_fstring::<_, LE>(&mut directory_index, "../../../").unwrap();
+        let directories = [(NONE_INDEX, 1, NONE_INDEX, NONE_INDEX), (0, 2, NONE_INDEX, NONE_INDEX), (1, NONE_INDEX, NONE_INDEX, 0)];
+        directory_index.write_u32::<LE>(directories.len() as u32).unwrap();
+        for (name, first_child, next_sibling, first_file) in directories {
+            for value in [name, first_child, next_sibling, first_file] {
+                directory_index.write_u32::<LE>(value).unwrap();
+            }
+        }
+        let files = [(2, 1, 0), (3, NONE_INDEX, 1)];
+        directory_index.write_u32::<LE>(files.len() as u32).unwrap();
+        for (name, next_file, chunk_index) in files {
+            for value in [name, next_file, chunk_index] {
+                directory_index.write_u32::<LE>(value).unwrap();
+            }
+        }
+        let strings = ["Game", "Data", "DT_Test.uasset", "DT_Test.ubulk"];
+        directory_index.write_u32::<LE>(strings.len() as u32).unwrap();
+        for string in strings {
+            write_fstring::<_, LE>(&mut directory_index, string).unwrap();
+        }
+
+        let mut utoc = vec![];
+        utoc.write_all(TOC_MAGIC).unwrap();
+        utoc.write_u8(3).unwrap();
+        utoc.write_all(&[0; 3]).unwrap();
+        for value in [144, 2, blocks.len() as u32, 12, 1, 32, BLOCK_SIZE as u32, directory_index.len() as u32, 1] {
+            utoc.write_u32::<LE>(value).unwrap();
+        }
+        utoc.write_u64::<LE>(0x1234).unwrap();
+        utoc.write_all(&[0; 16]).unwrap();
+        utoc.write_u8(0x1 | 0x8).unwrap();
+        utoc.write_all(&[0; 3]).unwrap();
+        utoc.write_u32::<LE>(0).unwrap();
+        utoc.write_u64::<LE>(u64::MAX).unwrap();
+        utoc.write_all(&[0; 48]).unwrap();
+        assert_eq!(utoc.len(), 144);
+
+        for (id, chunk_type) in [(0xaau64, 2u8), (0xaa, 3)] {
+            utoc.write_u64::<LE>(id).unwrap();
+            utoc.write_u16::<BE>(0).unwrap();
+            utoc.write_u8(0).unwrap();
+            utoc.write_u8(chunk_type).unwrap();
+        }
+        for (offset, length) in [(0u64, package.len() as u64), (0x40, bulk_data.len() as u64)] {
+            utoc.write_uint::<BE>(offset, 5).unwrap();
+            utoc.write_uint::<BE>(length, 5).unwrap();
+        }
+        for (offset, compressed_size, uncompressed_size, method_index) in blocks {
+            utoc.write_uint::<LE>(offset as u64, 5).unwrap();
+            utoc.write_uint::<LE>(compressed_size as u64, 3).unwrap();
+            utoc.write_uint::<LE>(uncompressed_size as u64, 3).unwrap();
+            utoc.write_u8(method_index).unwrap();
+        }
+        let mut method_name = b"Zlib".to_vec();
+        method_name.resize(32, 0);
+        utoc.write_all(&method_name).unwrap();
+        utoc.write_all(&directory_index).unwrap();
+
+        (utoc, ucas, package, bulk_data)
+    }
+
+    #[test]
+    fn container_lists_packages() {
+        let (utoc, _, _, _) = get_test_container();
+        let container = IoStoreContainer::from_buffer(&mut Cursor::new(utoc)).unwrap();
+        assert_eq!(container.files().collect::<Vec<_>>(), vec!["../../../Game/Data/DT_Test.uasset", "../../../Game/Data/DT_Test.ubulk"]);
+        assert_eq!(container.packages().collect::<Vec<_>>(), vec!["../../../Game/Data/DT_Test.uasset"]);
+        assert_eq!(container.partition_count(), 1);
+    }
+
+    #[test]
+    fn container_files_are_decompressed() {
+        let (utoc, ucas, package, bulk_data) = get_test_container();
+        let container = IoStoreContainer::from_buffer(&mut Cursor::new(utoc)).unwrap();
+        let mut partitions = [Cursor::new(ucas)];
+        assert_eq!(container.read_file("../../../Game/Data/DT_Test.uasset", &mut partitions).unwrap(), package);
+        assert_eq!(container.read_file("Game/Data/DT_Test.ubulk", &mut partitions).unwrap(), bulk_data);
+        assert!(container.read_file("Game/Data/Missing.uasset", &mut partitions).is_err());
+    }
+
+    #[test]
+    fn oversized_counts_are_errors() {
+        let (valid, _, _, _) = get_test_container();
+        // Entry count, compression block count, method name length and directory index size in the header
+        for (offset, value, expected) in [(24, u32::MAX, None), (28, u32::MAX, None), (40, 16, Some("Unexpected compression method name length 16, expected 32")), (48, u32::MAX, None)] {
+            let mut utoc = valid.clone();
+            utoc[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+            let err = IoStoreContainer::from_buffer(&mut Cursor::new(utoc)).err().unwrap();
+            if let Some(expected) = expected {
+                assert_eq!(err.to_string(), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn unregistered_compression_methods_are_reported() {
+        let (mut utoc, ucas, _, _) = get_test_container();
+        let method_name_offset = utoc.windows(4).position(|w| w == b"Zlib").unwrap();
+        utoc[method_name_offset..method_name_offset + 5].copy_from_slice(b"Brrr\0");
+        let container = IoStoreContainer::from_buffer(&mut Cursor::new(utoc)).unwrap();
+        let err = container.read_file("Game/Data/DT_Test.uasset", &mut [Cursor::new(ucas.clone())]).unwrap_err();
+        assert_eq!(err.to_string(), "No decompressor is registered for Brrr compression");
+
+        crate::compression::register_decompressor("brrr", |compressed, _| {
+            let mut bytes = vec![];
+            flate2::read::ZlibDecoder::new(compressed).read_to_end(&mut bytes)?;
+            Ok(bytes)
+        });
+        assert!(container.read_file("Game/Data/DT_Test.uasset", &mut [Cursor::new(ucas)]).is_ok());
+    }
+
+    fn pack_test_packages(packages: &[(String, Vec<u8>)]) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let (mut utoc, mut ucas, mut pak) = (vec![], vec![], vec![]);
+        IoStoreContainer::pack("MyMod_P", packages, &mut utoc, &mut ucas, &mut pak).unwrap();
+        (utoc, ucas, pak)
+    }
+
+    #[test]
+    fn packed_containers_can_be_read() {
+        let mut package = vec![];
+        get_test_object().to_bytes::<_, LE>(&mut package).unwrap();
+        let (utoc, ucas, pak) = pack_test_packages(&[("MyGame/Content/Data/DT_Test.uasset".to_string(), package.clone())]);
+
+        let container = IoStoreContainer::from_buffer(&mut Cursor::new(utoc)).unwrap();
+        assert_eq!(container.packages().collect::<Vec<_>>(), vec!["../../../MyGame/Content/Data/DT_Test.uasset"]);
+        let mut partitions = [Cursor::new(ucas)];
+        let bytes = container.read_file("MyGame/Content/Data/DT_Test.uasset", &mut partitions).unwrap();
+        assert_eq!(bytes, package);
+        assert!(IoUObject::from_buffer::<_, LE>(&mut Cursor::new(bytes)).is_ok());
+
+        let container_header = container.read_chunk(1, &mut partitions).unwrap();
+        assert_eq!(container.chunk_ids[1].chunk_type(), CHUNK_TYPE_CONTAINER_HEADER_UE4);
+        assert_eq!(container_header[..8], package_id("MyMod_P").to_le_bytes());
+        assert_eq!(container_header[8..12], 1u32.to_le_bytes()); // Package count
+        assert_eq!(container_header[20..24], 1u32.to_le_bytes());
+        assert_eq!(container_header[24..32], package_id("ArrayProperty").to_le_bytes());
+        assert_eq!(container_header[32..36], 32u32.to_le_bytes()); // One store entry
+        assert_eq!(container_header[36..44], (package.len() as u64).to_le_bytes());
+
+        let footer = &pak[pak.len() - 221..];
+        assert_eq!(footer[17..21], 0x5A6F12E1u32.to_le_bytes());
+        assert_eq!(footer[21..25], 11i32.to_le_bytes());
+    }
+
+    #[test]
+    fn packed_packages_must_be_unique() {
+        let mut package = vec![];
+        get_test_object().to_bytes::<_, LE>(&mut package).unwrap();
+        let packages = [("MyGame/Content/A.uasset".to_string(), package.clone()), ("MyGame/Content/B.uasset".to_string(), package)];
+        let err = IoStoreContainer::pack("MyMod_P", &packages, &mut vec![], &mut vec![], &mut vec![]).unwrap_err();
+        assert_eq!(err.to_string(), "ArrayProperty is packed more than once");
+    }
+
+    #[test]
+    fn directory_index_round_trips() {
+        let files = [
+            ("MyGame/Content/Data/A.uasset".to_string(), 0),
+            ("MyGame/Content/B.uasset".to_string(), 1),
+            ("MyGame/Content/Data/C.uasset".to_string(), 2),
+            ("MyGame/Content/Maps/D.umap".to_string(), 3),
+        ];
+        let mut directory_index = vec![];
+        write_directory_index(&mut directory_index, "../../../", &files).unwrap();
+        let read_files = read_directory_index(&mut Cursor::new(directory_index)).unwrap();
+        assert_eq!(read_files, vec![
+            ("../../../MyGame/Content/B.uasset".to_string(), 1),
+            ("../../../MyGame/Content/Data/A.uasset".to_string(), 0),
+            ("../../../MyGame/Content/Data/C.uasset".to_string(), 2),
+            ("../../../MyGame/Content/Maps/D.umap".to_string(), 3),
+        ]);
+    }
+}
@@ -1,7 +1,139 @@
 use base64::{prelude::BASE64_STANDARD, Engine};
 use byteorder::{ReadBytesExt, WriteBytesExt, LE};
-use std::{cmp::Ordering, error::Error, fmt::Display, io::{BufRead, Cursor, Read, Seek, SeekFrom, Write}};
+use crate::cityhash::city_hash_64;
+use crate::error::{EncodeError, ParseError, ParseRead, TextError, TextErrors, TextRead};
+use crate::struct_codecs::{find_struct_codec, StructCodec, StructFieldValue};
+use crate::unversioned;
+use crate::usmap::{script_import_hash, Mappings};
+use serde::{ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer};
+use std::{cmp::Ordering, error::Error, fmt::Display, io::{BufRead, Cursor, Read, Seek, SeekFrom, Write}, str::FromStr};
+
+/// Engine versions whose packages can be read and written.  IoStore packages from before UE4.27 store their names
+/// in a global name map instead of the package summary, so 4.25 and 4.26 are only supported for legacy packages.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EngineVersion {
+    Ue4_25,
+    Ue4_26,
+    Ue4_27,
+    Ue5, // FZenPackageSummary, as cooked by UE5.0 and 5.1
+}
+
+/// Tagged property layout details that can differ between engine versions
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PropertyLayout {
+    /// Bytes after each tag's type-specific metadata (FPropertyTag::HasPropertyGuid)
+    pub tag_suffix_len: usize,
+    /// StructProperty tag metadata - the struct name and guid, then the tag suffix
+    pub struct_metadata_len: usize,
+    /// Bytes after the struct name in the inner tag of an array of structs (its struct guid and tag suffix)
+    pub array_struct_padding_len: usize,
+    /// Map data counted in its tag's size ahead of the entries - the number of keys to remove and of entries
+    pub map_header_len: usize,
+}
+
+impl EngineVersion {
+    pub fn property_layout(self) -> PropertyLayout {
+        match self {
+            Self::Ue4_25 => PropertyLayout {
+                tag_suffix_len: 1,
+                struct_metadata_len: 8 + 16 + 1,
+                array_struct_padding_len: 16 + 1,
+                map_header_len: 4 + 4,
+            },
+            Self::Ue4_26 => PropertyLayout {
+                tag_suffix_len: 1,
+                struct_metadata_len: 8 + 16 + 1,
+                array_struct_padding_len: 16 + 1,
+                map_header_len: 4 + 4,
+            },
+            Self::Ue4_27 => PropertyLayout {
+                tag_suffix_len: 1,
+                struct_metadata_len: 8 + 16 + 1,
+                array_struct_padding_len: 16 + 1,
+                map_header_len: 4 + 4,
+            },
+            Self::Ue5 => PropertyLayout {
+                tag_suffix_len: 1,
+                struct_metadata_len: 8 + 16 + 1,
+                array_struct_padding_len: 16 + 1,
+                map_header_len: 4 + 4,
+            },
+        }
+    }
+
+    /// Checks IoStore packages can be read and written as this version.  4.25 and 4.26 IoStore packages keep their
+    /// names in the container's global name map rather than the package summary, which isn't supported.
+    pub fn check_iostore_summary(self) -> Result<(), String> {
+        match self {
+            Self::Ue4_25 | Self::Ue4_26 => Err(format!("{self} IoStore packages keep their names in the container's global name map, which isn't supported - IoStore packages have to be 4.27 or 5.x")),
+            Self::Ue4_27 | Self::Ue5 => Ok(()),
+        }
+    }
+
+    /// Whether packages use the UE5 Zen summary rather than the UE4 IoStore one
+    pub fn is_zen(self) -> bool {
+        self == Self::Ue5
+    }
 
+    /// Guesses the package layout from its first bytes, leaving the reader where it started.  The UE4.27 summary
+    /// is a fixed 64 bytes with the name map right after it, while the UE5 summary starts with a versioning flag.
+    pub fn detect<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R) -> Result<Self, ParseError> {
+        let start_position = reader.offset();
+        let mut start = [0;28];
+        let read = reader.read_exact(&mut start);
+        reader.seek(SeekFrom::Start(start_position)).map_err(|err| ParseError::invalid(start_position, err.to_string()))?;
+        read.map_err(|_| ParseError::invalid(start_position, "Package is too short to hold a package summary"))?;
+
+        if E::read_i32(&start[24..]) == 64 {
+            Ok(Self::Ue4_27)
+        } else if E::read_u32(&start[..4]) <= 1 {
+            Ok(Self::Ue5)
+        } else {
+            Err(ParseError::invalid(start_position, "Unable to detect the engine version of the package summary - try passing one with --engine"))
+        }
+    }
+}
+
+impl Display for EngineVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ue4_25 => f.write_str("4.25"),
+            Self::Ue4_26 => f.write_str("4.26"),
+            Self::Ue4_27 => f.write_str("4.27"),
+            Self::Ue5 => f.write_str("5.x"),
+        }
+    }
+}
+
+impl FromStr for EngineVersion {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "4.25" => Ok(Self::Ue4_25),
+            "4.26" => Ok(Self::Ue4_26),
+            "4.27" => Ok(Self::Ue4_27),
+            "5" | "5.x" | "5.0" | "5.1" => Ok(Self::Ue5),
+            other => Err(format!("Unsupported engine version [{other}] - expected one of: 4.25, 4.26, 4.27, 5.x"))?
+        }
+    }
+}
+
+impl Serialize for EngineVersion {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        display_string::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for EngineVersion {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        display_string::deserialize(deserializer)
+    }
+}
+
+/// FPackageSummary (UE4.27 layout).  UE5 Zen summaries are read into the fields the two share, with the name map
+/// offset and size describing the name batch, and the rest kept in a [`ZenSummary`].
+#[derive(Clone, Serialize, Deserialize)]
 struct UObjectSummaryHeader {
     name: u64,     
     source_name: u64,
@@ -20,21 +152,23 @@ struct UObjectSummaryHeader {
 }
 
 impl UObjectSummaryHeader {
-    pub fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R) -> Result<Self, Box<dyn Error>> {
-        let name = reader.read_u64::<E>().unwrap();
-        let source_name = reader.read_u64::<E>().unwrap();
-        let package_flags = reader.read_u32::<E>().unwrap();
-        let cooked_header_size = reader.read_u32::<E>().unwrap();
-        let name_map_names_offset = reader.read_i32::<E>().unwrap();
-        let name_map_names_size = reader.read_i32::<E>().unwrap();
-        let name_map_hashes_offset = reader.read_i32::<E>().unwrap();
-        let name_map_hashes_size = reader.read_i32::<E>().unwrap();
-        let import_map_offset = reader.read_i32::<E>().unwrap();
-        let export_map_offset = reader.read_i32::<E>().unwrap();
-        let export_bundles_offset = reader.read_i32::<E>().unwrap();
-        let graph_data_offset = reader.read_i32::<E>().unwrap();
-        let graph_data_size = reader.read_i32::<E>().unwrap();
-        reader.read_u32::<E>().unwrap(); //move reader past padding
+    const BYTE_LEN: usize = 64;
+
+    pub fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R) -> Result<Self, ParseError> {
+        let name = reader.parse_u64::<E>()?;
+        let source_name = reader.parse_u64::<E>()?;
+        let package_flags = reader.parse_u32::<E>()?;
+        let cooked_header_size = reader.parse_u32::<E>()?;
+        let name_map_names_offset = reader.parse_i32::<E>()?;
+        let name_map_names_size = reader.parse_i32::<E>()?;
+        let name_map_hashes_offset = reader.parse_i32::<E>()?;
+        let name_map_hashes_size = reader.parse_i32::<E>()?;
+        let import_map_offset = reader.parse_i32::<E>()?;
+        let export_map_offset = reader.parse_i32::<E>()?;
+        let export_bundles_offset = reader.parse_i32::<E>()?;
+        let graph_data_offset = reader.parse_i32::<E>()?;
+        let graph_data_size = reader.parse_i32::<E>()?;
+        reader.parse_u32::<E>()?; //move reader past padding
 
         Ok(Self {
             name,
@@ -76,64 +210,861 @@ impl UObjectSummaryHeader {
     }
 }
 
-enum StringType {
+#[derive(Clone)]
+pub(crate) enum StringType {
     Utf8,
     Utf16,
 }
 
+impl StringType {
+    /// How the engine stores a new string - one byte per character if it's ASCII, or UTF-16
+    pub(crate) fn for_string(value: &str) -> Self {
+        if value.is_ascii() { Self::Utf8 } else { Self::Utf16 }
+    }
+}
+
+/// Identifies the hash algorithm used for name map hashes, stored ahead of the hashes themselves
+const NAME_HASH_ALGORITHM_ID: u64 = 0xC1640000;
+
+/// FPackageId of a package name, which is also how FIoContainerId is found from a container name
+pub(crate) fn package_id(package_name: &str) -> u64 {
+    name_hash(package_name, &StringType::Utf16)
+}
+
+/// Lowercase CityHash64 of a name, as stored in the name map hashes
+fn name_hash(name: &str, string_type: &StringType) -> u64 {
+    match string_type {
+        StringType::Utf8 => city_hash_64(name.to_ascii_lowercase().as_bytes()),
+        StringType::Utf16 => {
+            let bytes: Vec<u8> = name.chars()
+                .flat_map(char::to_lowercase)
+                .collect::<String>()
+                .encode_utf16()
+                .flat_map(u16::to_le_bytes)
+                .collect();
+            city_hash_64(&bytes)
+        }
+    }
+}
+
+
+/// The parts of a UE5 FZenPackageSummary with no equivalent in the UE4.27 summary header
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+struct ZenSummary {
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "optional_base64_string")]
+    versioning_info: Option<Vec<u8>>, // FZenPackageVersioningInfo, only present in uncooked packages
+    imported_public_export_hashes_offset: i32,
+    imported_public_export_hashes: Vec<u64>,
+}
+
+/// Warns about name hashes that won't survive re-encoding, since they're always regenerated
+fn check_name_hashes(algorithm_id: u64, hashes: &[u64], name_map: &[String], name_map_type: &[StringType]) {
+    if algorithm_id != NAME_HASH_ALGORITHM_ID {
+        eprintln!("WARNING: Unknown name map hash algorithm 0x{algorithm_id:x}  # Hashes will be regenerated as CityHash64 when encoding.");
+    }
+    for ((name, string_type), hash) in name_map.iter().zip(name_map_type).zip(hashes) {
+        if *hash != name_hash(name, string_type) {
+            eprintln!("WARNING: Name map hash for [{name}] doesn't match its CityHash64  # It will be regenerated when encoding.");
+        }
+    }
+}
+
+/// FPackageObjectIndex - the top two bits hold the index type, the rest hold either
+/// a local export index or a hash identifying a script/package import.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub(crate) struct PackageObjectIndex(u64);
+
+impl PackageObjectIndex {
+    const INDEX_BITS: u64 = 62;
+    const INDEX_MASK: u64 = (1 << Self::INDEX_BITS) - 1;
+    pub(crate) const NULL: Self = Self(u64::MAX);
+
+    pub(crate) fn export(export_index: usize) -> Self {
+        Self(export_index as u64)
+    }
+
+    /// The local export index, if this refers to an export
+    pub(crate) fn export_index(&self) -> Option<usize> {
+        (*self != Self::NULL && self.0 >> Self::INDEX_BITS == 0).then_some(self.0 as usize)
+    }
+
+    /// The global index IoStore gives an imported object - a script import if it's in a /Script/ package, or a
+    /// package import otherwise, identified by the hash of its path
+    pub(crate) fn from_import_path(object_path: &str) -> Self {
+        let kind = if object_path.starts_with("/Script/") { 1 } else { 2 };
+        Self((kind << Self::INDEX_BITS) | script_import_hash(object_path))
+    }
+
+    /// The hash of the script object path, if this is a script import
+    pub(crate) fn script_import_hash(&self) -> Option<u64> {
+        (*self != Self::NULL && self.0 >> Self::INDEX_BITS == 1).then_some(self.0 & Self::INDEX_MASK)
+    }
+}
+
+impl Display for PackageObjectIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = self.0 & Self::INDEX_MASK;
+        match self.0 >> Self::INDEX_BITS {
+            _ if *self == Self::NULL => f.write_str("null"),
+            0 => write!(f, "export:{value}"),
+            1 => write!(f, "script:0x{value:x}"),
+            2 => write!(f, "package:0x{value:x}"),
+            _ => write!(f, "0x{:x}", self.0),
+        }
+    }
+}
+
+impl FromStr for PackageObjectIndex {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s == "null" {
+            return Ok(Self::NULL);
+        }
+
+        let (kind, value) = s.split_once(':').unwrap_or(("", s));
+        let value = match value.trim().strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16)?,
+            None => value.trim().parse::<u64>()?,
+        };
+        let kind = match kind.trim() {
+            "" => return Ok(Self(value)),
+            "export" => 0,
+            "script" => 1,
+            "package" => 2,
+            other => Err(format!("Unknown object index type [{other}] - expected one of: null, export, script, package"))?
+        };
+        if value > Self::INDEX_MASK {
+            Err(format!("Object index value [{s}] is too large"))?;
+        }
+        Ok(Self((kind << Self::INDEX_BITS) | value))
+    }
+}
+
+impl Serialize for PackageObjectIndex {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        display_string::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for PackageObjectIndex {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        display_string::deserialize(deserializer)
+    }
+}
+
+/// FExportMapEntry.  UE5 entries have the same layout, with a public export hash in place of the global import index.
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct UObjectExportMapEntry {
+    pub(crate) cooked_serial_offset: u64,
+    pub(crate) cooked_serial_size: u64,
+    pub(crate) object_name: String,
+    pub(crate) object_name_number: u32,
+    pub(crate) outer_index: PackageObjectIndex,
+    pub(crate) class_index: PackageObjectIndex,
+    pub(crate) super_index: PackageObjectIndex,
+    pub(crate) template_index: PackageObjectIndex,
+    pub(crate) global_import_index: PackageObjectIndex, // Public export hash that other packages import this export by
+    pub(crate) object_flags: u32,
+    pub(crate) filter_flags: u8,
+}
+
+impl UObjectExportMapEntry {
+    pub fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R, name_map: &[String]) -> Result<Self, ParseError> {
+        let cooked_serial_offset = reader.parse_u64::<E>()?;
+        let cooked_serial_size = reader.parse_u64::<E>()?;
+        let name_offset = reader.offset();
+        let name_index = reader.parse_u32::<E>()? as u64;
+        let object_name = name_map.get(name_index as usize)
+            .ok_or(ParseError::BadNameIndex { index: name_index, offset: name_offset, path: String::new() })?.clone();
+        let object_name_number = reader.parse_u32::<E>()?;
+        let outer_index = PackageObjectIndex(reader.parse_u64::<E>()?);
+        let class_index = PackageObjectIndex(reader.parse_u64::<E>()?);
+        let super_index = PackageObjectIndex(reader.parse_u64::<E>()?);
+        let template_index = PackageObjectIndex(reader.parse_u64::<E>()?);
+        let global_import_index = PackageObjectIndex(reader.parse_u64::<E>()?);
+        let object_flags = reader.parse_u32::<E>()?;
+        let filter_flags = reader.parse_u8()?;
+        let mut _pad = [0;3];
+        reader.parse_exact(&mut _pad)?;
+
+        Ok(Self {
+            cooked_serial_offset,
+            cooked_serial_size,
+            object_name,
+            object_name_number,
+            outer_index,
+            class_index,
+            super_index,
+            template_index,
+            global_import_index,
+            object_flags,
+            filter_flags,
+        })
+    }
+
+    pub fn to_bytes<W: Write, E: byteorder::ByteOrder>(&self, writer: &mut W, name_map: &[String]) -> Result<usize, EncodeError> {
+        let name_index = name_map.iter().position(|n| n == &self.object_name)
+            .ok_or_else(|| EncodeError::MissingName { name: self.object_name.clone(), path: "object_name".to_owned() })? as u32;
+
+        writer.write_u64::<E>(self.cooked_serial_offset)?;
+        writer.write_u64::<E>(self.cooked_serial_size)?;
+        writer.write_u32::<E>(name_index)?;
+        writer.write_u32::<E>(self.object_name_number)?;
+        writer.write_u64::<E>(self.outer_index.0)?;
+        writer.write_u64::<E>(self.class_index.0)?;
+        writer.write_u64::<E>(self.super_index.0)?;
+        writer.write_u64::<E>(self.template_index.0)?;
+        writer.write_u64::<E>(self.global_import_index.0)?;
+        writer.write_u32::<E>(self.object_flags)?;
+        writer.write_u8(self.filter_flags)?;
+        writer.write_all(&[0;3])?;
+
+        Ok(Self::byte_len())
+    }
+
+    pub fn to_string<W: Write>(&self, writer: &mut W, indent_spaces: usize, engine_version: EngineVersion) {
+        let indention = " ".repeat(indent_spaces);
+        writer.write_all(format!("{indention}cooked_serial_offset: {}\n", self.cooked_serial_offset).as_bytes()).unwrap();
+        writer.write_all(format!("{indention}cooked_serial_size: {}\n", self.cooked_serial_size).as_bytes()).unwrap();
+        writer.write_all(format!("{indention}object_name: {}\n", self.object_name).as_bytes()).unwrap();
+        writer.write_all(format!("{indention}object_name_number: {}\n", self.object_name_number).as_bytes()).unwrap();
+        writer.write_all(format!("{indention}outer_index: {}\n", self.outer_index).as_bytes()).unwrap();
+        writer.write_all(format!("{indention}class_index: {}\n", self.class_index).as_bytes()).unwrap();
+        writer.write_all(format!("{indention}super_index: {}\n", self.super_index).as_bytes()).unwrap();
+        writer.write_all(format!("{indention}template_index: {}\n", self.template_index).as_bytes()).unwrap();
+        match engine_version.is_zen() {
+            false => writer.write_all(format!("{indention}global_import_index: {}\n", self.global_import_index).as_bytes()).unwrap(),
+            true => writer.write_all(format!("{indention}public_export_hash: 0x{:x}\n", self.global_import_index.0).as_bytes()).unwrap(),
+        }
+        writer.write_all(format!("{indention}object_flags: 0x{:x}\n", self.object_flags).as_bytes()).unwrap();
+        writer.write_all(format!("{indention}filter_flags: {}\n", self.filter_flags).as_bytes()).unwrap();
+    }
+
+    pub fn from_string<R: BufRead + Seek>(reader: &mut R, expected_indent_level: usize, engine_version: EngineVersion) -> Result<Self, TextError> {
+        let object_flags = |reader: &mut R, v: String| -> Result<u32, TextError> {
+            u32::from_str_radix(v.trim_start_matches("0x"), 16).map_err(|err| reader.text_error(format!("invalid value [{v}]: {err}")).at_token(&v))
+        };
+        let global_import_index_field = match engine_version.is_zen() {
+            false => "global_import_index",
+            true => "public_export_hash",
+        };
+
+        Ok(Self {
+            cooked_serial_offset: parse_field(reader, expected_indent_level, "cooked_serial_offset")?,
+            cooked_serial_size: parse_field(reader, expected_indent_level, "cooked_serial_size")?,
+            object_name: next_field(reader, expected_indent_level, "object_name")?,
+            object_name_number: parse_field(reader, expected_indent_level, "object_name_number")?,
+            outer_index: parse_field(reader, expected_indent_level, "outer_index")?,
+            class_index: parse_field(reader, expected_indent_level, "class_index")?,
+            super_index: parse_field(reader, expected_indent_level, "super_index")?,
+            template_index: parse_field(reader, expected_indent_level, "template_index")?,
+            global_import_index: parse_field(reader, expected_indent_level, global_import_index_field)?,
+            object_flags: {
+                let flags = next_field(reader, expected_indent_level, "object_flags")?;
+                object_flags(reader, flags)?
+            },
+            filter_flags: parse_field(reader, expected_indent_level, "filter_flags")?,
+        })
+    }
+
+    #[inline]
+    pub fn byte_len() -> usize {
+        8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 4 + 1 + 3
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ExportCommandType {
+    Create,
+    Serialize,
+}
+
+/// FExportBundleEntry
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+struct UObjectExportBundleEntry {
+    local_export_index: u32,
+    command_type: ExportCommandType,
+}
+
+/// An export bundle, stored as an FExportBundleHeader followed (after all other headers) by its entries
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+struct UObjectExportBundle {
+    entries: Vec<UObjectExportBundleEntry>,
+}
+
+impl UObjectExportBundle {
+    /// Reads every export bundle in a region of `region_size` bytes.  The bundle count isn't stored in the
+    /// package, so headers are read until they (and the entries they reference) account for the whole region.
+    pub fn all_from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R, region_size: usize) -> Result<Vec<Self>, ParseError> {
+        let start_position = reader.offset();
+        let mut entry_counts = vec![];
+        let mut total_entries = 0;
+        while (entry_counts.len() + total_entries) * 8 < region_size {
+            let first_entry_index = reader.parse_u32::<E>()? as usize;
+            let entry_count = reader.parse_u32::<E>()? as usize;
+            if first_entry_index != total_entries {
+                Err(ParseError::invalid(start_position, format!("Export bundle {} should start at entry {total_entries}, but starts at {first_entry_index}", entry_counts.len())))?;
+            }
+            entry_counts.push(entry_count);
+            total_entries += entry_count;
+        }
+        if (entry_counts.len() + total_entries) * 8 != region_size {
+            Err(ParseError::invalid(start_position, "Export bundle headers don't match the export bundle region size"))?;
+        }
+
+        entry_counts.into_iter().map(|count| Self::entries_from_buffer::<R,E>(reader, count)).collect()
+    }
+
+    /// Reads a bundle's worth of FExportBundleEntry.  UE5 packages store the entries of every bundle without their
+    /// headers (which are kept in the graph data), so they're read as a single bundle.
+    pub fn entries_from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R, count: usize) -> Result<Self, ParseError> {
+        let mut entries = Vec::with_capacity(count);
+        for _ in 0..count {
+            let local_export_index = reader.parse_u32::<E>()?;
+            let command_type = match reader.parse_u32::<E>()? {
+                0 => ExportCommandType::Create,
+                1 => ExportCommandType::Serialize,
+                other => Err(ParseError::invalid(reader.offset() - 4, format!("Unknown export bundle command type {other}")))?
+            };
+            entries.push(UObjectExportBundleEntry { local_export_index, command_type });
+        }
+        Ok(Self { entries })
+    }
+
+    /// The size [`Self::all_to_bytes`] writes the bundles in - a header per bundle, then the entries
+    pub fn all_byte_len(bundles: &[Self]) -> usize {
+        bundles.iter().map(|b| 8 + b.entries.len() * 8).sum()
+    }
+
+    pub fn all_to_bytes<W: Write, E: byteorder::ByteOrder>(bundles: &[Self], writer: &mut W) -> Result<usize, EncodeError> {
+        let mut first_entry_index = 0;
+        for bundle in bundles {
+            writer.write_u32::<E>(first_entry_index)?;
+            writer.write_u32::<E>(bundle.entries.len() as u32)?;
+            first_entry_index += bundle.entries.len() as u32;
+        }
+        Ok(bundles.len() * 8 + Self::entries_to_bytes::<W,E>(bundles, writer)?)
+    }
+
+    /// Writes the entries of every bundle, without any bundle headers
+    pub fn entries_to_bytes<W: Write, E: byteorder::ByteOrder>(bundles: &[Self], writer: &mut W) -> Result<usize, EncodeError> {
+        let mut len = 0;
+        for entry in bundles.iter().flat_map(|b| &b.entries) {
+            writer.write_u32::<E>(entry.local_export_index)?;
+            writer.write_u32::<E>(match entry.command_type {
+                ExportCommandType::Create => 0,
+                ExportCommandType::Serialize => 1,
+            })?;
+            len += 8;
+        }
+        Ok(len)
+    }
+
+    pub fn to_string<W: Write>(&self, writer: &mut W, indent_spaces: usize) {
+        for entry in &self.entries {
+            let command = match entry.command_type {
+                ExportCommandType::Create => "create",
+                ExportCommandType::Serialize => "serialize",
+            };
+            writer.write_all(format!("{}{command}: {}\n", " ".repeat(indent_spaces), entry.local_export_index).as_bytes()).unwrap();
+        }
+    }
+
+    pub fn from_string<R: BufRead + Seek>(reader: &mut R, expected_indent_level: usize) -> Result<Self, TextError> {
+        let mut entries = vec![];
+        loop {
+            let next_line = next_nonempty_line(reader);
+            if next_line.is_empty() || next_line.trim().starts_with('-') || !check_indent(&next_line, expected_indent_level) {
+                reader.seek(SeekFrom::Current(-(next_line.len() as i64))).unwrap();
+                break;
+            }
 
-struct UObjectSummary {
+            let (command, index) = next_line.split_once(':').ok_or_else(|| reader.text_error("export bundle entries should use format 'create: <index>' or 'serialize: <index>'"))?;
+            let command_type = match command.trim() {
+                "create" => ExportCommandType::Create,
+                "serialize" => ExportCommandType::Serialize,
+                other => Err(reader.text_error(format!("unknown export bundle command [{other}], expected create or serialize")).at_token(other))?
+            };
+            entries.push(UObjectExportBundleEntry { local_export_index: parse_value(reader, index)?, command_type });
+        }
+        Ok(Self { entries })
+    }
+}
+
+#[derive(Clone)]
+pub struct UObjectSummary {
+    engine_version: EngineVersion,
     header: UObjectSummaryHeader,
+    zen: Option<ZenSummary>, // Only set for UE5 packages
     name_map: Vec<String>,
     name_map_type: Vec<StringType>,
-    remaining_bytes: Vec<u8>,
+    import_map: Vec<PackageObjectIndex>,
+    export_map: Vec<UObjectExportMapEntry>,
+    export_bundles: Vec<UObjectExportBundle>,
+    graph_data: Vec<u8>,
+    import_paths: Vec<Option<String>>, // Object paths of the imports, where they're known, for naming references to them
 }
 
 impl UObjectSummary {
-    pub fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R) -> Result<Self, Box<dyn Error>> {
+    /// Reads a package summary in the layout of the given engine version, or a detected one if it's None
+    pub fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R, engine_version: Option<EngineVersion>) -> Result<Self, ParseError> {
+        let start_position = reader.offset();
+        let engine_version = match engine_version {
+            Some(engine_version) => engine_version,
+            None => EngineVersion::detect::<R, E>(reader)?,
+        };
+        engine_version.check_iostore_summary().map_err(|err| ParseError::invalid(start_position, err))?;
+        let mut summary = match engine_version.is_zen() {
+            false => Self::header_and_name_map_from_buffer::<R, E>(reader)?,
+            true => Self::zen_header_and_name_map_from_buffer::<R, E>(reader)?,
+        };
+        summary.engine_version = engine_version;
+        let header = &summary.header;
+        let offsets = [header.import_map_offset, header.export_map_offset, header.export_bundles_offset, header.graph_data_offset];
+        if offsets[0] < 0 || offsets.windows(2).any(|pair| pair[0] > pair[1]) || header.graph_data_size < 0 {
+            Err(ParseError::invalid(start_position, "Package summary has map offsets out of order"))?;
+        }
+
+        let import_count = (header.export_map_offset - header.import_map_offset) as usize / std::mem::size_of::<u64>();
+        for _ in 0..import_count {
+            summary.import_map.push(PackageObjectIndex(reader.parse_u64::<E>()?));
+        }
+
+        let export_count = (header.export_bundles_offset - header.export_map_offset) as usize / UObjectExportMapEntry::byte_len();
+        for _ in 0..export_count {
+            summary.export_map.push(UObjectExportMapEntry::from_buffer::<R, E>(reader, &summary.name_map)?);
+        }
+
+        let export_bundles_size = (header.graph_data_offset - header.export_bundles_offset) as usize;
+        summary.export_bundles = match summary.zen {
+            None => UObjectExportBundle::all_from_buffer::<R, E>(reader, export_bundles_size)?,
+            Some(_) => vec![UObjectExportBundle::entries_from_buffer::<R, E>(reader, export_bundles_size / 8)?],
+        };
+
+        summary.graph_data = reader.parse_vec(summary.header.graph_data_size as usize)?;
+
+        Ok(summary)
+    }
+
+    /// Reads the header and name map, leaving the import map, export map, export bundles and graph data empty
+    fn header_and_name_map_from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R) -> Result<Self, ParseError> {
         let header = UObjectSummaryHeader::from_buffer::<R, E>(reader)?;
-        let names_count = (header.name_map_hashes_size/(std::mem::size_of::<u64>() as i32)) - 1;
-        let mut name_map: Vec<String> = Vec::with_capacity(names_count as usize);
-        let mut name_map_type: Vec<StringType> = Vec::with_capacity(names_count as usize);
+        let names_count = ((header.name_map_hashes_size/(std::mem::size_of::<u64>() as i32)) - 1).max(0);
+        // The count comes from the file, so the maps aren't preallocated from it
+        let mut name_map: Vec<String> = vec![];
+        let mut name_map_type: Vec<StringType> = vec![];
         for _ in 0..names_count {
-            let meta1 = reader.read_u8().unwrap();
-            let meta2 = reader.read_u8().unwrap();
+            let name_offset = reader.offset();
+            let meta1 = reader.parse_u8()?;
+            let meta2 = reader.parse_u8()?;
 
             let len = ((meta1 & 0x7f) as usize) * 256 + meta2 as usize;
             if meta1 & 0x80 > 0 { //utf16 marker
-                if reader.stream_position().unwrap() % 2 > 0 { // for some reason utf16 names seem to only start at even positions
-                    reader.read_u8().unwrap();
+                if !reader.offset().is_multiple_of(2) { // for some reason utf16 names seem to only start at even positions
+                    reader.parse_u8()?;
                 }
                 let mut raw_string = Vec::<u16>::with_capacity(len);
                 for _ in 0..len {
-                    raw_string.push(reader.read_u16::<E>().unwrap());
+                    raw_string.push(reader.parse_u16::<E>()?);
                 }
-                name_map.push(String::from_utf16(&raw_string).unwrap());
+                name_map.push(String::from_utf16(&raw_string).map_err(|_| ParseError::MalformedFString { offset: name_offset, path: String::new() })?);
                 name_map_type.push(StringType::Utf16);
             } else {
                 let mut raw_string = vec![0;len];
-                reader.read_exact(&mut raw_string).unwrap();
-                name_map.push(String::from_utf8(raw_string).unwrap());
+                reader.parse_exact(&mut raw_string)?;
+                name_map.push(String::from_utf8(raw_string).map_err(|_| ParseError::MalformedFString { offset: name_offset, path: String::new() })?);
                 name_map_type.push(StringType::Utf8);
             }
         }
 
-        let pos = reader.stream_position().unwrap() as usize;
-        let raw_byte_length = (header.graph_data_offset + header.graph_data_size) as usize;
-        let mut raw_bytes = vec![0;raw_byte_length-pos];
+        let pos = reader.offset();
+        let hashes_offset = u64::try_from(header.name_map_hashes_offset).ok().filter(|o| *o >= pos).ok_or(ParseError::invalid(pos, "Name map runs past the name hashes offset"))?;
+        reader.parse_seek(SeekFrom::Start(hashes_offset))?; // Padding
+
+        let algorithm_id = reader.parse_u64::<E>()?;
+        let hashes = (0..name_map.len()).map(|_| reader.parse_u64::<E>()).collect::<Result<Vec<_>,_>>()?;
+        check_name_hashes(algorithm_id, &hashes, &name_map, &name_map_type);
 
-        reader.read_exact(&mut raw_bytes).unwrap();
-        
         Ok(Self {
+            engine_version: EngineVersion::Ue4_27,
             header,
+            zen: None,
             name_map,
             name_map_type,
-            remaining_bytes: raw_bytes,
+            import_map: vec![],
+            export_map: vec![],
+            export_bundles: vec![],
+            graph_data: vec![],
+            import_paths: vec![],
         })
     }
 
-    pub fn to_bytes<E: byteorder::ByteOrder>(&self) -> Vec<u8> {
+    /// Reads a UE5 FZenPackageSummary, its versioning info, name batch and imported public export hashes, into
+    /// a summary with empty maps like [`Self::header_and_name_map_from_buffer`]
+    fn zen_header_and_name_map_from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R) -> Result<Self, ParseError> {
+        let has_versioning_info = reader.parse_u32::<E>()? != 0;
+        let header_size = reader.parse_u32::<E>()? as i32;
+        let name = reader.parse_u64::<E>()?;
+        let package_flags = reader.parse_u32::<E>()?;
+        let cooked_header_size = reader.parse_u32::<E>()?;
+        let imported_public_export_hashes_offset = reader.parse_i32::<E>()?;
+        let import_map_offset = reader.parse_i32::<E>()?;
+        let export_map_offset = reader.parse_i32::<E>()?;
+        let export_bundles_offset = reader.parse_i32::<E>()?;
+        let graph_data_offset = reader.parse_i32::<E>()?;
+
+        let versioning_info = if has_versioning_info {
+            let mut versioning_info = vec![0;16]; // Zen, UE4, UE5 and licensee versions
+            reader.parse_exact(&mut versioning_info)?;
+            let custom_version_count = reader.parse_i32::<E>()?;
+            versioning_info.write_i32::<E>(custom_version_count).unwrap();
+            versioning_info.extend(reader.parse_vec(custom_version_count.max(0) as usize * 20)?); // Guid, version
+            Some(versioning_info)
+        } else {
+            None
+        };
+
+        let name_batch_offset = reader.offset() as i32;
+        let names_count = reader.parse_u32::<E>()? as usize;
+        let mut name_map = vec![]; // The count comes from the file, so the maps aren't preallocated from it
+        let mut name_map_type = vec![];
+        if names_count > 0 {
+            let string_bytes_len = reader.parse_u32::<E>()? as usize;
+            let algorithm_id = reader.parse_u64::<E>()?;
+            let hashes = (0..names_count).map(|_| reader.parse_u64::<E>()).collect::<Result<Vec<_>,_>>()?;
+            let headers = reader.parse_vec(names_count * 2)?;
+            let strings_offset = reader.offset();
+            let strings = reader.parse_vec(string_bytes_len)?;
+
+            let mut pos = 0;
+            for header in headers.chunks(2) {
+                let len = ((header[0] & 0x7f) as usize) * 256 + header[1] as usize;
+                if header[0] & 0x80 > 0 { // utf16 names are aligned within the string data
+                    pos += pos % 2;
+                    let name_offset = strings_offset + pos as u64;
+                    let raw_string = strings.get(pos..pos + len * 2).ok_or(ParseError::invalid(name_offset, "Name batch has names past the end of its string data"))?;
+                    name_map.push(String::from_utf16(&raw_string.chunks(2).map(E::read_u16).collect::<Vec<_>>())
+                        .map_err(|_| ParseError::MalformedFString { offset: name_offset, path: String::new() })?);
+                    name_map_type.push(StringType::Utf16);
+                    pos += len * 2;
+                } else {
+                    let name_offset = strings_offset + pos as u64;
+                    let raw_string = strings.get(pos..pos + len).ok_or(ParseError::invalid(name_offset, "Name batch has names past the end of its string data"))?;
+                    name_map.push(String::from_utf8(raw_string.to_vec())
+                        .map_err(|_| ParseError::MalformedFString { offset: name_offset, path: String::new() })?);
+                    name_map_type.push(StringType::Utf8);
+                    pos += len;
+                }
+            }
+            check_name_hashes(algorithm_id, &hashes, &name_map, &name_map_type);
+        }
+        let name_batch_size = reader.offset() as i32 - name_batch_offset;
+
+        let pos = reader.offset();
+        let padding_len = u64::try_from(imported_public_export_hashes_offset).ok().and_then(|o| o.checked_sub(pos))
+            .ok_or(ParseError::invalid(pos, "Zen package summary has an invalid imported public export hashes offset"))?;
+        reader.parse_vec(padding_len as usize)?;
+        let hashes_count = (import_map_offset - imported_public_export_hashes_offset).max(0) as usize / std::mem::size_of::<u64>();
+        let imported_public_export_hashes = (0..hashes_count).map(|_| reader.parse_u64::<E>()).collect::<Result<_,_>>()?;
+
+        let header = UObjectSummaryHeader {
+            name,
+            source_name: 0,
+            package_flags,
+            cooked_header_size,
+            name_map_names_offset: name_batch_offset,
+            name_map_names_size: name_batch_size,
+            name_map_hashes_offset: 0,
+            name_map_hashes_size: 0,
+            import_map_offset,
+            export_map_offset,
+            export_bundles_offset,
+            graph_data_offset,
+            graph_data_size: header_size - graph_data_offset,
+            pad: 0,
+        };
+
+        Ok(Self {
+            engine_version: EngineVersion::Ue5,
+            header,
+            zen: Some(ZenSummary {
+                versioning_info,
+                imported_public_export_hashes_offset,
+                imported_public_export_hashes,
+            }),
+            name_map,
+            name_map_type,
+            import_map: vec![],
+            export_map: vec![],
+            export_bundles: vec![],
+            graph_data: vec![],
+            import_paths: vec![],
+        })
+    }
+
+    /// A summary holding only what tagged properties refer to - the names, imports, export names and classes, and
+    /// package flags of a legacy package.  The IoStore-only parts are left empty.
+    pub(crate) fn for_legacy_package(engine_version: EngineVersion, package_flags: u32, name_map: Vec<String>, name_map_type: Vec<StringType>, import_map: Vec<PackageObjectIndex>, exports: Vec<(String, PackageObjectIndex)>) -> Self {
+        let export_map = exports.into_iter().map(|(object_name, class_index)| UObjectExportMapEntry {
+            cooked_serial_offset: 0,
+            cooked_serial_size: 0,
+            object_name,
+            object_name_number: 0,
+            outer_index: PackageObjectIndex::NULL,
+            class_index,
+            super_index: PackageObjectIndex::NULL,
+            template_index: PackageObjectIndex::NULL,
+            global_import_index: PackageObjectIndex::NULL,
+            object_flags: 0,
+            filter_flags: 0,
+        }).collect();
+
+        Self {
+            engine_version,
+            header: UObjectSummaryHeader {
+                name: 0,
+                source_name: 0,
+                package_flags,
+                cooked_header_size: 0,
+                name_map_names_offset: 0,
+                name_map_names_size: 0,
+                name_map_hashes_offset: 0,
+                name_map_hashes_size: 0,
+                import_map_offset: 0,
+                export_map_offset: 0,
+                export_bundles_offset: 0,
+                graph_data_offset: 0,
+                graph_data_size: 0,
+                pad: 0,
+            },
+            zen: None,
+            name_map,
+            name_map_type,
+            import_map,
+            export_map,
+            export_bundles: vec![],
+            graph_data: vec![],
+            import_paths: vec![],
+        }
+    }
+
+    pub fn engine_version(&self) -> EngineVersion {
+        self.engine_version
+    }
+
+    /// Builds a UE4 summary around maps converted from a legacy package, laying out the header, name map hashes,
+    /// a single export bundle and the graph data.  Each imported (non-script) package gets one arc from its first
+    /// export bundle to this package's.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn for_converted_package(engine_version: EngineVersion, package_name: &str, package_flags: u32, cooked_header_size: u32, name_map: Vec<String>, name_map_type: Vec<StringType>, import_map: Vec<PackageObjectIndex>, export_map: Vec<UObjectExportMapEntry>, imported_packages: &[String]) -> Self {
+        let mut summary = Self::for_legacy_package(engine_version, package_flags, name_map, name_map_type, import_map, vec![]);
+        summary.add_missing_names([package_name]);
+        summary.header.name = summary.name_map.iter().position(|n| n == package_name).unwrap() as u64;
+        summary.header.source_name = summary.header.name;
+        summary.header.cooked_header_size = cooked_header_size;
+
+        let mut serialize_order: Vec<usize> = (0..export_map.len()).collect();
+        serialize_order.sort_by_key(|i| export_map[*i].cooked_serial_offset);
+        let create_entries = (0..export_map.len()).map(|i| UObjectExportBundleEntry { local_export_index: i as u32, command_type: ExportCommandType::Create });
+        let serialize_entries = serialize_order.into_iter().map(|i| UObjectExportBundleEntry { local_export_index: i as u32, command_type: ExportCommandType::Serialize });
+        summary.export_bundles = vec![UObjectExportBundle { entries: create_entries.chain(serialize_entries).collect() }];
+        summary.export_map = export_map;
+
+        summary.graph_data.write_i32::<LE>(imported_packages.len() as i32).unwrap();
+        for package in imported_packages {
+            summary.graph_data.write_u64::<LE>(name_hash(package, &StringType::Utf16)).unwrap(); // FPackageId
+            summary.graph_data.write_i32::<LE>(1).unwrap();
+            summary.graph_data.write_i32::<LE>(0).unwrap();
+            summary.graph_data.write_i32::<LE>(0).unwrap();
+        }
+
+        let mut names = vec![0;UObjectSummaryHeader::BYTE_LEN];
+        summary.names_to_bytes::<LE>(&mut names);
+        let header = &mut summary.header;
+        header.name_map_names_offset = UObjectSummaryHeader::BYTE_LEN as i32;
+        header.name_map_names_size = (names.len() - UObjectSummaryHeader::BYTE_LEN) as i32;
+        header.name_map_hashes_offset = names.len().next_multiple_of(std::mem::size_of::<u64>()) as i32;
+        header.name_map_hashes_size = ((summary.name_map.len() + 1) * std::mem::size_of::<u64>()) as i32;
+        header.import_map_offset = header.name_map_hashes_offset + header.name_map_hashes_size;
+        header.export_map_offset = header.import_map_offset + (summary.import_map.len() * std::mem::size_of::<u64>()) as i32;
+        header.export_bundles_offset = header.export_map_offset + (summary.export_map.len() * UObjectExportMapEntry::byte_len()) as i32;
+        header.graph_data_offset = header.export_bundles_offset + UObjectExportBundle::all_byte_len(&summary.export_bundles) as i32;
+        header.graph_data_size = summary.graph_data.len() as i32;
+
+        summary
+    }
+
+    pub(crate) fn name_map(&self) -> &[String] {
+        &self.name_map
+    }
+
+    pub(crate) fn import_map(&self) -> &[PackageObjectIndex] {
+        &self.import_map
+    }
+
+    /// Names imports by their object paths when decoding references to them, for the imports whose paths are known
+    pub(crate) fn set_import_paths(&mut self, import_paths: Vec<Option<String>>) {
+        self.import_paths = import_paths;
+    }
+
+    /// Names script imports by the script object paths they hash from, found in the mappings
+    fn name_script_imports(&mut self, mappings: &Mappings) {
+        self.import_paths = self.import_map.iter().map(|i| i.script_import_hash().and_then(|hash| mappings.find_script_object(hash))).collect();
+    }
+
+    pub(crate) fn export_map(&self) -> &[UObjectExportMapEntry] {
+        &self.export_map
+    }
+
+    pub(crate) fn package_flags(&self) -> u32 {
+        self.header.package_flags
+    }
+
+    /// The package's own name from its FMappedName, e.g. /Game/Data/MyTable
+    pub(crate) fn package_name(&self) -> Result<&str, Box<dyn Error>> {
+        let index = (self.header.name & 0x3FFF_FFFF) as usize;
+        Ok(self.name_map.get(index).ok_or(format!("Package name {index} isn't in the name map"))?)
+    }
+
+    pub(crate) fn export_bundle_count(&self) -> usize {
+        self.export_bundles.len()
+    }
+
+    /// FPackageIds of the packages this one imports, from the UE4 graph data
+    pub(crate) fn imported_package_ids(&self) -> Result<Vec<u64>, Box<dyn Error>> {
+        let mut reader = Cursor::new(&self.graph_data);
+        let package_count = match self.graph_data.is_empty() {
+            true => 0,
+            false => reader.read_i32::<LE>()?,
+        };
+        let mut package_ids = vec![];
+        for _ in 0..package_count {
+            package_ids.push(reader.read_u64::<LE>()?);
+            let arc_count = reader.read_i32::<LE>()?;
+            reader.seek(SeekFrom::Current(arc_count as i64 * 8))?;
+        }
+        Ok(package_ids)
+    }
+
+    /// Switches to another engine version with the same summary layout
+    pub fn set_engine_version(&mut self, engine_version: EngineVersion) -> Result<(), Box<dyn Error>> {
+        if engine_version.is_zen() != self.engine_version.is_zen() {
+            Err(format!("Package summary is in the {} layout, which can't be written as {engine_version}", self.engine_version))?;
+        }
+        self.engine_version = engine_version;
+        Ok(())
+    }
+
+    pub fn property_layout(&self) -> PropertyLayout {
+        self.engine_version.property_layout()
+    }
+
+    pub fn to_bytes<E: byteorder::ByteOrder>(&self) -> Result<Vec<u8>, EncodeError> {
+        let mut result = self.header_and_name_map_to_bytes::<E>();
+        for import in &self.import_map {
+            result.write_u64::<E>(import.0)?;
+        }
+        for (i, export) in self.export_map.iter().enumerate() {
+            export.to_bytes::<_, E>(&mut result, &self.name_map).map_err(|err| err.in_property(&format!("exports[{i}]")))?;
+        }
+        match self.zen {
+            None => UObjectExportBundle::all_to_bytes::<_, E>(&self.export_bundles, &mut result)?,
+            Some(_) => UObjectExportBundle::entries_to_bytes::<_, E>(&self.export_bundles, &mut result)?,
+        };
+        result.write_all(&self.graph_data)?;
+
+        Ok(result)
+    }
+
+    fn header_and_name_map_to_bytes<E: byteorder::ByteOrder>(&self) -> Vec<u8> {
+        if let Some(zen) = &self.zen {
+            return self.zen_header_and_name_map_to_bytes::<E>(zen);
+        }
+
         let mut result = self.header.to_bytes::<E>();
+        self.names_to_bytes::<E>(&mut result);
+
+        result.resize(self.header.name_map_hashes_offset as usize, 0);
+        result.write_u64::<E>(NAME_HASH_ALGORITHM_ID).unwrap();
+        for (name, string_type) in self.name_map.iter().zip(&self.name_map_type) {
+            result.write_u64::<E>(name_hash(name, string_type)).unwrap();
+        }
+
+        result
+    }
+
+    fn zen_header_and_name_map_to_bytes<E: byteorder::ByteOrder>(&self, zen: &ZenSummary) -> Vec<u8> {
+        let header = &self.header;
+        let mut result = vec![];
+        result.write_u32::<E>(zen.versioning_info.is_some() as u32).unwrap();
+        result.write_i32::<E>(header.graph_data_offset + header.graph_data_size).unwrap();
+        result.write_u64::<E>(header.name).unwrap();
+        result.write_u32::<E>(header.package_flags).unwrap();
+        result.write_u32::<E>(header.cooked_header_size).unwrap();
+        result.write_i32::<E>(zen.imported_public_export_hashes_offset).unwrap();
+        result.write_i32::<E>(header.import_map_offset).unwrap();
+        result.write_i32::<E>(header.export_map_offset).unwrap();
+        result.write_i32::<E>(header.export_bundles_offset).unwrap();
+        result.write_i32::<E>(header.graph_data_offset).unwrap();
+        if let Some(versioning_info) = &zen.versioning_info {
+            result.write_all(versioning_info).unwrap();
+        }
+
+        result.extend(self.name_batch_to_bytes::<E>());
+        result.resize(zen.imported_public_export_hashes_offset as usize, 0);
+        for hash in &zen.imported_public_export_hashes {
+            result.write_u64::<E>(*hash).unwrap();
+        }
+
+        result
+    }
+
+    /// The UE5 name batch - the name count, then (for any names) their hashes, headers and strings
+    fn name_batch_to_bytes<E: byteorder::ByteOrder>(&self) -> Vec<u8> {
+        let mut result = vec![];
+        result.write_u32::<E>(self.name_map.len() as u32).unwrap();
+        if self.name_map.is_empty() {
+            return result;
+        }
+
+        let mut headers = vec![];
+        let mut strings = vec![];
+        for (name, string_type) in self.name_map.iter().zip(&self.name_map_type) {
+            match string_type {
+                StringType::Utf16 => {
+                    let chars: Vec<u16> = name.encode_utf16().collect();
+                    headers.extend([(chars.len() / 256) as u8 | 0x80, (chars.len() % 256) as u8]);
+                    if !strings.len().is_multiple_of(2) {
+                        strings.push(0);
+                    }
+                    for char in chars {
+                        strings.write_u16::<E>(char).unwrap();
+                    }
+                },
+                StringType::Utf8 => {
+                    headers.extend([(name.len() / 256) as u8, (name.len() % 256) as u8]);
+                    strings.write_all(name.as_bytes()).unwrap();
+                }
+            }
+        }
+
+        result.write_u32::<E>(strings.len() as u32).unwrap();
+        result.write_u64::<E>(NAME_HASH_ALGORITHM_ID).unwrap();
+        for (name, string_type) in self.name_map.iter().zip(&self.name_map_type) {
+            result.write_u64::<E>(name_hash(name, string_type)).unwrap();
+        }
+        result.extend(headers);
+        result.extend(strings);
+        result
+    }
+
+    /// Appends the name map entries to `result`, which should already hold everything preceding the names
+    /// (utf16 names are aligned based on their position in the file).
+    fn names_to_bytes<E: byteorder::ByteOrder>(&self, result: &mut Vec<u8>) {
         for i in 0..self.name_map.len() {
             let name = &self.name_map[i];
             match self.name_map_type[i] {
@@ -142,7 +1073,7 @@ impl UObjectSummary {
                     result.push((bytes.len() / 256) as u8 | 0x80);
                     result.push((bytes.len() % 256) as u8);
 
-                    if result.len() % 2 > 0 {
+                    if !result.len().is_multiple_of(2) {
                         result.push(0);
                     }
 
@@ -157,26 +1088,155 @@ impl UObjectSummary {
                 }
             }
         }
-        
-        result.write_all(&self.remaining_bytes).unwrap();
+    }
+
+    /// Adds any names that aren't in the name map yet, then updates the name map
+    /// sizes and shifts every offset that comes after the name map.
+    pub fn add_missing_names<'a>(&mut self, names: impl IntoIterator<Item = &'a str>) {
+        let original_count = self.name_map.len();
+        for name in names {
+            if self.name_map.iter().any(|n| n == name) {
+                continue;
+            }
+
+            self.name_map.push(name.to_owned());
+            self.name_map_type.push(StringType::for_string(name));
+        }
+        if self.name_map.len() == original_count {
+            return;
+        }
 
-        result
+        let delta = if let Some(zen) = &self.zen {
+            let name_batch_size = self.name_batch_to_bytes::<LE>().len() as i32;
+            let imported_public_export_hashes_offset = ((self.header.name_map_names_offset + name_batch_size) as usize).next_multiple_of(std::mem::size_of::<u64>()) as i32;
+            let delta = imported_public_export_hashes_offset - zen.imported_public_export_hashes_offset;
+            self.header.name_map_names_size = name_batch_size;
+            self.zen.as_mut().unwrap().imported_public_export_hashes_offset = imported_public_export_hashes_offset;
+            delta
+        } else {
+            // The names are always written straight after the header
+            let mut names = vec![0;UObjectSummaryHeader::BYTE_LEN];
+            self.names_to_bytes::<LE>(&mut names);
+
+            let header = &mut self.header;
+            let original_end = header.name_map_hashes_offset + header.name_map_hashes_size;
+            header.name_map_names_size = (names.len() - UObjectSummaryHeader::BYTE_LEN) as i32;
+            header.name_map_hashes_offset = names.len().next_multiple_of(std::mem::size_of::<u64>()) as i32;
+            header.name_map_hashes_size = ((self.name_map.len() + 1) * std::mem::size_of::<u64>()) as i32;
+            header.name_map_hashes_offset + header.name_map_hashes_size - original_end
+        };
+
+        let header = &mut self.header;
+        header.import_map_offset += delta;
+        header.export_map_offset += delta;
+        header.export_bundles_offset += delta;
+        header.graph_data_offset += delta;
     }
 
+    /// Parses the opaque part of the summary written by [`Display`].  The import map, export map and export
+    /// bundles are written as their own sections, so they're left empty to be filled in by the caller.
     pub fn from_string(str: &str) -> Result<Self, Box<dyn Error>> {
         let bytes = BASE64_STANDARD.decode(str).map_err(|_| "Unable to read object summary header from base64 string. This value shouldn't be manually edited.")?;
         let mut bytes = Cursor::new(bytes);
-        Self::from_buffer::<_, LE>(&mut bytes)
+        let mut summary = match EngineVersion::detect::<_, LE>(&mut bytes)?.is_zen() {
+            false => Self::header_and_name_map_from_buffer::<_, LE>(&mut bytes)?,
+            true => Self::zen_header_and_name_map_from_buffer::<_, LE>(&mut bytes)?,
+        };
+        let graph_data_size = usize::try_from(summary.header.graph_data_size).map_err(|_| "Object summary has a negative graph data size. This value shouldn't be manually edited.")?;
+        summary.graph_data = bytes.parse_vec(graph_data_size).map_err(|_| "Object summary base64 string is missing graph data. This value shouldn't be manually edited.")?;
+        Ok(summary)
+    }
+
+    /// PKG_UnversionedProperties - exports only store property values, laid out as described in a mappings file
+    pub fn has_unversioned_properties(&self) -> bool {
+        self.header.package_flags & 0x2000 != 0
+    }
+
+    /// The mapped class that an export's unversioned properties follow.  Class defaults are named after their
+    /// class, and other exports' classes are found from their script import.
+    fn unversioned_class(&self, export_index: usize, mappings: &Mappings) -> Option<String> {
+        let export = self.export_map.get(export_index)?;
+        if let Some(class) = export.object_name.strip_prefix("Default__").filter(|c| mappings.structs.contains_key(*c)) {
+            return Some(class.to_owned());
+        }
+        mappings.find_script_struct(export.class_index.script_import_hash()?).map(str::to_owned)
+    }
+
+    /// Local export indices in the order their data is serialized after the summary
+    pub fn serialized_export_order(&self) -> Vec<usize> {
+        self.export_bundles.iter()
+            .flat_map(|b| &b.entries)
+            .filter(|e| e.command_type == ExportCommandType::Serialize)
+            .map(|e| e.local_export_index as usize)
+            .collect()
+    }
+
+    /// Sets the serial size of an export, shifting the serial offset of every export stored after it so the
+    /// export map keeps describing the payload.  The cooked header size is left alone - serial offsets are
+    /// relative to it, and it only depends on the (legacy) package header, not on export data.
+    pub fn resize_export(&mut self, export_index: usize, serial_size: u64) -> Result<(), EncodeError> {
+        let out_of_range = |i: usize| EncodeError::unsupported("Export serial offset and size are out of range").in_property(&format!("exports[{i}]"));
+        let export = self.export_map.get_mut(export_index).ok_or_else(|| out_of_range(export_index))?;
+        let old_end = export.cooked_serial_offset.checked_add(export.cooked_serial_size).ok_or_else(|| out_of_range(export_index))?;
+        let delta = serial_size as i64 - export.cooked_serial_size as i64;
+        export.cooked_serial_size = serial_size;
+
+        for (i, export) in self.export_map.iter_mut().enumerate() {
+            if i != export_index && export.cooked_serial_offset >= old_end {
+                export.cooked_serial_offset = export.cooked_serial_offset.checked_add_signed(delta).ok_or_else(|| out_of_range(i))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn maps_to_string<W: Write>(&self, writer: &mut W) {
+        writer.write_all("imports:\n".as_bytes()).unwrap();
+        for (i, import) in self.import_map.iter().enumerate() {
+            writer.write_all(format!("  - {i}: {import}\n").as_bytes()).unwrap();
+        }
+
+        writer.write_all("exports:\n".as_bytes()).unwrap();
+        for (i, export) in self.export_map.iter().enumerate() {
+            writer.write_all(format!("  - {i}:\n").as_bytes()).unwrap();
+            export.to_string(writer, 6, self.engine_version());
+        }
+
+        writer.write_all("export_bundles:\n".as_bytes()).unwrap();
+        for (i, bundle) in self.export_bundles.iter().enumerate() {
+            writer.write_all(format!("  - {i}:\n").as_bytes()).unwrap();
+            bundle.to_string(writer, 6);
+        }
+    }
+
+    pub fn maps_from_string<R: BufRead + Seek>(&mut self, reader: &mut R) -> Result<(), TextError> {
+        next_field(reader, 0, "imports")?;
+        while let Some(import) = next_list_item(reader, 2)? {
+            self.import_map.push(parse_value(reader, &import)?);
+        }
+
+        next_field(reader, 0, "exports")?;
+        while next_list_item(reader, 2)?.is_some() {
+            self.export_map.push(UObjectExportMapEntry::from_string(reader, 6, self.engine_version())?);
+        }
+
+        next_field(reader, 0, "export_bundles")?;
+        while next_list_item(reader, 2)?.is_some() {
+            self.export_bundles.push(UObjectExportBundle::from_string(reader, 6)?);
+        }
+
+        Ok(())
     }
 }
 
 impl Display for UObjectSummary {    
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&BASE64_STANDARD.encode(self.to_bytes::<LE>()))
+        let mut bytes = self.header_and_name_map_to_bytes::<LE>();
+        bytes.extend_from_slice(&self.graph_data);
+        f.write_str(&BASE64_STANDARD.encode(bytes))
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Serialize, Deserialize)]
 pub struct UObjectPropertyHeader {
     pub name: String,
     pub r#type: String,
@@ -184,40 +1244,49 @@ pub struct UObjectPropertyHeader {
 }
 
 impl UObjectPropertyHeader {
-    pub fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R, name_map: &[String]) -> Option<(Self, usize)> {
-        let name_index = reader.read_u64::<E>().unwrap() as usize;
-        let name = name_map[name_index].clone();
+    pub fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R, summary: &UObjectSummary) -> Result<Option<(Self, usize)>, ParseError> {
+        let name = name_from_buffer::<R,E>(reader, summary)?;
 
         if name == "None" {
-            return None;
+            return Ok(None);
         }
 
-        let type_index = reader.read_u64::<E>().unwrap() as usize;
-        let r#type = name_map[type_index].clone();
-
-        let data_size = reader.read_u32::<E>().unwrap() as usize;
-        let arr_index = reader.read_u32::<E>().unwrap() as usize;
+        let read_tag = |reader: &mut R| -> Result<_, ParseError> {
+            let r#type = name_from_buffer::<R,E>(reader, summary)?;
+            let data_size = reader.parse_u32::<E>()? as usize;
+            let arr_index = reader.parse_u32::<E>()? as usize;
+            Ok((r#type, data_size, arr_index))
+        };
+        let (r#type, data_size, arr_index) = read_tag(reader).map_err(|err| err.in_property(&name))?;
 
-        Some((Self {
+        Ok(Some((Self {
             name,
             r#type,
             arr_index
-        }, data_size))
+        }, data_size)))
+    }
+
+    /// The property's name in error paths, with its array index when it's one of a static array's elements
+    pub(crate) fn path_segment(&self) -> String {
+        match self.arr_index {
+            0 => self.name.clone(),
+            arr_index => format!("{}[{arr_index}]", self.name),
+        }
     }
 
-    pub fn to_bytes<W: Write, E: byteorder::ByteOrder>(&self, writer: &mut W, name_map: &[String], data_size: usize) -> bool {
-        let name_index = name_map.iter().position(|n| n == &self.name).unwrap_or_else(|| panic!("Object type [{}] wasn't in name map", self.name)) as u64;
+    pub fn to_bytes<W: Write, E: byteorder::ByteOrder>(&self, writer: &mut W, summary: &UObjectSummary, data_size: usize) -> Result<bool, EncodeError> {
+        let name = name_index(summary, &self.name)?;
         if self.name == "None" {
-            writer.write_u64::<E>(name_index).unwrap();
-            false
+            writer.write_u64::<E>(name)?;
+            Ok(false)
         } else {
-            let type_index = name_map.iter().position(|n| n == &self.r#type).unwrap_or_else(|| panic!("Object type [{}] wasn't in name map", self.r#type)) as u64;
+            let type_index = name_index(summary, &self.r#type)?;
 
-            writer.write_u64::<E>(name_index).unwrap();
-            writer.write_u64::<E>(type_index).unwrap();
-            writer.write_u32::<E>(data_size as u32).unwrap();
-            writer.write_u32::<E>(self.arr_index as u32).unwrap();
-            true
+            writer.write_u64::<E>(name)?;
+            writer.write_u64::<E>(type_index)?;
+            writer.write_u32::<E>(data_size as u32)?;
+            writer.write_u32::<E>(self.arr_index as u32)?;
+            Ok(true)
         }
     }
 
@@ -227,19 +1296,23 @@ impl UObjectPropertyHeader {
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Serialize, Deserialize)]
 pub struct UObjectProperty {
-    header: UObjectPropertyHeader,
-    metadata: UObjectPropertyMetadata,
-    data: UObjectPropertyData,
+    pub(crate) header: UObjectPropertyHeader,
+    pub(crate) metadata: UObjectPropertyMetadata,
+    pub(crate) data: UObjectPropertyData,
 }
 
 impl UObjectProperty {
-    pub fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R, name_map: &[String]) -> Result<Option<Self>, Box<dyn Error>> {
-        match UObjectPropertyHeader::from_buffer::<R,E>(reader, name_map) {
+    pub fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R, summary: &UObjectSummary) -> Result<Option<Self>, ParseError> {
+        match UObjectPropertyHeader::from_buffer::<R,E>(reader, summary)? {
             Some((header, expected_size)) => {
-                let metadata = UObjectPropertyMetadata::from_buffer::<R,E>(reader, &header.r#type, name_map);
-                let data = UObjectPropertyData::from_buffer::<R,E>(reader, &header.r#type, &metadata, name_map, expected_size)?;
+                let mut read_value = || -> Result<_, ParseError> {
+                    let metadata = UObjectPropertyMetadata::from_buffer::<R,E>(reader, &header.r#type, summary)?;
+                    let data = UObjectPropertyData::from_buffer::<R,E>(reader, &header.r#type, &metadata, summary, expected_size)?;
+                    Ok((metadata, data))
+                };
+                let (metadata, data) = read_value().map_err(|err| err.in_property(&header.path_segment()))?;
                 Ok(Some(Self {
                     header,
                     metadata,
@@ -250,17 +1323,28 @@ impl UObjectProperty {
         }
     }
 
-    pub fn to_bytes<W: Write, E: byteorder::ByteOrder>(&self, writer: &mut W, name_map: &[String]) -> usize {
-        let mut data = vec![];
-        let data_size = self.data.to_bytes::<_,E>(&mut data, name_map);
+    pub fn to_bytes<W: Write, E: byteorder::ByteOrder>(&self, writer: &mut W, summary: &UObjectSummary) -> Result<usize, EncodeError> {
+        let mut write_property = || -> Result<usize, EncodeError> {
+            let mut data = vec![];
+            let data_size = self.data.to_bytes::<_,E>(&mut data, summary)?;
 
-        if self.header.to_bytes::<W,E>(writer, name_map, data_size) {
-            let meta_len = self.metadata.to_bytes::<W,E>(writer, name_map);
-            writer.write_all(&data).unwrap();
-            UObjectPropertyHeader::byte_len() + data.len() + meta_len
-        } else {
-            UObjectPropertyHeader::byte_len()
-        }
+            if self.header.to_bytes::<W,E>(writer, summary, data_size)? {
+                let meta_len = self.metadata.to_bytes::<W,E>(writer, summary)?;
+                writer.write_all(&data)?;
+                Ok(UObjectPropertyHeader::byte_len() + data.len() + meta_len)
+            } else {
+                Ok(UObjectPropertyHeader::byte_len())
+            }
+        };
+        write_property().map_err(|err| err.in_property(&self.header.path_segment()))
+    }
+
+    /// Adds every name this property needs in the name map to `names`
+    pub fn collect_names<'a>(&'a self, names: &mut Vec<&'a str>) {
+        names.push(&self.header.name);
+        names.push(&self.header.r#type);
+        self.metadata.collect_names(names);
+        self.data.collect_names(names);
     }
 
     pub fn to_string<W: Write>(&self, writer: &mut W, indent_spaces: usize) {
@@ -272,21 +1356,21 @@ impl UObjectProperty {
         self.data.to_string(&self.metadata, writer, indent_spaces);
     }
 
-    pub fn from_string<R: BufRead + Seek>(reader: &mut R, expected_indent_level: usize) -> Result<Option<Self>, Box<dyn Error>> {
+    pub fn from_string<R: BufRead + Seek>(reader: &mut R, expected_indent_level: usize) -> Result<Option<Self>, TextErrors> {
         let next_line = next_nonempty_line(reader);
         if next_line.is_empty() || !check_indent(&next_line, expected_indent_level) {
             reader.seek(SeekFrom::Current(-(next_line.len() as i64))).unwrap();
             return Ok(None);
         }
 
-        let (name, val) = next_line.split_once(':').ok_or(format!("Missing ':' delimiter for property at position 0x{:x}", reader.stream_position().unwrap()))?;
+        let (name, val) = next_line.split_once(':').ok_or_else(|| reader.text_error("missing ':' delimiter for property"))?;
 
         let (name, arr_index) = {
             let iter = name.trim().chars();
             let name = iter.clone().take_while(|c| *c != '[').collect::<String>();
             let mut iter = iter.skip_while(|c| *c != '[');
             let index = match iter.next() {
-                Some(_) => { Some(iter.take_while(|c| *c != ']').collect::<String>().parse::<usize>()?) },
+                Some(_) => { Some(parse_value::<usize, R>(reader, &iter.take_while(|c| *c != ']').collect::<String>())?) },
                 None => None,
             };
             (name, index.unwrap_or(0))
@@ -306,311 +1390,624 @@ impl UObjectProperty {
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Serialize, Deserialize)]
 pub enum UObjectPropertyMetadata {
     Array(String),
     Bool(bool),
     Byte(u64,u8),
     Enum(String),
     Map(String, String),
-    Struct(Vec<u8>),
+    Set(String),
+    Struct(String, #[serde(with = "guid_string")] [u8;16]), // Struct type, struct GUID
+    StructRaw(#[serde(with = "base64_string")] Vec<u8>),    // Struct metadata that couldn't be decoded, or was given as base64 in older text files
     None,
 }
 
 impl UObjectPropertyMetadata {
-    pub fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R, r#type: &str, name_map: &[String]) -> Self {
-        match r#type {
-            "ArrayProperty" => {
-                let item_type = reader.read_u64::<E>().unwrap() as usize;
-                let item_type = &name_map[item_type];
+    pub fn collect_names<'a>(&'a self, names: &mut Vec<&'a str>) {
+        match self {
+            Self::Array(item_type) | Self::Set(item_type) => names.push(item_type),
+            Self::Enum(enum_name) => names.push(enum_name),
+            Self::Map(key_type, val_type) => {
+                names.push(key_type);
+                names.push(val_type);
+            },
+            Self::Struct(struct_type, _) => names.push(struct_type),
+            Self::Bool(_) | Self::Byte(_, _) | Self::StructRaw(_) | Self::None => {}
+        }
+    }
 
-                let _unknown_byte = reader.read_u8().unwrap();
-                UObjectPropertyMetadata::Array(item_type.clone())
+    /// The kind of metadata, for errors about metadata that doesn't match its property type
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Array(_) => "Array metadata",
+            Self::Bool(_) => "Bool metadata",
+            Self::Byte(_, _) => "Byte metadata",
+            Self::Enum(_) => "Enum metadata",
+            Self::Map(_, _) => "Map metadata",
+            Self::Set(_) => "Set metadata",
+            Self::Struct(_, _) | Self::StructRaw(_) => "Struct metadata",
+            Self::None => "no metadata",
+        }
+    }
+
+    pub fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R, r#type: &str, summary: &UObjectSummary) -> Result<Self, ParseError> {
+        Ok(match r#type {
+            "ArrayProperty" => {
+                let item_type = name_from_buffer::<R,E>(reader, summary)?;
+                skip_tag_suffix(reader, summary)?;
+                UObjectPropertyMetadata::Array(item_type)
             },
             "BoolProperty" => {
-                let val = reader.read_u8().unwrap() > 0;
-                let _unknown_byte = reader.read_u8().unwrap();
+                let val = reader.parse_u8()? > 0;
+                skip_tag_suffix(reader, summary)?;
                 UObjectPropertyMetadata::Bool(val)
             },
             "ByteProperty" => {
-                let enum_name = reader.read_u64::<E>().unwrap();
-                let val = reader.read_u8().unwrap();
+                let enum_name = reader.parse_u64::<E>()?;
+                let val = reader.parse_u8()?;
                 UObjectPropertyMetadata::Byte(enum_name, val)
             },
             "EnumProperty" => {
-                let enum_name = reader.read_u64::<E>().unwrap() as usize;
-                let _unknown_byte = reader.read_u8().unwrap();
-                UObjectPropertyMetadata::Enum(name_map[enum_name].clone())
+                let enum_name = name_from_buffer::<R,E>(reader, summary)?;
+                skip_tag_suffix(reader, summary)?;
+                UObjectPropertyMetadata::Enum(enum_name)
             },
             "StructProperty" => {
-                let mut data = vec![0;25];
-                reader.read_exact(&mut data).unwrap();
-                UObjectPropertyMetadata::Struct(data)
+                let metadata_len = summary.property_layout().struct_metadata_len;
+                let mut data = vec![0;metadata_len];
+                reader.parse_exact(&mut data)?;
+
+                let struct_type = E::read_u64(&data) as usize;
+                let guid: [u8;16] = data[8..24].try_into().unwrap();
+                let has_guid = data[24..].iter().any(|b| *b != 0);
+                match summary.name_map.get(struct_type) {
+                    Some(struct_type) if !has_guid => UObjectPropertyMetadata::Struct(struct_type.clone(), guid),
+                    _ => {
+                        eprintln!("WARNING: Unable to decode struct metadata at 0x{:x}  # Keeping it as raw data.", reader.offset() - metadata_len as u64);
+                        UObjectPropertyMetadata::StructRaw(data)
+                    }
+                }
             },
             "MapProperty" => {
-                let key_type = reader.read_u64::<E>().unwrap() as usize;
-                let key_type = &name_map[key_type];
-
-                let value_type = reader.read_u64::<E>().unwrap() as usize;
-                let value_type = &name_map[value_type];
+                let key_type = name_from_buffer::<R,E>(reader, summary)?;
+                let value_type = name_from_buffer::<R,E>(reader, summary)?;
 
-                let _unknown_byte = reader.read_u8().unwrap();
-                let _unknown_value = reader.read_u32::<E>().unwrap() as usize;
+                skip_tag_suffix(reader, summary)?;
+                let _num_keys_to_remove = reader.parse_u32::<E>()? as usize;
 
-                UObjectPropertyMetadata::Map(key_type.clone(), value_type.clone())
+                UObjectPropertyMetadata::Map(key_type, value_type)
+            },
+            "SetProperty" => {
+                let item_type = name_from_buffer::<R,E>(reader, summary)?;
+                skip_tag_suffix(reader, summary)?;
+                UObjectPropertyMetadata::Set(item_type)
             },
             _ => {
-                let _unknown_byte = reader.read_u8().unwrap();
+                skip_tag_suffix(reader, summary)?;
                 UObjectPropertyMetadata::None
             }
-        }
+        })
     }
 
-    pub fn to_bytes<W: Write, E: byteorder::ByteOrder>(&self, writer: &mut W, name_map: &[String]) -> usize {
-        match self {
-            Self::Array(item_type) => {
-                let item_type_index = name_map.iter().position(|n| n == item_type).unwrap_or_else(|| panic!("Object type [{}] wasn't in name map", item_type)) as u64;
-                writer.write_u64::<E>(item_type_index).unwrap();
-                writer.write_u8(0).unwrap();
-                8 + 1
+    pub fn to_bytes<W: Write, E: byteorder::ByteOrder>(&self, writer: &mut W, summary: &UObjectSummary) -> Result<usize, EncodeError> {
+        Ok(match self {
+            Self::Array(item_type) | Self::Set(item_type) => {
+                writer.write_u64::<E>(name_index(summary, item_type)?)?;
+                8 + write_tag_suffix(writer, summary)?
             },
             Self::Bool(val) => {
                 if *val {
-                    writer.write_u8(1).unwrap(); // true
+                    writer.write_u8(1)?; // true
                 } else {
-                    writer.write_u8(0).unwrap(); // false
+                    writer.write_u8(0)?; // false
                 }
-                writer.write_u8(0).unwrap();  // Unknown value - seems to be 0?
 
-                2
+                1 + write_tag_suffix(writer, summary)?
             }
             Self::Byte(enum_name, val, ) => {
-                writer.write_u64::<E>(*enum_name).unwrap();
-                writer.write_u8(*val).unwrap();
+                writer.write_u64::<E>(*enum_name)?;
+                writer.write_u8(*val)?;
                 8 + 1
             },
             Self::Enum(enum_name) => {
-                writer.write_u64::<E>(name_map.iter().position(|n| n == enum_name).unwrap_or_else(|| panic!("Object type [{enum_name}] wasn't in name map")) as u64).unwrap();
-                writer.write_u8(0).unwrap();
-                8 + 1
+                writer.write_u64::<E>(name_index(summary, enum_name)?)?;
+                8 + write_tag_suffix(writer, summary)?
             },
             Self::Map(key_type, val_type) => {
-                let key_type_index = name_map.iter().position(|n| n == key_type).unwrap_or_else(|| panic!("Object type [{}] wasn't in name map", key_type)) as u64;
-                let val_type_index = name_map.iter().position(|n| n == val_type).unwrap_or_else(|| panic!("Object type [{}] wasn't in name map", val_type)) as u64;
-
-                writer.write_u64::<E>(key_type_index).unwrap();
-                writer.write_u64::<E>(val_type_index).unwrap();
-                writer.write_u8(0).unwrap();  // Unknown value - seems to be 0?
-                writer.write_u32::<E>(0).unwrap();   // Unknown value - seems to be 0?
-                8 + 8 + 1 + 4
+                let key_type_index = name_index(summary, key_type)?;
+                let val_type_index = name_index(summary, val_type)?;
+
+                writer.write_u64::<E>(key_type_index)?;
+                writer.write_u64::<E>(val_type_index)?;
+                let suffix_len = write_tag_suffix(writer, summary)?;
+                writer.write_u32::<E>(0)?;   // Number of keys to remove
+                8 + 8 + suffix_len + 4
             },
-            Self::Struct(data) => {
-                writer.write_all(data).unwrap();
+            Self::Struct(struct_type, guid) => {
+                writer.write_u64::<E>(name_index(summary, struct_type)?)?;
+                writer.write_all(guid)?;
+                let metadata_len = summary.property_layout().struct_metadata_len;
+                writer.write_all(&vec![0;metadata_len - 8 - 16])?;
+                metadata_len
+            },
+            Self::StructRaw(data) => {
+                writer.write_all(data)?;
                 data.len()
             },
-            Self::None => {
-                writer.write_u8(0).unwrap();  // Unknown value - seems to be 0?
-                1
+            Self::None => write_tag_suffix(writer, summary)?,
+        })
+    }
+}
+
+/// Skips the bytes that end each property tag (see [`PropertyLayout::tag_suffix_len`])
+fn skip_tag_suffix<R: Read + Seek>(reader: &mut R, summary: &UObjectSummary) -> Result<(), ParseError> {
+    let mut _suffix = vec![0;summary.property_layout().tag_suffix_len];
+    reader.parse_exact(&mut _suffix)
+}
+
+/// Reads an FName as its u64 index in the name map
+fn name_from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R, summary: &UObjectSummary) -> Result<String, ParseError> {
+    let offset = reader.offset();
+    let index = reader.parse_u64::<E>()?;
+    summary.name_map.get(index as usize).cloned().ok_or(ParseError::BadNameIndex { index, offset, path: String::new() })
+}
+
+fn write_tag_suffix<W: Write>(writer: &mut W, summary: &UObjectSummary) -> Result<usize, EncodeError> {
+    let suffix_len = summary.property_layout().tag_suffix_len;
+    writer.write_all(&vec![0;suffix_len])?;
+    Ok(suffix_len)
+}
+
+/// The index of a name in the name map, for writing an FName
+fn name_index(summary: &UObjectSummary, name: &str) -> Result<u64, EncodeError> {
+    summary.name_map.iter().position(|n| n == name).map(|index| index as u64)
+        .ok_or_else(|| EncodeError::MissingName { name: name.to_owned(), path: String::new() })
+}
+
+/// A reference to another object in the package (FPackageIndex).  Exports are identified by their object name
+/// and imports by their object path (or object index, if the path isn't known), as long as those are unique -
+/// otherwise their map index is used.
+#[derive(PartialEq, Debug, Serialize, Deserialize)]
+pub enum ObjectReference {
+    Null,
+    Export(String),
+    Import(String),
+}
+
+impl ObjectReference {
+    pub fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R, summary: &UObjectSummary) -> Result<Self, ParseError> {
+        let offset = reader.offset();
+        let index = reader.parse_i32::<E>()?;
+        let bad_index = || ParseError::BadObjectIndex { index: index as i64, offset, path: String::new() };
+        match index.cmp(&0) {
+            Ordering::Equal => Ok(Self::Null),
+            Ordering::Greater => {
+                let export_index = index as usize - 1;
+                let export = summary.export_map.get(export_index).ok_or_else(bad_index)?;
+                let is_unique = summary.export_map.iter().filter(|e| e.object_name == export.object_name).count() == 1;
+                if is_unique && export.object_name.parse::<usize>().is_err() {
+                    Ok(Self::Export(export.object_name.clone()))
+                } else {
+                    Ok(Self::Export(export_index.to_string()))
+                }
+            },
+            Ordering::Less => {
+                let import_index = (-(index as i64) - 1) as usize;
+                let import = summary.import_map.get(import_index).ok_or_else(bad_index)?;
+                if summary.import_map.iter().filter(|i| *i == import).count() != 1 {
+                    Ok(Self::Import(import_index.to_string()))
+                } else if let Some(path) = summary.import_paths.get(import_index).and_then(Option::as_ref) {
+                    Ok(Self::Import(path.clone()))
+                } else {
+                    Ok(Self::Import(import.to_string()))
+                }
+            }
+        }
+    }
+
+    pub fn to_bytes<W: Write, E: byteorder::ByteOrder>(&self, writer: &mut W, summary: &UObjectSummary) -> Result<usize, EncodeError> {
+        let missing = || EncodeError::MissingObject { reference: self.to_string(), path: String::new() };
+        let index = match self {
+            Self::Null => 0,
+            Self::Export(id) => {
+                let export_index = summary.export_map.iter().position(|e| &e.object_name == id)
+                    .or_else(|| id.parse().ok())
+                    .ok_or_else(missing)?;
+                export_index as i32 + 1
+            },
+            Self::Import(id) => {
+                let import_index = id.parse::<usize>().ok()
+                    .or_else(|| match id.starts_with('/') {
+                        true => summary.import_map.iter().position(|i| *i == PackageObjectIndex::from_import_path(id)),
+                        false => summary.import_map.iter().position(|i| &i.to_string() == id),
+                    })
+                    .ok_or_else(missing)?;
+                -(import_index as i32) - 1
             }
+        };
+        writer.write_i32::<E>(index)?;
+        Ok(4)
+    }
+}
+
+impl Display for ObjectReference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Null => f.write_str("null"),
+            Self::Export(id) => write!(f, "export:{id}"),
+            Self::Import(id) => write!(f, "import:{id}"),
         }
     }
 }
 
-#[derive(PartialEq, Debug)]
+impl FromStr for ObjectReference {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s == "null" {
+            Ok(Self::Null)
+        } else if let Some(id) = s.strip_prefix("export:") {
+            Ok(Self::Export(id.to_owned()))
+        } else if let Some(id) = s.strip_prefix("import:") {
+            Ok(Self::Import(id.to_owned()))
+        } else {
+            Err(format!("Object reference [{s}] should be null, export:<name or index> or import:<object path, object index or index>"))?
+        }
+    }
+}
+
+/// FText history - only the histories that hold plain strings are supported
+#[derive(PartialEq, Debug, Serialize, Deserialize)]
+#[allow(clippy::large_enum_variant)] // Always boxed inside UObjectPropertyData::Text
+pub enum FTextHistory {
+    None(Option<UObjectPropertyData>), // Culture invariant string
+    Base(UObjectPropertyData, UObjectPropertyData, UObjectPropertyData), // Namespace, key, source string
+}
+
+#[derive(PartialEq, Debug, Serialize, Deserialize)]
 pub enum UObjectPropertyData {
     Array(Vec<UObjectPropertyData>, Option<(UObjectPropertyHeader, String)>),
     Bool,
+    BoolValue(bool), // Bools inside arrays/maps have no tag to hold their value, so it's stored with the data
     Byte(u8),
     Enum(String),
-    Struct(Vec<UObjectProperty>, Vec<u8>),
+    Struct(Vec<UObjectProperty>, #[serde(with = "base64_string")] Vec<u8>),
+    NativeStruct(String, Vec<StructFieldValue>), // Struct type, field values
     Float(f32),
     String(String),
     StringUtf16(String),
     Map(Vec<(UObjectPropertyData, UObjectPropertyData)>),
+    Set(Vec<UObjectPropertyData>, Vec<UObjectPropertyData>), // Items to remove, items
     Name(String),
     UInt16(u16),
     UInt32(u32),
     Int8(i8),
     Int16(i16),
     Int32(i32),
+    Int64(i64),
+    UInt64(u64),
+    Double(f64),
+    Text(u32, Box<FTextHistory>),
+    TextRaw(#[serde(with = "base64_string")] Vec<u8>), // Text with a history type that couldn't be decoded
+    Object(ObjectReference),
+    Class(ObjectReference),
+    WeakObject(ObjectReference),
+    LazyObject(#[serde(with = "guid_string")] [u8;16]),
+    SoftObject(String, String), // Asset path name, sub path
+    SoftClass(String, String),
+    Zero(String), // An unversioned property of the given type, whose value is zero and isn't serialized
 }
 
 impl UObjectPropertyData {
+    /// Whether a map key can be written in the text format - the value types that print on a single line
+    fn is_printable_map_key(&self) -> bool {
+        matches!(self, Self::Enum(_) | Self::Int32(_) | Self::UInt16(_) | Self::String(_) | Self::Float(_) | Self::Byte(_) | Self::BoolValue(_)
+            | Self::Int64(_) | Self::UInt64(_) | Self::Double(_) | Self::Name(_) | Self::Object(_) | Self::Class(_) | Self::WeakObject(_))
+    }
+
+    fn is_object_map_key_type(key_type: &str) -> bool {
+        matches!(key_type, "ObjectProperty" | "ClassProperty" | "WeakObjectProperty")
+    }
+
     pub fn get_string_type(&self) -> &str {
         match self {
             UObjectPropertyData::Array(_, _) => "ArrayProperty",
             UObjectPropertyData::Bool => "BoolProperty",
+            UObjectPropertyData::BoolValue(_) => "BoolProperty",
             UObjectPropertyData::Byte(_) => "ByteProperty",
             UObjectPropertyData::Enum(_) => "EnumProperty",
             UObjectPropertyData::Struct(_,_) => "StructProperty",
+            UObjectPropertyData::NativeStruct(_,_) => "StructProperty",
             UObjectPropertyData::Float(_) => "FloatProperty",
             UObjectPropertyData::String(_) => "StrProperty",
             UObjectPropertyData::StringUtf16(_) => "StrProperty",
             UObjectPropertyData::Map(_) => "MapProperty",
+            UObjectPropertyData::Set(_, _) => "SetProperty",
             UObjectPropertyData::Name(_) => "NameProperty",
             UObjectPropertyData::UInt16(_) => "UInt16Property",
             UObjectPropertyData::UInt32(_) => "UInt32Property",
             UObjectPropertyData::Int8(_) => "Int8Property",
             UObjectPropertyData::Int16(_) => "Int16Property",
             UObjectPropertyData::Int32(_) => "IntProperty",
+            UObjectPropertyData::Int64(_) => "Int64Property",
+            UObjectPropertyData::UInt64(_) => "UInt64Property",
+            UObjectPropertyData::Double(_) => "DoubleProperty",
+            UObjectPropertyData::Text(_, _) => "TextProperty",
+            UObjectPropertyData::TextRaw(_) => "TextProperty",
+            UObjectPropertyData::Object(_) => "ObjectProperty",
+            UObjectPropertyData::Class(_) => "ClassProperty",
+            UObjectPropertyData::WeakObject(_) => "WeakObjectProperty",
+            UObjectPropertyData::LazyObject(_) => "LazyObjectProperty",
+            UObjectPropertyData::SoftObject(_, _) => "SoftObjectProperty",
+            UObjectPropertyData::SoftClass(_, _) => "SoftClassProperty",
+            UObjectPropertyData::Zero(r#type) => r#type,
+        }
+    }
+
+    pub fn collect_names<'a>(&'a self, names: &mut Vec<&'a str>) {
+        match self {
+            Self::Array(items, struct_meta) => {
+                if let Some((item_schema, array_name)) = struct_meta {
+                    names.push(&item_schema.name);
+                    names.push(&item_schema.r#type);
+                    names.push(array_name);
+                }
+                for item in items {
+                    item.collect_names(names);
+                }
+            },
+            Self::Enum(enum_val) | Self::Name(enum_val) => names.push(enum_val),
+            Self::SoftObject(asset_path, _) | Self::SoftClass(asset_path, _) => names.push(asset_path),
+            Self::Struct(props, _) => {
+                if !props.is_empty() {
+                    names.push("None");
+                }
+                for prop in props {
+                    prop.collect_names(names);
+                }
+            },
+            Self::Map(entries) => {
+                for (key, val) in entries {
+                    key.collect_names(names);
+                    val.collect_names(names);
+                }
+            },
+            Self::Set(items_to_remove, items) => {
+                for item in items_to_remove.iter().chain(items) {
+                    item.collect_names(names);
+                }
+            },
+            _ => {}
         }
     }
 
-    pub fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R, r#type: &str, metadata: &UObjectPropertyMetadata, name_map: &[String], expected_size: usize) -> Result<Self, Box<dyn Error>> {
+    pub fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R, r#type: &str, metadata: &UObjectPropertyMetadata, summary: &UObjectSummary, expected_size: usize) -> Result<Self, ParseError> {
         match r#type {
             "ArrayProperty" => {
-                let len = reader.read_u32::<E>().unwrap() as usize;
-                let mut items = Vec::with_capacity(len);
+                let len = reader.parse_u32::<E>()? as usize;
+                // Every item takes at least a byte, so a corrupt length can't ask for more than the data holds
+                let mut items = Vec::with_capacity(len.min(expected_size));
 
                 let item_type = match metadata {
                     UObjectPropertyMetadata::Array(v) => v,
-                    _ => panic!("ArrayProperty should always have Array metadata!")
+                    other => Err(ParseError::type_mismatch(reader.offset(), "Array metadata", other.kind()))?
                 };
 
                 let struct_meta = if item_type == "StructProperty" {
-                    let item_schema = UObjectPropertyHeader::from_buffer::<R,E>(reader, name_map).expect("Array property missing item definition!");
-                    let array_name = name_map[reader.read_u64::<E>().unwrap() as usize].clone();
-                    let mut _additional_unknown_data = vec![0;17];
-                    reader.read_exact(&mut _additional_unknown_data).unwrap();
-                    assert!(vec![0;17].eq(&_additional_unknown_data), "Array of struct metadata wasn't empty!");
+                    let schema_offset = reader.offset();
+                    let item_schema = UObjectPropertyHeader::from_buffer::<R,E>(reader, summary)?
+                        .ok_or(ParseError::invalid(schema_offset, "Array of structs is missing its item definition"))?;
+                    let array_name = name_from_buffer::<R,E>(reader, summary)?;
+                    let padding_offset = reader.offset();
+                    let mut _additional_unknown_data = vec![0;summary.property_layout().array_struct_padding_len];
+                    reader.parse_exact(&mut _additional_unknown_data)?;
+                    if _additional_unknown_data.iter().any(|b| *b != 0) {
+                        Err(ParseError::invalid(padding_offset, "Array of struct metadata wasn't empty"))?;
+                    }
+                    // Native structs can be decoded when every item has the codec's size
+                    if let Some(codec) = find_struct_codec(&array_name).filter(|c| c.byte_len() * len == item_schema.1) {
+                        for i in 0..len {
+                            let values = codec.read_values::<R,E>(reader).map_err(|err| err.in_property(&format!("[{i}]")))?;
+                            items.push(UObjectPropertyData::NativeStruct(array_name.clone(), values));
+                        }
+                    }
                     Some((item_schema.0, array_name))
                 } else {
                     None
                 };
                 
-                for _ in 0..len {
-                    items.push(UObjectPropertyData::from_buffer::<_,E>(reader, item_type, metadata, name_map, expected_size)?);
+                for i in items.len()..len {
+                    items.push(UObjectPropertyData::from_buffer::<_,E>(reader, item_type, metadata, summary, expected_size)
+                        .map_err(|err| err.in_property(&format!("[{i}]")))?);
                 }
                 Ok(UObjectPropertyData::Array(items, struct_meta))
             },
             "BoolProperty" => {
-                Ok(UObjectPropertyData::Bool)
+                match metadata {
+                    UObjectPropertyMetadata::Bool(_) => Ok(UObjectPropertyData::Bool),
+                    _ => Ok(UObjectPropertyData::BoolValue(reader.parse_u8()? > 0)),
+                }
             },
             "ByteProperty" => {
-                let val = reader.read_u8().unwrap();
+                let val = reader.parse_u8()?;
                 Ok(UObjectPropertyData::Byte(val))
             },
             "EnumProperty" => {
-                Ok(UObjectPropertyData::Enum(name_map[reader.read_u64::<E>().unwrap() as usize].clone()))
+                Ok(UObjectPropertyData::Enum(name_from_buffer::<R,E>(reader, summary)?))
             },
             "StructProperty" => {
+                if let UObjectPropertyMetadata::Struct(struct_type, guid) = metadata {
+                    if let Some(codec) = find_struct_codec(struct_type).filter(|c| *guid == [0;16] && c.byte_len() == expected_size) {
+                        return Ok(UObjectPropertyData::NativeStruct(struct_type.clone(), codec.read_values::<R,E>(reader)?));
+                    }
+                }
+
                 //HACK - dunno how to handle struct data that doesn't look like regular properties
-                let next_name = reader.read_u64::<E>().unwrap();
-                reader.seek(SeekFrom::Current(-8)).unwrap();
-                if next_name == 0 || next_name >= name_map.len() as u64 {
-                    let mut additional_data = vec![0;expected_size];
-                    reader.read_exact(&mut additional_data).unwrap();
-                    return Ok(UObjectPropertyData::Struct(vec![], additional_data));
+                let next_name = reader.parse_u64::<E>()?;
+                reader.parse_seek(SeekFrom::Current(-8))?;
+                if next_name == 0 || next_name >= summary.name_map.len() as u64 {
+                    return Ok(UObjectPropertyData::Struct(vec![], reader.parse_vec(expected_size)?));
                 }
 
                 let mut props = vec![];
-                while let Some(prop) = UObjectProperty::from_buffer::<R,E>(reader, name_map)? {
+                while let Some(prop) = UObjectProperty::from_buffer::<R,E>(reader, summary)? {
                     props.push(prop);
                 }
                 Ok(UObjectPropertyData::Struct(props, vec![]))
             },
             "FloatProperty" => {
-                let val = reader.read_f32::<E>().unwrap();
+                let val = reader.parse_f32::<E>()?;
                 Ok(UObjectPropertyData::Float(val))
             },
             "StrProperty" => {
-                let len = reader.read_i32::<E>().unwrap();
-                match len.cmp(&0) {
-                    Ordering::Less => {
-                        let len = -len as usize;
-                        let mut raw_string = Vec::with_capacity(len-1);
-                        for _ in 0..len-1 {
-                            raw_string.push(reader.read_u16::<E>().unwrap());
-                        }
-                        if reader.read_u16::<E>().unwrap() != 0 {
-                            Err(format!("Malformed FString at byte 0x{:x} - length or termination byte is incorrect", reader.stream_position().unwrap()))?;
-                        }
-                        Ok(UObjectPropertyData::StringUtf16(String::from_utf16(&raw_string).unwrap()))
-                    },
-                    Ordering::Greater => {
-                        let len = len as usize;
-                        let mut raw_string = vec![0;len-1];
-                        reader.read_exact(&mut raw_string).unwrap();
-                        if reader.read_u8().unwrap() != 0 {
-                            Err(format!("Malformed FString at byte 0x{:x} - length or termination byte is incorrect", reader.stream_position().unwrap()))?;
-                        }
-                        Ok(UObjectPropertyData::String(String::from_utf8(raw_string).unwrap()))
-                    },
-                    Ordering::Equal => {
-                        Ok(UObjectPropertyData::String(String::new()))
+                Self::fstring_from_buffer::<R,E>(reader)
+            },
+            "TextProperty" => {
+                if !matches!(metadata, UObjectPropertyMetadata::None) {
+                    return Self::text_from_buffer::<R,E>(reader);
+                }
 
+                // Tagged text properties know their size, so unsupported histories can fall back to raw data
+                let mut data = Cursor::new(reader.parse_vec(expected_size)?);
+                match Self::text_from_buffer::<_,E>(&mut data) {
+                    Ok(text) if data.position() as usize == expected_size => Ok(text),
+                    Ok(_) | Err(_) => {
+                        eprintln!("WARNING: Unhandled TextProperty history type, keeping its raw data");
+                        Ok(UObjectPropertyData::TextRaw(data.into_inner()))
                     }
                 }
             },
             "MapProperty" => {
                 let (key_type, value_type) = match metadata {
                     UObjectPropertyMetadata::Map(key_type, value_type) => (key_type, value_type),
-                    _ => panic!("MapProperty should always have UObjectPropertyMetadata::Map present!"),
+                    other => Err(ParseError::type_mismatch(reader.offset(), "Map metadata", other.kind()))?,
                 };
 
-                let arr_size = reader.read_u32::<E>().unwrap() as usize;
-                let mut sets = Vec::with_capacity(arr_size);
-                for _ in 0..arr_size {
-                    let next_key = UObjectPropertyData::from_buffer::<R,E>(reader, key_type, metadata, name_map, expected_size)?;
-                    let next_value = UObjectPropertyData::from_buffer::<R,E>(reader, value_type, metadata, name_map, expected_size)?;
+                let arr_size = reader.parse_u32::<E>()? as usize;
+                let mut sets = Vec::with_capacity(arr_size.min(expected_size));
+                for i in 0..arr_size {
+                    let key_offset = reader.offset();
+                    let next_key = UObjectPropertyData::from_buffer::<R,E>(reader, key_type, metadata, summary, expected_size)
+                        .map_err(|err| err.in_property(&format!("[{i}].key")))?;
+                    if !next_key.is_printable_map_key() {
+                        Err(ParseError::invalid(key_offset, format!("Maps with {} keys aren't supported", next_key.get_string_type())).in_property(&format!("[{i}].key")))?;
+                    }
+                    let next_value = UObjectPropertyData::from_buffer::<R,E>(reader, value_type, metadata, summary, expected_size)
+                        .map_err(|err| err.in_property(&format!("[{i}].value")))?;
                     sets.push((next_key, next_value));
                 }
 
                 Ok(UObjectPropertyData::Map(sets))
             },
+            "SetProperty" => {
+                let item_type = match metadata {
+                    UObjectPropertyMetadata::Set(v) => v,
+                    other => Err(ParseError::type_mismatch(reader.offset(), "Set metadata", other.kind()))?,
+                };
+
+                let num_to_remove = reader.parse_u32::<E>()? as usize;
+                let mut items_to_remove = Vec::with_capacity(num_to_remove.min(expected_size));
+                for i in 0..num_to_remove {
+                    items_to_remove.push(UObjectPropertyData::from_buffer::<R,E>(reader, item_type, metadata, summary, expected_size)
+                        .map_err(|err| err.in_property(&format!("removed[{i}]")))?);
+                }
+
+                let len = reader.parse_u32::<E>()? as usize;
+                let mut items = Vec::with_capacity(len.min(expected_size));
+                for i in 0..len {
+                    items.push(UObjectPropertyData::from_buffer::<R,E>(reader, item_type, metadata, summary, expected_size)
+                        .map_err(|err| err.in_property(&format!("[{i}]")))?);
+                }
+
+                Ok(UObjectPropertyData::Set(items_to_remove, items))
+            },
             "NameProperty" => {
-                Ok(UObjectPropertyData::Name(name_map[reader.read_u64::<E>().unwrap() as usize].clone()))
+                Ok(UObjectPropertyData::Name(name_from_buffer::<R,E>(reader, summary)?))
             },
             "UInt16Property" => {
-                Ok(UObjectPropertyData::UInt16(reader.read_u16::<E>().unwrap()))
+                Ok(UObjectPropertyData::UInt16(reader.parse_u16::<E>()?))
             },
             "UInt32Property" => {
-                Ok(UObjectPropertyData::UInt32(reader.read_u32::<E>().unwrap()))
+                Ok(UObjectPropertyData::UInt32(reader.parse_u32::<E>()?))
             },
             "Int8Property" => {
-                Ok(UObjectPropertyData::Int8(reader.read_i8().unwrap()))
+                Ok(UObjectPropertyData::Int8(reader.parse_i8()?))
             },
             "Int16Property" => {
-                Ok(UObjectPropertyData::Int16(reader.read_i16::<E>().unwrap()))
+                Ok(UObjectPropertyData::Int16(reader.parse_i16::<E>()?))
             },
             "IntProperty" => {
-                Ok(UObjectPropertyData::Int32(reader.read_i32::<E>().unwrap()))
+                Ok(UObjectPropertyData::Int32(reader.parse_i32::<E>()?))
+            },
+            "ObjectProperty" => {
+                Ok(UObjectPropertyData::Object(ObjectReference::from_buffer::<R,E>(reader, summary)?))
+            },
+            "ClassProperty" => {
+                Ok(UObjectPropertyData::Class(ObjectReference::from_buffer::<R,E>(reader, summary)?))
+            },
+            "WeakObjectProperty" => {
+                Ok(UObjectPropertyData::WeakObject(ObjectReference::from_buffer::<R,E>(reader, summary)?))
+            },
+            "LazyObjectProperty" => {
+                let mut guid = [0;16];
+                reader.parse_exact(&mut guid)?;
+                Ok(UObjectPropertyData::LazyObject(guid))
+            },
+            "SoftObjectProperty" | "SoftClassProperty" => {
+                let asset_path = name_from_buffer::<R,E>(reader, summary)?;
+                let sub_path = match Self::fstring_from_buffer::<R,E>(reader)? {
+                    UObjectPropertyData::String(v) | UObjectPropertyData::StringUtf16(v) => v,
+                    _ => unreachable!(),
+                };
+                if r#type == "SoftObjectProperty" {
+                    Ok(UObjectPropertyData::SoftObject(asset_path, sub_path))
+                } else {
+                    Ok(UObjectPropertyData::SoftClass(asset_path, sub_path))
+                }
+            },
+            "Int64Property" => {
+                Ok(UObjectPropertyData::Int64(reader.parse_i64::<E>()?))
+            },
+            "UInt64Property" => {
+                Ok(UObjectPropertyData::UInt64(reader.parse_u64::<E>()?))
+            },
+            "DoubleProperty" => {
+                Ok(UObjectPropertyData::Double(reader.parse_f64::<E>()?))
             },
             _ => {
                 //Err(format!("Unhandled property type: {}", r#type))?
                 eprintln!("WARNING: Unhandled property type: {}  # Expect errors.", r#type);
                 
-                let mut additional_data = vec![0;expected_size];
-                reader.read_exact(&mut additional_data).unwrap();
-                Ok(UObjectPropertyData::Struct(vec![], additional_data))
+                Ok(UObjectPropertyData::Struct(vec![], reader.parse_vec(expected_size)?))
             }
         }
     }
 
-    pub fn to_bytes<W: Write, E: byteorder::ByteOrder>(&self, writer: &mut W, name_map: &[String]) -> usize {
-        match self {
+    pub fn to_bytes<W: Write, E: byteorder::ByteOrder>(&self, writer: &mut W, summary: &UObjectSummary) -> Result<usize, EncodeError> {
+        Ok(match self {
             Self::Array(items, struct_meta) => {
-                writer.write_u32::<E>(items.len() as u32).unwrap();
+                writer.write_u32::<E>(items.len() as u32)?;
                 let mut written_len = 4;
 
                 let mut data = Cursor::new(vec![]);
-                for i in items {
-                    i.to_bytes::<Cursor<Vec<u8>>,E>(&mut data, name_map);
+                for (i, item) in items.iter().enumerate() {
+                    item.to_bytes::<Cursor<Vec<u8>>,E>(&mut data, summary).map_err(|err| err.in_property(&format!("[{i}]")))?;
                 }
                 let data = data.into_inner();
 
                 if let Some((item_schema, array_name)) = struct_meta {
-                    item_schema.to_bytes::<W,E>(writer, name_map, data.len());
+                    item_schema.to_bytes::<W,E>(writer, summary, data.len())?;
                     written_len += UObjectPropertyHeader::byte_len();
-                    let array_name_index = name_map.iter().position(|n| n == array_name).unwrap_or_else(|| panic!("Object type [{}] wasn't in name map", array_name)) as u64;
-                    writer.write_u64::<E>(array_name_index).unwrap();
+                    writer.write_u64::<E>(name_index(summary, array_name)?)?;
                     written_len += 8;
-                    let additional_unknown_data = [0u8;17];
-                    writer.write_all(&additional_unknown_data).unwrap();
-                    written_len += 17;
+                    let additional_unknown_data = vec![0u8;summary.property_layout().array_struct_padding_len];
+                    writer.write_all(&additional_unknown_data)?;
+                    written_len += additional_unknown_data.len();
                 }
 
-                writer.write_all(&data).unwrap(); // len += data.len()
+                writer.write_all(&data)?; // len += data.len()
                 written_len += data.len();
                 
                 written_len
@@ -618,42 +2015,48 @@ impl UObjectPropertyData {
             Self::Bool => {
                 0 
             },
+            Self::BoolValue(val) => {
+                writer.write_u8(*val as u8)?;
+                1
+            },
             Self::Byte(val, ) => {
-                writer.write_u8(*val).unwrap();
+                writer.write_u8(*val)?;
                 1
             },
             Self::Enum(enum_val) => {
-                writer.write_u64::<E>(name_map.iter().position(|n| n == enum_val).unwrap_or_else(|| panic!("Object type [{enum_val}] wasn't in name map")) as u64).unwrap();
+                writer.write_u64::<E>(name_index(summary, enum_val)?)?;
                 8
             },
             Self::Struct(val, raw) => {
                 let mut len = 0;
                 if !val.is_empty() {
                     for v in val {
-                        len += v.to_bytes::<W,E>(writer, name_map);
+                        len += v.to_bytes::<W,E>(writer, summary)?;
                     }
-                    let none_index = name_map.iter().position(|n| n == "None").unwrap_or_else(|| panic!("Object type [None] wasn't in name map")) as u64;
-                    writer.write_u64::<E>(none_index).unwrap();
+                    writer.write_u64::<E>(name_index(summary, "None")?)?;
                     len += std::mem::size_of::<u64>();
                 } else {
-                    writer.write_all(raw).unwrap();
+                    writer.write_all(raw)?;
                     len += raw.len();
                 }
                 len
             },
+            Self::NativeStruct(_, values) => {
+                StructCodec::write_values::<W,E>(values, writer)?
+            },
             Self::Float(val) => {
-                writer.write_f32::<E>(*val).unwrap();
+                writer.write_f32::<E>(*val)?;
                 4
             },
             Self::String(val) => {
                 let len = if val.is_empty() {
-                    writer.write_u32::<E>(0).unwrap();
+                    writer.write_u32::<E>(0)?;
                     0
                 } else {
                     let len = val.len() + 1; // +1 for termination byte
-                    writer.write_u32::<E>(len as u32).unwrap();
-                    writer.write_all(val.as_bytes()).unwrap();
-                    writer.write_u8(0).unwrap();  // FString termination byte
+                    writer.write_u32::<E>(len as u32)?;
+                    writer.write_all(val.as_bytes())?;
+                    writer.write_u8(0)?;  // FString termination byte
                     len
                 };
                 
@@ -662,49 +2065,110 @@ impl UObjectPropertyData {
             Self::StringUtf16(val) => {
                 let bytes: Vec<u16> = val.encode_utf16().collect();
                 let len = bytes.len() + 1;
-                writer.write_i32::<E>(-(len as i32)).unwrap();
+                writer.write_i32::<E>(-(len as i32))?;
                 for char in bytes {
-                    writer.write_u16::<E>(char).unwrap();
+                    writer.write_u16::<E>(char)?;
                 }
-                writer.write_u16::<E>(0).unwrap();  // FString termination byte
+                writer.write_u16::<E>(0)?;  // FString termination byte
                 
                 4 + (len * 2)
             },
             Self::Map(val) => {
-                writer.write_u32::<E>(val.len() as u32).unwrap();
-                let mut size = 8; // Seems like final size is 8 + map data size...?
-                for v in val {
-                    size += v.0.to_bytes::<W,E>(writer, name_map);
-                    size += v.1.to_bytes::<W,E>(writer, name_map);
+                writer.write_u32::<E>(val.len() as u32)?;
+                let mut size = summary.property_layout().map_header_len; // The keys to remove are read with the tag metadata
+                for (i, (key, value)) in val.iter().enumerate() {
+                    size += key.to_bytes::<W,E>(writer, summary).map_err(|err| err.in_property(&format!("[{i}].key")))?;
+                    size += value.to_bytes::<W,E>(writer, summary).map_err(|err| err.in_property(&format!("[{i}].value")))?;
                 }
 
                 size
             },
+            Self::Set(items_to_remove, items) => {
+                let mut size = 0;
+                for (list, segment) in [(items_to_remove, "removed"), (items, "")] {
+                    writer.write_u32::<E>(list.len() as u32)?;
+                    size += 4;
+                    for (i, item) in list.iter().enumerate() {
+                        size += item.to_bytes::<W,E>(writer, summary).map_err(|err| err.in_property(&format!("{segment}[{i}]")))?;
+                    }
+                }
+                size
+            },
             Self::Name(val) => {
-                writer.write_u64::<E>(name_map.iter().position(|n| n == val).unwrap_or_else(|| panic!("Object type [{val}] wasn't in name map")) as u64).unwrap();
+                writer.write_u64::<E>(name_index(summary, val)?)?;
                 8
             }
             Self::UInt16(val) => {
-                writer.write_u16::<E>(*val).unwrap();
+                writer.write_u16::<E>(*val)?;
                 2
             },
             Self::UInt32(val) => {
-                writer.write_u32::<E>(*val).unwrap();
+                writer.write_u32::<E>(*val)?;
                 4
             },
             Self::Int8(val) => {
-                writer.write_i8(*val).unwrap();
+                writer.write_i8(*val)?;
                 1
             },
             Self::Int16(val) => {
-                writer.write_i16::<E>(*val).unwrap();
+                writer.write_i16::<E>(*val)?;
                 2
             },
             Self::Int32(val) => {
-                writer.write_i32::<E>(*val).unwrap();
+                writer.write_i32::<E>(*val)?;
                 4
             },
-        }
+            Self::Int64(val) => {
+                writer.write_i64::<E>(*val)?;
+                8
+            },
+            Self::UInt64(val) => {
+                writer.write_u64::<E>(*val)?;
+                8
+            },
+            Self::Double(val) => {
+                writer.write_f64::<E>(*val)?;
+                8
+            },
+            Self::Object(reference) | Self::Class(reference) | Self::WeakObject(reference) => {
+                reference.to_bytes::<W,E>(writer, summary)?
+            },
+            Self::LazyObject(guid) => {
+                writer.write_all(guid)?;
+                16
+            },
+            Self::TextRaw(data) => {
+                writer.write_all(data)?;
+                data.len()
+            },
+            Self::Zero(r#type) => Err(EncodeError::unsupported(format!("Zero {type} values can only be used in exports with unversioned properties")))?,
+            Self::SoftObject(asset_path, sub_path) | Self::SoftClass(asset_path, sub_path) => {
+                writer.write_u64::<E>(name_index(summary, asset_path)?)?;
+                let sub_path = if sub_path.is_ascii() { Self::String(sub_path.clone()) } else { Self::StringUtf16(sub_path.clone()) };
+                8 + sub_path.to_bytes::<W,E>(writer, summary)?
+            },
+            Self::Text(flags, history) => {
+                writer.write_u32::<E>(*flags)?;
+                let mut len = 4 + 1;
+                match history.as_ref() {
+                    FTextHistory::None(culture_invariant_string) => {
+                        writer.write_i8(-1)?;
+                        writer.write_u32::<E>(culture_invariant_string.is_some() as u32)?;
+                        len += 4;
+                        if let Some(val) = culture_invariant_string {
+                            len += val.to_bytes::<W,E>(writer, summary)?;
+                        }
+                    },
+                    FTextHistory::Base(namespace, key, source_string) => {
+                        writer.write_i8(0)?;
+                        len += namespace.to_bytes::<W,E>(writer, summary)?;
+                        len += key.to_bytes::<W,E>(writer, summary)?;
+                        len += source_string.to_bytes::<W,E>(writer, summary)?;
+                    }
+                }
+                len
+            },
+        })
     }
 
     pub fn to_string<W: Write>(&self, metadata: &UObjectPropertyMetadata, writer: &mut W, indent_spaces: usize) {
@@ -741,10 +2205,13 @@ impl UObjectPropertyData {
                     writer.write_all("false\n".as_bytes()).unwrap();
                 }
             },
+            Self::BoolValue(val) => {
+                writer.write_all(format!("{val}\n").as_bytes()).unwrap();
+            },
             Self::Byte(val) => {
                 let (enum_name, metadata_val) = match metadata {
                     UObjectPropertyMetadata::Byte(e,m) => (e,m),
-                    UObjectPropertyMetadata::Array(_) => (&0, &0), // Bytes seem to be able to be in arrays without needing metadata
+                    UObjectPropertyMetadata::Array(_) | UObjectPropertyMetadata::Set(_) | UObjectPropertyMetadata::Map(_, _) => (&0, &0), // Bytes seem to be able to be in arrays without needing metadata
                     _ => panic!("Byte property data must have byte metadata")
                 };
                 writer.write_all(format!("!ByteProperty {enum_name:x} {metadata_val:x} {val:x}\n").as_bytes()).unwrap();
@@ -758,8 +2225,20 @@ impl UObjectPropertyData {
                 writer.write_all(format!("!EnumProperty {enum_name} {sanitized_val}\n").as_bytes()).unwrap();
             },
             Self::Struct(val, raw) => {
-                if let UObjectPropertyMetadata::Struct(data) = metadata {
-                    writer.write_all(format!("!struct {} {}", BASE64_STANDARD.encode(data), BASE64_STANDARD.encode(raw)).as_bytes()).unwrap();
+                match metadata {
+                    UObjectPropertyMetadata::Struct(struct_type, guid) => {
+                        writer.write_all(format!("!struct {struct_type}").as_bytes()).unwrap();
+                        if *guid != [0;16] {
+                            writer.write_all(format!(" {}", guid_to_string(guid)).as_bytes()).unwrap();
+                        }
+                        if !raw.is_empty() {
+                            writer.write_all(format!(" {}", BASE64_STANDARD.encode(raw)).as_bytes()).unwrap();
+                        }
+                    },
+                    UObjectPropertyMetadata::StructRaw(data) => {
+                        writer.write_all(format!("!struct {} {}", BASE64_STANDARD.encode(data), BASE64_STANDARD.encode(raw)).as_bytes()).unwrap();
+                    },
+                    _ => {}
                 }
                 writer.write_all("\n".as_bytes()).unwrap();
                 for v in val {
@@ -767,6 +2246,20 @@ impl UObjectPropertyData {
                     v.to_string::<W>(writer, indent_spaces + 2);
                 }
             },
+            Self::NativeStruct(struct_type, values) => match find_struct_codec(struct_type) {
+                Some(codec) => writer.write_all(format!("!{struct_type} {}\n", codec.format_values(values)).as_bytes()).unwrap(),
+                None => {
+                    // Only YAML can name a struct without a codec - its values are kept as raw struct data instead
+                    eprintln!("WARNING: Struct type [{struct_type}] has no codec, so its values are written as raw data");
+                    let mut raw = vec![];
+                    StructCodec::write_values::<_,LE>(values, &mut raw).unwrap();
+                    let guid = match metadata {
+                        UObjectPropertyMetadata::Struct(_, guid) => *guid,
+                        _ => [0;16],
+                    };
+                    Self::Struct(vec![], raw).to_string(&UObjectPropertyMetadata::Struct(struct_type.clone(), guid), writer, indent_spaces);
+                },
+            },
             Self::Float(val) => {
                 writer.write_all(format!("{val:.}\n").as_bytes()).unwrap();
             },
@@ -802,12 +2295,41 @@ impl UObjectPropertyData {
                         Self::String(v) => v.clone(),
                         Self::Float(v) => format!("{v:.}"),
                         Self::Byte(v) => format!("{v:x}"),
+                        Self::BoolValue(v) => v.to_string(),
+                        Self::Int64(v) => v.to_string(),
+                        Self::UInt64(v) => v.to_string(),
+                        Self::Double(v) => v.to_string(),
+                        Self::Name(v) => v.clone(),
+                        Self::Object(v) | Self::Class(v) | Self::WeakObject(v) => v.to_string(),
                         _ => panic!("Unprintable map key type: {key_type}")
                     };
-                    writer.write_all(format!("{}- {}:", " ".repeat(indent_spaces + 4), key_string).as_bytes()).unwrap();
+                    // Object references hold colons, so their keys end at a colon followed by a space
+                    let separator = if Self::is_object_map_key_type(key_type) { ": " } else { ":" };
+                    writer.write_all(format!("{}- {}{separator}", " ".repeat(indent_spaces + 4), key_string).as_bytes()).unwrap();
                     v.1.to_string::<W>(metadata,writer, indent_spaces + 6);
                 }
             },
+            Self::Set(items_to_remove, items) => {
+                let item_type = match metadata {
+                    UObjectPropertyMetadata::Set(i) => i,
+                    _ => panic!("Set property data must have set metadata")
+                };
+
+                writer.write_all("!Set\n".as_bytes()).unwrap();
+                writer.write_all(format!("{}item_type: {item_type}\n", " ".repeat(indent_spaces + 2)).as_bytes()).unwrap();
+                if !items_to_remove.is_empty() {
+                    writer.write_all(format!("{}items_to_remove:\n", " ".repeat(indent_spaces + 2)).as_bytes()).unwrap();
+                    for (i, item) in items_to_remove.iter().enumerate() {
+                        writer.write_all(format!("{}- {}:", " ".repeat(indent_spaces + 2), i).as_bytes()).unwrap();
+                        item.to_string(metadata, writer, indent_spaces + 4);
+                    }
+                }
+                writer.write_all(format!("{}items:\n", " ".repeat(indent_spaces + 2)).as_bytes()).unwrap();
+                for (i, item) in items.iter().enumerate() {
+                    writer.write_all(format!("{}- {}:", " ".repeat(indent_spaces + 2), i).as_bytes()).unwrap();
+                    item.to_string(metadata, writer, indent_spaces + 4);
+                }
+            },
             Self::Name(val) => {
                 writer.write_all(format!("!name {val}\n").as_bytes()).unwrap();
             },
@@ -826,115 +2348,277 @@ impl UObjectPropertyData {
             Self::Int32(val) => {
                 writer.write_all(format!("!i32 {val}\n").as_bytes()).unwrap();
             },
+            Self::Int64(val) => {
+                writer.write_all(format!("!i64 {val}\n").as_bytes()).unwrap();
+            },
+            Self::UInt64(val) => {
+                writer.write_all(format!("!u64 {val}\n").as_bytes()).unwrap();
+            },
+            Self::Double(val) => {
+                writer.write_all(format!("!f64 {val}\n").as_bytes()).unwrap();
+            },
+            Self::Object(reference) => {
+                writer.write_all(format!("!object {reference}\n").as_bytes()).unwrap();
+            },
+            Self::Class(reference) => {
+                writer.write_all(format!("!class {reference}\n").as_bytes()).unwrap();
+            },
+            Self::WeakObject(reference) => {
+                writer.write_all(format!("!weakobject {reference}\n").as_bytes()).unwrap();
+            },
+            Self::Zero(r#type) => {
+                writer.write_all(format!("!zero {type}\n").as_bytes()).unwrap();
+            },
+            Self::TextRaw(data) => {
+                writer.write_all(format!("!rawtext {}\n", BASE64_STANDARD.encode(data)).as_bytes()).unwrap();
+            },
+            Self::LazyObject(guid) => {
+                let guid: String = guid.iter().map(|b| format!("{b:02x}")).collect();
+                writer.write_all(format!("!lazyobject {guid}\n").as_bytes()).unwrap();
+            },
+            Self::SoftObject(asset_path, sub_path) | Self::SoftClass(asset_path, sub_path) => {
+                let tag = if matches!(self, Self::SoftObject(_, _)) { "!softobject" } else { "!softclass" };
+                if sub_path.is_empty() {
+                    writer.write_all(format!("{tag} {asset_path}\n").as_bytes()).unwrap();
+                } else {
+                    writer.write_all(format!("{tag} {asset_path} {sub_path}\n").as_bytes()).unwrap();
+                }
+            },
+            Self::Text(flags, history) => {
+                let indention = " ".repeat(indent_spaces + 2);
+                writer.write_all("!Text\n".as_bytes()).unwrap();
+                writer.write_all(format!("{indention}flags: {flags}\n").as_bytes()).unwrap();
+                match history.as_ref() {
+                    FTextHistory::None(culture_invariant_string) => {
+                        writer.write_all(format!("{indention}history: None\n").as_bytes()).unwrap();
+                        if let Some(val) = culture_invariant_string {
+                            writer.write_all(format!("{indention}culture_invariant_string: ").as_bytes()).unwrap();
+                            val.to_string(metadata, writer, indent_spaces + 2);
+                        }
+                    },
+                    FTextHistory::Base(namespace, key, source_string) => {
+                        writer.write_all(format!("{indention}history: Base\n").as_bytes()).unwrap();
+                        writer.write_all(format!("{indention}namespace: ").as_bytes()).unwrap();
+                        namespace.to_string(metadata, writer, indent_spaces + 2);
+                        writer.write_all(format!("{indention}key: ").as_bytes()).unwrap();
+                        key.to_string(metadata, writer, indent_spaces + 2);
+                        writer.write_all(format!("{indention}source_string: ").as_bytes()).unwrap();
+                        source_string.to_string(metadata, writer, indent_spaces + 2);
+                    }
+                }
+            },
+        }
+    }
+
+    /// Reads an FString as either String or StringUtf16 data, depending on how it was stored
+    fn fstring_from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R) -> Result<Self, ParseError> {
+        let offset = reader.offset();
+        let len = reader.parse_i32::<E>()?;
+        let malformed = || ParseError::MalformedFString { offset, path: String::new() };
+        match len.cmp(&0) {
+            Ordering::Less => {
+                let data = reader.parse_vec(len.unsigned_abs() as usize * 2)?;
+                let (raw_string, terminator) = data.split_at(data.len() - 2);
+                if terminator != [0, 0] {
+                    Err(malformed())?;
+                }
+                let raw_string = raw_string.chunks(2).map(E::read_u16).collect::<Vec<_>>();
+                Ok(UObjectPropertyData::StringUtf16(String::from_utf16(&raw_string).map_err(|_| malformed())?))
+            },
+            Ordering::Greater => {
+                let mut raw_string = reader.parse_vec(len as usize)?;
+                if raw_string.pop() != Some(0) {
+                    Err(malformed())?;
+                }
+                Ok(UObjectPropertyData::String(String::from_utf8(raw_string).map_err(|_| malformed())?))
+            },
+            Ordering::Equal => {
+                Ok(UObjectPropertyData::String(String::new()))
+
+            }
+        }
+    }
+
+    /// Reads an FText (flags, history type and history data)
+    pub(crate) fn text_from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R) -> Result<Self, ParseError> {
+        let flags = reader.parse_u32::<E>()?;
+        let history = match reader.parse_i8()? {
+            -1 => {
+                let has_culture_invariant_string = reader.parse_u32::<E>()? != 0;
+                FTextHistory::None(if has_culture_invariant_string { Some(Self::fstring_from_buffer::<R,E>(reader)?) } else { None })
+            },
+            0 => {
+                let namespace = Self::fstring_from_buffer::<R,E>(reader)?;
+                let key = Self::fstring_from_buffer::<R,E>(reader)?;
+                let source_string = Self::fstring_from_buffer::<R,E>(reader)?;
+                FTextHistory::Base(namespace, key, source_string)
+            },
+            other => Err(ParseError::invalid(reader.offset() - 1, format!("Unsupported FText history type {other}")))?
+        };
+        Ok(UObjectPropertyData::Text(flags, Box::new(history)))
+    }
+
+    /// Reads a string value written by [`Self::to_string`] for String or StringUtf16 data
+    fn fstring_from_string(val: &str) -> Self {
+        let val = val.trim();
+        if let Some(utf16val) = val.strip_prefix("!utf16 ") {
+            UObjectPropertyData::StringUtf16(utf16val.replace("\\n", "\n"))
+        } else if val.starts_with("!EmptyString") {
+            UObjectPropertyData::String(String::new())
+        } else {
+            let val = val.strip_prefix('"').and_then(|v| v.strip_suffix('"')).unwrap_or(val);
+            UObjectPropertyData::String(val.replace("\\n", "\n"))
+        }
+    }
+
+    /// Bools read from text keep their value in the metadata, like a tagged property would.  Array and map
+    /// items don't have tags, so the value needs to move into the data.
+    fn container_item((data, metadata): (Self, UObjectPropertyMetadata)) -> (Self, UObjectPropertyMetadata) {
+        match (data, &metadata) {
+            (Self::Bool, UObjectPropertyMetadata::Bool(val)) => (Self::BoolValue(*val), metadata),
+            (data, _) => (data, metadata),
         }
     }
 
-    pub fn from_string<R: BufRead + Seek>(val: &str, reader: &mut R, expected_indent_level: usize) -> Result<(Self, UObjectPropertyMetadata), Box<dyn Error>> {
+    pub fn from_string<R: BufRead + Seek>(val: &str, reader: &mut R, expected_indent_level: usize) -> Result<(Self, UObjectPropertyMetadata), TextErrors> {
         let val = val.trim();
-        if val.is_empty() || val.starts_with("!struct") { // Struct start
+        let native_struct = val.strip_prefix('!')
+            .and_then(|v| v.split_once(' '))
+            .filter(|(_, fields)| fields.trim_start().starts_with('{'))
+            .and_then(|(struct_type, fields)| find_struct_codec(struct_type).map(|codec| (struct_type, fields, codec)));
+        if let Some((struct_type, fields, codec)) = native_struct {
+            Ok((
+                UObjectPropertyData::NativeStruct(struct_type.to_owned(), codec.parse_values(struct_type, fields).map_err(|e| reader.text_error(e.to_string()).at_token(fields))?),
+                UObjectPropertyMetadata::Struct(struct_type.to_owned(), [0;16])
+            ))
+        } else if val.is_empty() || val.starts_with("!struct") { // Struct start
             let (meta, raw) = if val.is_empty() {
                 (UObjectPropertyMetadata::None, vec![])
             } else {
-                let mut vals = val.split_whitespace();
+                let mut vals = val.split_whitespace().peekable();
                 vals.next().unwrap(); // !struct
 
-                let err = format!("Error at 0x{:x}: !struct should have one or two base64 parameters", reader.stream_position().unwrap());
-                let meta = BASE64_STANDARD.decode(vals.next().ok_or(err.clone())?).map_err(|_| "Unable to read !struct metadata from base64 string. This value shouldn't be manually edited.".to_string())?;
-                let raw = vals.next().map(|v| BASE64_STANDARD.decode(v).map_err(|_| "Unable to read !struct metadata from base64 string. This value shouldn't be manually edited.".to_string())).transpose()?.unwrap_or(vec![]);
-                (UObjectPropertyMetadata::Struct(meta), raw)
+                let err = reader.text_error("!struct should have a struct type, an optional {guid} and optional base64 data");
+                let struct_type = vals.next().ok_or(err.clone())?;
+                let meta = match BASE64_STANDARD.decode(struct_type) {
+                    Ok(meta) if meta.len() == 25 => UObjectPropertyMetadata::StructRaw(meta), // Older files store the metadata as base64
+                    _ => {
+                        let guid = match vals.next_if(|v| v.starts_with('{')) {
+                            Some(guid) => guid_from_string(guid).ok_or_else(|| reader.text_error(format!("invalid struct guid {guid}")).at_token(guid))?,
+                            None => [0;16],
+                        };
+                        UObjectPropertyMetadata::Struct(struct_type.to_owned(), guid)
+                    }
+                };
+                let raw = vals.next().map(|v| BASE64_STANDARD.decode(v).map_err(|_| reader.text_error("unable to read !struct data from base64 string. This value shouldn't be manually edited.").at_token(v))).transpose()?.unwrap_or(vec![]);
+                (meta, raw)
             };
 
-            let mut props = vec![];
-            while let Some(prop) = UObjectProperty::from_string::<R>(reader, expected_indent_level + 2)? {
-                props.push(prop);
-            }
+            let props = properties_from_string(reader, expected_indent_level + 2)?;
             Ok((UObjectPropertyData::Struct(props, raw), meta))
         } else if val.starts_with("!Map") {
-            let start_position = reader.stream_position().unwrap();
+            let start_offset = reader.line_offset();
             let mut key_type:   Option<String> = None;
             let mut value_type: Option<String> = None;
             let mut sets = vec![];
+            let mut errors = vec![];
 
             for _ in 0..3 {
                 let next_line = next_nonempty_line(reader);
                 if !check_indent(&next_line, expected_indent_level + 2) {
-                    Err(format!("Map at 0x{start_position:x} should have properties (in order): key_type, val_type, map_data"))?;
+                    Err(reader.text_error("Map should have properties (in order): key_type, val_type, map_data"))?;
                 }
 
-                let (key, val) = next_line.split_once(':').ok_or(format!("Map at 0x{:x} - expected [key_type:] property, but got:\n{}", start_position, next_line.trim()))?;
+                let (key, val) = next_line.split_once(':').ok_or_else(|| reader.text_error("expected [key_type:] property"))?;
                 match key.trim() {
                     "key_type" => { key_type = Some(val.trim().to_owned()); },
                     "val_type" => { value_type = Some(val.trim().to_owned()); },
                     "map_data" => {
-                        if key_type.is_none() {
-                            Err(format!("Map at 0x{start_position:x} - key_type should come before map_data"))?;
-                        }
-                        if value_type.is_none() {
-                            Err(format!("Map at 0x{start_position:x} - val_type should come before map_data"))?;
-                        }
+                        let (Some(key_type), Some(val_type)) = (&key_type, &value_type) else {
+                            return Err(reader.text_error("key_type and val_type should come before map_data").into());
+                        };
 
-                        let format_err = format!("Map at 0x{start_position:x} - map_data should use format ' - key: value'");
+                        let format_err = "map_data should use format ' - key: value'";
                         loop {
                             let next_line = next_nonempty_line(reader);
                             if !next_line.trim().starts_with('-') || !check_indent(&next_line, expected_indent_level + 4) {
                                 reader.seek(SeekFrom::Current(-(next_line.len() as i64))).unwrap();
                                 break;
                             }
-    
-                            let (key, val) = next_line.split_once('-').ok_or(format_err.clone())?.1.split_once(':').ok_or(format_err.clone())?;
-                            let key = key.trim();
-                            let key = match key_type.as_ref().unwrap().as_str() {
-                                "IntProperty" => UObjectPropertyData::Int32(key.parse()?),
-                                "UInt16Property" => UObjectPropertyData::UInt16(key.parse()?),
-                                "StrProperty" => UObjectPropertyData::String(key.to_owned()),
-                                "FloatProperty" => UObjectPropertyData::Float(key.parse()?),
-                                "ByteProperty" => UObjectPropertyData::Byte(u8::from_str_radix(key, 16)?),
-                                "EnumProperty" => UObjectPropertyData::Enum(key.replace("->", "::")),
-                                other => Err(format!("Map at 0x{start_position:x} - unable to read data of key type '{other}'"))?,
-                            };
-                            let val = UObjectPropertyData::from_string::<R>(val, reader, expected_indent_level + 6)?;
-                            sets.push((key, val));
-                        }
 
-                        let val_type = value_type.as_ref().unwrap().as_str();
-                        for (_key, (item, _meta)) in &sets {
-                            let set_val_type = item.get_string_type();
-                            if set_val_type != val_type {
-                                Err(format!("Map at 0x{start_position:x} - expected value type '{val_type}', but got '{set_val_type}'"))?;
+                            let item_offset = reader.line_offset();
+                            let mut read_item = || -> Result<_, TextErrors> {
+                                let key_separator = if UObjectPropertyData::is_object_map_key_type(key_type) { ": " } else { ":" };
+                                let (key, val) = next_line.split_once('-').and_then(|(_, item)| item.split_once(key_separator)).ok_or_else(|| reader.text_error(format_err))?;
+                                let key = key.trim();
+                                let key = match key_type.as_str() {
+                                    "IntProperty" => UObjectPropertyData::Int32(parse_value(reader, key)?),
+                                    "UInt16Property" => UObjectPropertyData::UInt16(parse_value(reader, key)?),
+                                    "StrProperty" => UObjectPropertyData::String(key.to_owned()),
+                                    "FloatProperty" => UObjectPropertyData::Float(parse_value(reader, key)?),
+                                    "ByteProperty" => UObjectPropertyData::Byte(u8::from_str_radix(key, 16).map_err(|err| reader.text_error(format!("invalid value [{key}]: {err}")).at_token(key))?),
+                                    "EnumProperty" => UObjectPropertyData::Enum(key.replace("->", "::")),
+                                    "BoolProperty" => UObjectPropertyData::BoolValue(parse_value(reader, key)?),
+                                    "Int64Property" => UObjectPropertyData::Int64(parse_value(reader, key)?),
+                                    "UInt64Property" => UObjectPropertyData::UInt64(parse_value(reader, key)?),
+                                    "DoubleProperty" => UObjectPropertyData::Double(parse_value(reader, key)?),
+                                    "NameProperty" => UObjectPropertyData::Name(key.to_owned()),
+                                    "ObjectProperty" => UObjectPropertyData::Object(parse_value(reader, key)?),
+                                    "ClassProperty" => UObjectPropertyData::Class(parse_value(reader, key)?),
+                                    "WeakObjectProperty" => UObjectPropertyData::WeakObject(parse_value(reader, key)?),
+                                    other => Err(TextError::new(start_offset, format!("unable to read data of key type '{other}'")).at_token(other))?,
+                                };
+                                let value = UObjectPropertyData::container_item(UObjectPropertyData::from_string::<R>(val, reader, expected_indent_level + 6)?);
+                                let set_val_type = value.0.get_string_type();
+                                if set_val_type != val_type {
+                                    Err(TextError::new(item_offset, format!("expected value type '{val_type}', but got '{set_val_type}'")).at_token(val))?;
+                                }
+                                Ok((key, value))
+                            };
+                            match read_item() {
+                                Ok(item) => sets.push(item),
+                                Err(err) => {
+                                    errors.extend(err.0);
+                                    skip_nested_lines(reader, expected_indent_level + 4);
+                                }
                             }
                         }
                     }
-                    _ => { Err(format!("Map at 0x{:x} - expected key_type or val_type, but got {}", start_position, key.trim()))?; }
+                    _ => { Err(reader.text_error(format!("expected key_type, val_type or map_data, but got {}", key.trim())).at_token(key))?; }
                 }
             }
 
+            if !errors.is_empty() {
+                return Err(TextErrors(errors));
+            }
             if sets.is_empty() {
-                Err(format!("Map at 0x{start_position:x} - missing map_data!"))?;
+                Err(TextError::new(start_offset, "Map is missing map_data"))?;
             }
 
             Ok((
                 UObjectPropertyData::Map(sets.into_iter().map(|s| (s.0, s.1.0)).collect()),
                 UObjectPropertyMetadata::Map(
-                    key_type.ok_or(format!("Map at 0x{start_position:x} - missing key_type!"))?,
-                    value_type.ok_or(format!("Map at 0x{start_position:x} - missing val_type!"))?
+                    key_type.ok_or_else(|| TextError::new(start_offset, "Map is missing key_type"))?,
+                    value_type.ok_or_else(|| TextError::new(start_offset, "Map is missing val_type"))?
                 )
             ))
 
         } else if val.starts_with("!Array") {
-            let start_position = reader.stream_position().unwrap();
+            let start_offset = reader.line_offset();
             let mut item_type:   Option<String> = None;
             let mut item_schema: Option<UObjectPropertyHeader> = None;
             let mut array_name:  Option<String> = None;
             let mut items = vec![];
+            let mut errors = vec![];
 
             let mut i = 0;
             while i < 2 {
                 let next_line = next_nonempty_line(reader);
                 if !check_indent(&next_line, expected_indent_level + 2) {
-                    Err(format!("Array at 0x{start_position:x} should have properties (in order): item_type, <item_schema?>, <array_name?>, items"))?;
+                    Err(reader.text_error("Array should have properties (in order): item_type, <item_schema?>, <array_name?>, items"))?;
                 }
 
-                let (key, val) = next_line.split_once(':').ok_or(format!("Array at 0x{:x} - expected [item_type:] property, but got:\n{}", start_position, next_line.trim()))?;
+                let (key, val) = next_line.split_once(':').ok_or_else(|| reader.text_error("expected [item_type:] property"))?;
                 match key.trim() {
                     "item_type" => { 
                         item_type = Some(val.trim().to_owned());
@@ -948,105 +2632,235 @@ impl UObjectPropertyData {
                         for _ in 0..2 {
                             let next_line = next_nonempty_line(reader);
                             if !check_indent(&next_line, expected_indent_level + 4) {
-                                Err(format!("Array at 0x{start_position:x} - item_schema should have properties (in order): name, type"))?;
+                                Err(reader.text_error("item_schema should have properties (in order): name, type"))?;
                             }
-                            let (key, val) = next_line.split_once(':').ok_or(format!("Array at 0x{start_position:x} - misformatted item_schema property"))?;
+                            let (key, val) = next_line.split_once(':').ok_or_else(|| reader.text_error("misformatted item_schema property"))?;
                             match key.trim() {
                                 "name" => { name = Some(val.trim().to_string()); },
                                 "type" => { r#type = Some(val.trim().to_string()); },
-                                _ => Err(format!("Array at 0x{start_position:x}, item_schema: unknown property [{}]", val.trim()))?
+                                other => Err(reader.text_error(format!("unknown item_schema property [{other}]")).at_token(other))?
                             }
                         }
                         item_schema = Some(UObjectPropertyHeader {
-                            name: name.ok_or(format!("Array at 0x{start_position:x} - item_schema missing 'name' property!"))?,
-                            r#type: r#type.ok_or(format!("Array at 0x{start_position:x} - item_schema missing 'type' property!"))?,
+                            name: name.ok_or_else(|| reader.text_error("item_schema is missing its 'name' property"))?,
+                            r#type: r#type.ok_or_else(|| reader.text_error("item_schema is missing its 'type' property"))?,
                             arr_index: 0
                         }); 
                     },
                     "array_name" => { array_name = Some(val.trim().to_owned()); },
                     "items" => {
-                        if item_type.is_none() {
-                            Err(format!("Array at 0x{start_position:x} - item_type should come before items"))?;
-                        }
+                        let Some(val_type) = &item_type else {
+                            return Err(reader.text_error("item_type should come before items").into());
+                        };
 
-                        let format_err = format!("Array at 0x{start_position:x} - items should use format ' - <index>: value'");
                         loop {
                             let next_line = next_nonempty_line(reader);
                             if !next_line.trim().starts_with('-') || !check_indent(&next_line, expected_indent_level + 2) {
                                 reader.seek(SeekFrom::Current(-(next_line.len() as i64))).unwrap();
                                 break;
                             }
-    
-                            let (_, val) = next_line.split_once(':').ok_or(format_err.clone())?;
-                            let val = UObjectPropertyData::from_string::<R>(val, reader, expected_indent_level + 4)?;
-                            items.push(val);
-                        }
 
-                        let val_type = item_type.as_ref().unwrap().as_str();
-                        for (item, _meta) in &items {
-                            let entry_type = item.get_string_type();
-                            if entry_type != val_type {
-                                Err(format!("Array at 0x{start_position:x} - expected item type '{val_type}', but got '{entry_type}'"))?;
+                            let item_offset = reader.line_offset();
+                            let mut read_item = || -> Result<_, TextErrors> {
+                                let (_, val) = next_line.split_once(':').ok_or_else(|| reader.text_error("Array items should use format ' - <index>: value'"))?;
+                                let item = UObjectPropertyData::container_item(UObjectPropertyData::from_string::<R>(val, reader, expected_indent_level + 4)?);
+                                let entry_type = item.0.get_string_type();
+                                if entry_type != val_type {
+                                    Err(TextError::new(item_offset, format!("expected item type '{val_type}', but got '{entry_type}'")).at_token(val))?;
+                                }
+                                Ok(item)
+                            };
+                            match read_item() {
+                                Ok(item) => items.push(item),
+                                Err(err) => {
+                                    errors.extend(err.0);
+                                    skip_nested_lines(reader, expected_indent_level + 2);
+                                }
                             }
                         }
                     }
-                    _ => { Err(format!("Array at 0x{:x} - expected item_type, item_schema, or items, but got {}", start_position, key.trim()))?; }
+                    _ => { Err(reader.text_error(format!("expected item_type, item_schema, or items, but got {}", key.trim())).at_token(key))?; }
                 }
                 i += 1;
             }
 
-            if items.is_empty() {
-                println!("Info: Array at 0x{start_position:x} - missing items!");
+            if !errors.is_empty() {
+                return Err(TextErrors(errors));
+            }
+
+            let item_schema = match item_schema {
+                Some(schema) => Some((schema, array_name.ok_or_else(|| TextError::new(start_offset, "Array with an item_schema is missing array_name"))?)),
+                None => None,
+            };
+            Ok((
+                UObjectPropertyData::Array(items.into_iter().map(|i| i.0).collect(), item_schema),
+                UObjectPropertyMetadata::Array(item_type.ok_or_else(|| TextError::new(start_offset, "Array is missing item_type"))?)
+            ))
+        } else if val.starts_with("!Set") {
+            let next_line = next_nonempty_line(reader);
+            if !check_indent(&next_line, expected_indent_level + 2) {
+                Err(reader.text_error("Set should have properties (in order): item_type, <items_to_remove?>, items"))?;
+            }
+            let item_type = match next_line.split_once(':') {
+                Some((key, val)) if key.trim() == "item_type" => val.trim().to_owned(),
+                _ => Err(reader.text_error("expected [item_type:] property"))?,
+            };
+
+            let mut items_to_remove = vec![];
+            let mut items = None;
+            let mut errors = vec![];
+            while items.is_none() {
+                let next_line = next_nonempty_line(reader);
+                if !check_indent(&next_line, expected_indent_level + 2) {
+                    Err(reader.text_error("Set should have properties (in order): item_type, <items_to_remove?>, items"))?;
+                }
+
+                let list = match next_line.trim() {
+                    "items_to_remove:" if items_to_remove.is_empty() => &mut items_to_remove,
+                    "items:" => items.insert(vec![]),
+                    other => Err(reader.text_error(format!("expected items_to_remove or items, but got {other}")))?,
+                };
+                while let Some(val) = next_list_item(reader, expected_indent_level + 2)? {
+                    let item_offset = reader.line_offset();
+                    let mut read_item = || -> Result<_, TextErrors> {
+                        let (item, _meta) = UObjectPropertyData::container_item(UObjectPropertyData::from_string::<R>(&val, reader, expected_indent_level + 4)?);
+                        let entry_type = item.get_string_type();
+                        if entry_type != item_type {
+                            Err(TextError::new(item_offset, format!("expected item type '{item_type}', but got '{entry_type}'")).at_token(&val))?;
+                        }
+                        Ok(item)
+                    };
+                    match read_item() {
+                        Ok(item) => list.push(item),
+                        Err(err) => {
+                            errors.extend(err.0);
+                            skip_nested_lines(reader, expected_indent_level + 2);
+                        }
+                    }
+                }
+            }
+
+            if !errors.is_empty() {
+                return Err(TextErrors(errors));
             }
 
             Ok((
-                UObjectPropertyData::Array(
-                    items.into_iter().map(|i| i.0).collect(),
-                    item_schema.map(|s| (s, array_name.unwrap_or_else(|| panic!("Array at 0x{start_position:x} - missing array_name!")))),
-                ),
-                UObjectPropertyMetadata::Array(item_type.ok_or(format!("Array at 0x{start_position:x} - missing item_type!"))?)
+                UObjectPropertyData::Set(items_to_remove, items.unwrap()),
+                UObjectPropertyMetadata::Set(item_type)
             ))
         } else if val.starts_with("!u16") {
-            let (_, u16value) = val.split_once(' ').ok_or(format!("Error at 0x{:x}: !u16 should have one integer parameter", reader.stream_position().unwrap()))?;
-            Ok((UObjectPropertyData::UInt16(u16value.parse::<u16>()?), UObjectPropertyMetadata::None))
+            let (_, u16value) = val.split_once(' ').ok_or_else(|| reader.text_error("!u16 should have one integer parameter").at_token(val))?;
+            Ok((UObjectPropertyData::UInt16(parse_value::<u16, R>(reader, u16value)?), UObjectPropertyMetadata::None))
         } else if val.starts_with("!u32") {
-            let (_, u32value) = val.split_once(' ').ok_or(format!("Error at 0x{:x}: !u32 should have one integer parameter", reader.stream_position().unwrap()))?;
-            Ok((UObjectPropertyData::UInt32(u32value.parse::<u32>()?), UObjectPropertyMetadata::None))
+            let (_, u32value) = val.split_once(' ').ok_or_else(|| reader.text_error("!u32 should have one integer parameter").at_token(val))?;
+            Ok((UObjectPropertyData::UInt32(parse_value::<u32, R>(reader, u32value)?), UObjectPropertyMetadata::None))
         } else if val.starts_with("!i8") {
-            let (_, i8value) = val.split_once(' ').ok_or(format!("Error at 0x{:x}: !i8 should have one integer parameter", reader.stream_position().unwrap()))?;
-            Ok((UObjectPropertyData::Int8(i8value.parse::<i8>()?), UObjectPropertyMetadata::None))
+            let (_, i8value) = val.split_once(' ').ok_or_else(|| reader.text_error("!i8 should have one integer parameter").at_token(val))?;
+            Ok((UObjectPropertyData::Int8(parse_value::<i8, R>(reader, i8value)?), UObjectPropertyMetadata::None))
         } else if val.starts_with("!i16") {
-            let (_, i16value) = val.split_once(' ').ok_or(format!("Error at 0x{:x}: !i16 should have one integer parameter", reader.stream_position().unwrap()))?;
-            Ok((UObjectPropertyData::Int16(i16value.parse::<i16>()?), UObjectPropertyMetadata::None))
+            let (_, i16value) = val.split_once(' ').ok_or_else(|| reader.text_error("!i16 should have one integer parameter").at_token(val))?;
+            Ok((UObjectPropertyData::Int16(parse_value::<i16, R>(reader, i16value)?), UObjectPropertyMetadata::None))
         } else if val.starts_with("!i32") {
-            let (_, i32value) = val.split_once(' ').ok_or(format!("Error at 0x{:x}: !i32 should have one integer parameter", reader.stream_position().unwrap()))?;
-            Ok((UObjectPropertyData::Int32(i32value.parse::<i32>()?), UObjectPropertyMetadata::None))
+            let (_, i32value) = val.split_once(' ').ok_or_else(|| reader.text_error("!i32 should have one integer parameter").at_token(val))?;
+            Ok((UObjectPropertyData::Int32(parse_value::<i32, R>(reader, i32value)?), UObjectPropertyMetadata::None))
+        } else if val.starts_with("!Text") {
+            let flags = parse_field(reader, expected_indent_level + 2, "flags")?;
+            let history = match next_field(reader, expected_indent_level + 2, "history")?.as_str() {
+                "None" => {
+                    let next_line = next_nonempty_line(reader);
+                    match next_line.split_once(':') {
+                        Some((key, val)) if key.trim() == "culture_invariant_string" && check_indent(&next_line, expected_indent_level + 2) => {
+                            FTextHistory::None(Some(UObjectPropertyData::fstring_from_string(val)))
+                        },
+                        _ => {
+                            reader.seek(SeekFrom::Current(-(next_line.len() as i64))).unwrap();
+                            FTextHistory::None(None)
+                        }
+                    }
+                },
+                "Base" => {
+                    let namespace = UObjectPropertyData::fstring_from_string(&next_field(reader, expected_indent_level + 2, "namespace")?);
+                    let key = UObjectPropertyData::fstring_from_string(&next_field(reader, expected_indent_level + 2, "key")?);
+                    let source_string = UObjectPropertyData::fstring_from_string(&next_field(reader, expected_indent_level + 2, "source_string")?);
+                    FTextHistory::Base(namespace, key, source_string)
+                },
+                other => Err(reader.text_error(format!("unsupported !Text history [{other}], expected None or Base")).at_token(other))?
+            };
+            Ok((UObjectPropertyData::Text(flags, Box::new(history)), UObjectPropertyMetadata::None))
+        } else if val.starts_with("!object") || val.starts_with("!class") || val.starts_with("!weakobject") {
+            let (tag, reference) = val.split_once(' ').ok_or_else(|| reader.text_error(format!("{val} should have one object reference parameter")).at_token(val))?;
+            let reference = parse_value::<ObjectReference, R>(reader, reference)?;
+            match tag {
+                "!object" => Ok((UObjectPropertyData::Object(reference), UObjectPropertyMetadata::None)),
+                "!class" => Ok((UObjectPropertyData::Class(reference), UObjectPropertyMetadata::None)),
+                _ => Ok((UObjectPropertyData::WeakObject(reference), UObjectPropertyMetadata::None)),
+            }
+        } else if val.starts_with("!rawtext") {
+            let err = reader.text_error("!rawtext should have one base64 parameter. This value shouldn't be manually edited.").at_token(val);
+            let (_, data) = val.split_once(' ').ok_or(err.clone())?;
+            let data = BASE64_STANDARD.decode(data.trim()).map_err(|_| err)?;
+            Ok((UObjectPropertyData::TextRaw(data), UObjectPropertyMetadata::None))
+        } else if val.starts_with("!zero") {
+            let (_, r#type) = val.split_once(' ').ok_or_else(|| reader.text_error("!zero should have one property type parameter").at_token(val))?;
+            Ok((UObjectPropertyData::Zero(r#type.trim().to_owned()), UObjectPropertyMetadata::None))
+        } else if val.starts_with("!lazyobject") {
+            let err = reader.text_error("!lazyobject should have one 32 digit hex parameter").at_token(val);
+            let (_, guid_str) = val.split_once(' ').ok_or(err.clone())?;
+            let guid_str = guid_str.trim();
+            if guid_str.len() != 32 {
+                Err(err.clone())?;
+            }
+            let mut guid = [0;16];
+            for (i, b) in guid.iter_mut().enumerate() {
+                *b = guid_str.get(i*2..i*2+2).and_then(|hex| u8::from_str_radix(hex, 16).ok()).ok_or(err.clone())?;
+            }
+            Ok((UObjectPropertyData::LazyObject(guid), UObjectPropertyMetadata::None))
+        } else if val.starts_with("!softobject") || val.starts_with("!softclass") {
+            let (tag, path) = val.split_once(' ').ok_or_else(|| reader.text_error(format!("{val} should have an asset path parameter")).at_token(val))?;
+            let (asset_path, sub_path) = path.trim().split_once(' ').unwrap_or((path.trim(), ""));
+            if tag == "!softobject" {
+                Ok((UObjectPropertyData::SoftObject(asset_path.to_owned(), sub_path.to_owned()), UObjectPropertyMetadata::None))
+            } else {
+                Ok((UObjectPropertyData::SoftClass(asset_path.to_owned(), sub_path.to_owned()), UObjectPropertyMetadata::None))
+            }
+        } else if val.starts_with("!i64") {
+            let (_, i64value) = val.split_once(' ').ok_or_else(|| reader.text_error("!i64 should have one integer parameter").at_token(val))?;
+            Ok((UObjectPropertyData::Int64(parse_value::<i64, R>(reader, i64value)?), UObjectPropertyMetadata::None))
+        } else if val.starts_with("!u64") {
+            let (_, u64value) = val.split_once(' ').ok_or_else(|| reader.text_error("!u64 should have one integer parameter").at_token(val))?;
+            Ok((UObjectPropertyData::UInt64(parse_value::<u64, R>(reader, u64value)?), UObjectPropertyMetadata::None))
+        } else if val.starts_with("!f64") {
+            let (_, f64value) = val.split_once(' ').ok_or_else(|| reader.text_error("!f64 should have one number parameter").at_token(val))?;
+            Ok((UObjectPropertyData::Double(parse_value::<f64, R>(reader, f64value)?), UObjectPropertyMetadata::None))
         } else if val.starts_with("!ByteProperty") {
             let mut vals = val.split_whitespace();
             vals.next().unwrap(); // !ByteProperty
 
-            let err = format!("Error at 0x{:x}: !ByteProperty should have three hex parameters", reader.stream_position().unwrap());
+            let err = reader.text_error("!ByteProperty should have three hex parameters").at_token(val);
             let enum_id = vals.next().ok_or(err.clone())?;
             let enum_val = vals.next().ok_or(err.clone())?;
-            let byte_val = vals.next().ok_or(err)?;
+            let byte_val = vals.next().ok_or(err.clone())?;
             
-            Ok((UObjectPropertyData::Byte(u8::from_str_radix(byte_val, 16)?), UObjectPropertyMetadata::Byte(u64::from_str_radix(enum_id, 16)?, u8::from_str_radix(enum_val, 16)?)))
+            let byte_val = u8::from_str_radix(byte_val, 16).map_err(|_| err.clone())?;
+            let enum_id = u64::from_str_radix(enum_id, 16).map_err(|_| err.clone())?;
+            let enum_val = u8::from_str_radix(enum_val, 16).map_err(|_| err)?;
+            Ok((UObjectPropertyData::Byte(byte_val), UObjectPropertyMetadata::Byte(enum_id, enum_val)))
         } else if val.starts_with("!EnumProperty") {
             let mut vals = val.split_whitespace();
             vals.next().unwrap(); // !EnumProperty
 
-            let err = format!("Error at 0x{:x}: !EnumProperty should have two string parameters", reader.stream_position().unwrap());
+            let err = reader.text_error("!EnumProperty should have two string parameters").at_token(val);
             let enum_name = vals.next().ok_or(err.clone())?;
             let enum_val = vals.next().ok_or(err.clone())?;
             
             Ok((UObjectPropertyData::Enum(enum_val.replace("->", "::")), UObjectPropertyMetadata::Enum(enum_name.to_owned())))
         } else if val.starts_with("!utf16") {
-            let (_, utf16val) = val.split_once(' ').ok_or(format!("Error at 0x{:x}: !utf16 should have one string parameter", reader.stream_position().unwrap()))?;
+            let (_, utf16val) = val.split_once(' ').ok_or_else(|| reader.text_error("!utf16 should have one string parameter").at_token(val))?;
             Ok((UObjectPropertyData::StringUtf16(utf16val.replace("\\n", "\n")), UObjectPropertyMetadata::None))
         } else if val.starts_with("!EmptyString") {
             Ok((UObjectPropertyData::String(String::new()), UObjectPropertyMetadata::None))
         } else if val.starts_with("!name") {
-            let (_, name) = val.split_once(' ').ok_or(format!("Error at 0x{:x}: !name should have one string parameter", reader.stream_position().unwrap()))?;
+            let (_, name) = val.split_once(' ').ok_or_else(|| reader.text_error("!name should have one string parameter").at_token(val))?;
             Ok((UObjectPropertyData::Name(name.to_owned()), UObjectPropertyMetadata::None))
         } else if let Ok(val) = val.parse::<f32>() {
             Ok((UObjectPropertyData::Float(val), UObjectPropertyMetadata::None))
@@ -1054,7 +2868,7 @@ impl UObjectPropertyData {
             Ok((UObjectPropertyData::Bool, UObjectPropertyMetadata::Bool(val)))
         } else {
             let str: String = if val.starts_with('\"') {
-                if !val.ends_with('\"') { Err(format!("String value [{val}] doesn't have closing quote"))? }
+                if !val.ends_with('\"') { Err(reader.text_error(format!("string value [{val}] doesn't have closing quote")).at_token(val))? }
                 val.chars().skip(1).take(val.len()-2).collect()
             } else { 
                 val.chars().collect()
@@ -1064,6 +2878,38 @@ impl UObjectPropertyData {
     }
 }
 
+/// Reads properties until the end of a struct, at the first line indented less than `expected_indent_level`.  After a
+/// property that can't be read, reading carries on at the next property at the same level, so every error is found.
+fn properties_from_string<R: BufRead + Seek>(reader: &mut R, expected_indent_level: usize) -> Result<Vec<UObjectProperty>, TextErrors> {
+    let mut properties = vec![];
+    let mut errors = vec![];
+    loop {
+        match UObjectProperty::from_string::<R>(reader, expected_indent_level) {
+            Ok(Some(prop)) => properties.push(prop),
+            Ok(None) => break,
+            Err(err) => {
+                errors.extend(err.0);
+                skip_nested_lines(reader, expected_indent_level);
+            },
+        }
+    }
+    match errors.is_empty() {
+        true => Ok(properties),
+        false => Err(TextErrors(errors)),
+    }
+}
+
+/// Skips the rest of a property that couldn't be read - every line indented further than the property itself
+fn skip_nested_lines<R: BufRead + Seek>(reader: &mut R, indent_level: usize) {
+    loop {
+        let next_line = next_nonempty_line(reader);
+        if next_line.is_empty() || !check_indent(&next_line, indent_level + 1) {
+            reader.seek(SeekFrom::Current(-(next_line.len() as i64))).unwrap();
+            break;
+        }
+    }
+}
+
 fn check_indent(val: &str, spaces: usize) -> bool {
     val.replace('\t', "  ").chars().take(spaces).all(|c| c == ' ')
 }
@@ -1082,109 +2928,678 @@ fn next_nonempty_line<R: BufRead + Seek>(reader: &mut R) -> String {
     line
 }
 
-pub struct IoUObject {
-    summary: UObjectSummary,
+/// 
+/// Reads the next `key: value` line in the reader, returning the trimmed value.  Errors if the line isn't
+/// indented by at least `expected_indent_level` spaces or doesn't start with `key`.
+/// 
+fn next_field<R: BufRead + Seek>(reader: &mut R, expected_indent_level: usize, key: &str) -> Result<String, TextError> {
+    let next_line = next_nonempty_line(reader);
+    if !check_indent(&next_line, expected_indent_level) {
+        Err(reader.text_error(format!("expected [{key}:] property")))?;
+    }
+
+    let (name, val) = next_line.split_once(':').ok_or_else(|| reader.text_error(format!("expected [{key}:] property")))?;
+    if name.trim() != key {
+        Err(reader.text_error(format!("expected [{key}:] property, but got [{}:]", name.trim())).at_token(name))?;
+    }
+    Ok(val.trim().to_owned())
+}
+
+/// Reads the next `key: value` line like [`next_field`], parsing the value
+fn parse_field<T: FromStr, R: BufRead + Seek>(reader: &mut R, expected_indent_level: usize, key: &str) -> Result<T, TextError> where T::Err: Display {
+    let val = next_field(reader, expected_indent_level, key)?;
+    parse_value(reader, &val)
+}
+
+/// Parses a value from the line that was just read, with errors pointing at the value
+fn parse_value<T: FromStr, R: BufRead + Seek>(reader: &mut R, val: &str) -> Result<T, TextError> where T::Err: Display {
+    let val = val.trim();
+    val.parse().map_err(|err| reader.text_error(format!("invalid value [{val}]: {err}")).at_token(val))
+}
+
+/// 
+/// Reads the next ` - <index>: value` list item in the reader, returning the trimmed value.  Returns None once
+/// the list has ended, leaving the reader at the start of the next line.
+/// 
+fn next_list_item<R: BufRead + Seek>(reader: &mut R, expected_indent_level: usize) -> Result<Option<String>, TextError> {
+    let next_line = next_nonempty_line(reader);
+    if !next_line.trim().starts_with('-') || !check_indent(&next_line, expected_indent_level) {
+        reader.seek(SeekFrom::Current(-(next_line.len() as i64))).unwrap();
+        return Ok(None);
+    }
+
+    let (_, val) = next_line.split_once(':').ok_or_else(|| reader.text_error("list items should use format ' - <index>: value'"))?;
+    Ok(Some(val.trim().to_owned()))
+}
+
+/// Formats a GUID as {xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx}, with the bytes in the order they're serialized
+fn guid_to_string(guid: &[u8;16]) -> String {
+    let hex: String = guid.iter().map(|b| format!("{b:02x}")).collect();
+    format!("{{{}-{}-{}-{}-{}}}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32])
+}
+
+/// Reads a GUID written by [`guid_to_string`]
+fn guid_from_string(val: &str) -> Option<[u8;16]> {
+    let hex = val.strip_prefix('{')?.strip_suffix('}')?.replace('-', "");
+    if hex.len() != 32 {
+        return None;
+    }
+    let mut guid = [0;16];
+    for (i, b) in guid.iter_mut().enumerate() {
+        *b = u8::from_str_radix(hex.get(i*2..i*2+2)?, 16).ok()?;
+    }
+    Some(guid)
+}
+
+/// Serializes values that have a string form (like engine versions and object indices) as that string
+mod display_string {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use std::{fmt::Display, str::FromStr};
+
+    pub fn serialize<T: Display, S: Serializer>(val: &T, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(val)
+    }
+
+    pub fn deserialize<'de, T: FromStr<Err: Display>, D: Deserializer<'de>>(deserializer: D) -> Result<T, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(D::Error::custom)
+    }
+}
+
+/// Serializes byte buffers as base64, like the text format
+mod base64_string {
+    use base64::{prelude::BASE64_STANDARD, Engine};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&BASE64_STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let val = String::deserialize(deserializer)?;
+        BASE64_STANDARD.decode(&val).map_err(|_| D::Error::custom(format!("unable to read [{val}] as a base64 string")))
+    }
+}
+
+/// Serializes optional byte buffers as base64, leaving missing ones out
+mod optional_base64_string {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &Option<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error> {
+        match bytes {
+            Some(bytes) => super::base64_string::serialize(bytes, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error> {
+        #[derive(Deserialize)]
+        struct Base64(#[serde(with = "super::base64_string")] Vec<u8>);
+        Ok(Option::<Base64>::deserialize(deserializer)?.map(|b| b.0))
+    }
+}
+
+/// Serializes GUIDs as written by [`guid_to_string`](super::guid_to_string)
+mod guid_string {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(guid: &[u8;16], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&super::guid_to_string(guid))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8;16], D::Error> {
+        let val = String::deserialize(deserializer)?;
+        super::guid_from_string(&val).ok_or_else(|| D::Error::custom(format!("invalid guid [{val}]")))
+    }
+}
+
+/// The tagged properties of one export, followed by whatever else the export serializes after them
+#[derive(PartialEq, Debug, Serialize, Deserialize)]
+pub(crate) struct UObjectExport {
+    pub(crate) export_index: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    class: Option<String>, // The mapped class of unversioned properties
     properties: Vec<UObjectProperty>,
+    #[serde(with = "base64_string")]
+    extra_data: Vec<u8>,
 }
 
-impl IoUObject {
-    pub fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R) -> Result<Self, Box<dyn Error>> {
-        let summary = UObjectSummary::from_buffer::<R,E>(reader)?;
+impl UObjectExport {
+    /// UObjects without a GUID end with a zeroed 'has guid' flag after their properties
+    const DEFAULT_EXTRA_DATA: [u8;4] = [0;4];
+
+    pub fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R, export_index: usize, serial_size: usize, summary: &UObjectSummary, mappings: Option<&Mappings>) -> Result<Self, ParseError> {
+        let in_export = |err: ParseError| err.in_property(&format!("exports[{export_index}]"));
+        let start_position = reader.offset();
+        let data = reader.parse_vec(serial_size).map_err(in_export)?;
+        if summary.has_unversioned_properties() {
+            return Ok(Self::unversioned_from_buffer::<E>(data, export_index, summary, mappings));
+        }
+        let mut data = Cursor::new(data);
+
+        // Offsets in errors are relative to the export data, so they're moved to the export's position in the package
         let mut properties = vec![];
-        while let Some(prop) = UObjectProperty::from_buffer::<R,E>(reader, &summary.name_map)? {
+        while let Some(prop) = UObjectProperty::from_buffer::<_,E>(&mut data, summary).map_err(|err| in_export(err.moved_by(start_position)))? {
             properties.push(prop);
         }
 
+        let mut extra_data = vec![];
+        data.read_to_end(&mut extra_data).unwrap();
+
         Ok(Self {
-            summary,
+            export_index,
+            class: None,
             properties,
+            extra_data,
         })
     }
 
-    pub fn to_bytes<W: Write, E: byteorder::ByteOrder>(&self, writer: &mut W) -> usize {
-        let mut properties_bytes = vec![];
+    /// Decodes unversioned properties using the mappings.  Exports that can't be decoded, or wouldn't re-encode to
+    /// the same bytes, are kept whole as extra data.
+    fn unversioned_from_buffer<E: byteorder::ByteOrder>(data: Vec<u8>, export_index: usize, summary: &UObjectSummary, mappings: Option<&Mappings>) -> Self {
+        let raw_export = |data: Vec<u8>| Self { export_index, class: None, properties: vec![], extra_data: data };
+        let Some(mappings) = mappings else {
+            eprintln!("WARNING: Export {export_index} has unversioned properties, but no mappings were given  # Keeping it as raw data.");
+            return raw_export(data);
+        };
+        let Some(class) = summary.unversioned_class(export_index, mappings) else {
+            eprintln!("WARNING: Unable to find the class of export {export_index} in the mappings  # Keeping it as raw data.");
+            return raw_export(data);
+        };
+
+        let mut reader = Cursor::new(&data);
+        let properties = match unversioned::properties_from_buffer::<_,E>(&mut reader, &class, mappings, summary) {
+            Ok(properties) => properties,
+            Err(err) => {
+                eprintln!("WARNING: Unable to decode export {export_index} as {class}: {err}  # Keeping it as raw data.");
+                return raw_export(data);
+            }
+        };
+        let properties_len = reader.position() as usize;
+
+        let mut reencoded = vec![];
+        match unversioned::properties_to_bytes::<_,E>(&properties, &class, mappings, summary, &mut reencoded) {
+            Ok(_) if reencoded == data[..properties_len] => {},
+            Ok(_) | Err(_) => {
+                eprintln!("WARNING: Export {export_index} doesn't re-encode to the same bytes as {class}  # Keeping it as raw data.");
+                return raw_export(data);
+            }
+        }
+
+        Self {
+            export_index,
+            class: Some(class),
+            properties,
+            extra_data: data[properties_len..].to_vec(),
+        }
+    }
+
+    pub fn collect_names<'a>(&'a self, names: &mut Vec<&'a str>) {
+        if self.class.is_some() {
+            unversioned::collect_names(&self.properties, names);
+            return;
+        }
         for prop in &self.properties {
-            prop.to_bytes::<_,E>(&mut properties_bytes, &self.summary.name_map);
+            prop.collect_names(names);
+        }
+    }
+
+    pub fn to_bytes<W: Write, E: byteorder::ByteOrder>(&self, writer: &mut W, summary: &UObjectSummary, mappings: Option<&Mappings>) -> Result<usize, EncodeError> {
+        self.write_data::<W,E>(writer, summary, mappings).map_err(|err| err.in_property(&format!("exports[{}]", self.export_index)))
+    }
+
+    fn write_data<W: Write, E: byteorder::ByteOrder>(&self, writer: &mut W, summary: &UObjectSummary, mappings: Option<&Mappings>) -> Result<usize, EncodeError> {
+        if summary.has_unversioned_properties() {
+            let len = match &self.class {
+                Some(class) => {
+                    let mappings = mappings.ok_or_else(|| EncodeError::unsupported(format!("Export has unversioned {class} properties - encoding it needs mappings")))?;
+                    unversioned::properties_to_bytes::<W,E>(&self.properties, class, mappings, summary, writer)?
+                },
+                None if self.properties.is_empty() => 0,
+                None => Err(EncodeError::unsupported("Export has properties, but no class for its unversioned properties"))?,
+            };
+            writer.write_all(&self.extra_data)?;
+            return Ok(len + self.extra_data.len());
         }
-        let none_index = self.summary.name_map.iter().position(|n| n == "None").unwrap_or_else(|| panic!("Object type [None] wasn't in name map")) as u64;
-        properties_bytes.write_u64::<E>(none_index).unwrap();
 
-        let summary_bytes = self.summary.to_bytes::<E>();
-        writer.write_all(&summary_bytes).unwrap();
-        writer.write_all(&properties_bytes).unwrap();
-        writer.write_all(&[0;4]).unwrap();
+        let mut len = 0;
+        for prop in &self.properties {
+            len += prop.to_bytes::<_,E>(writer, summary)?;
+        }
+        writer.write_u64::<E>(name_index(summary, "None")?)?;
+        writer.write_all(&self.extra_data)?;
 
-        summary_bytes.len() + properties_bytes.len() + 4
+        Ok(len + std::mem::size_of::<u64>() + self.extra_data.len())
     }
 
-    pub fn to_string<W: Write>(&self, writer: &mut W) {
-        writer.write_all(format!("summary: {}\n", self.summary).as_bytes()).unwrap();
-        writer.write_all("contents:\n".as_bytes()).unwrap();
+    pub fn to_string<W: Write>(&self, writer: &mut W, object_name: &str) {
+        writer.write_all(format!("contents: {} ({object_name})\n", self.export_index).as_bytes()).unwrap();
+        if let Some(class) = &self.class {
+            writer.write_all(format!("class: {class}\n").as_bytes()).unwrap();
+        }
 
         for prop in &self.properties {
             let indent_spaces = 2usize;
             writer.write_all("  ".as_bytes()).unwrap();
             prop.to_string(writer, indent_spaces);
         }
+
+        if self.extra_data != Self::DEFAULT_EXTRA_DATA {
+            writer.write_all(format!("extra_data: {}\n", BASE64_STANDARD.encode(&self.extra_data)).as_bytes()).unwrap();
+        }
     }
 
-    pub fn from_string<R: BufRead + Seek>(reader: &mut R) -> Result<Self, Box<dyn Error>> {
-        let mut line = String::new();
-        reader.read_line(&mut line).unwrap();
+    /// Reads an export's properties, starting after its `contents:` heading.  The heading's value should
+    /// start with the export index - if it's empty (as in files written before packages could have multiple
+    /// exports), `default_export_index` is used instead.  The whole export is read even if it has errors, leaving the
+    /// reader at the next section.
+    pub fn from_string<R: BufRead + Seek>(heading: &str, reader: &mut R, default_export_index: usize) -> Result<Self, TextErrors> {
+        let mut errors = vec![];
+        let export_index = match heading.split_whitespace().next() {
+            Some(index) => index.parse().unwrap_or_else(|_| {
+                errors.push(reader.text_error("export heading should start with the export index").at_token(index));
+                default_export_index
+            }),
+            None => default_export_index,
+        };
+
+        let next_line = next_nonempty_line(reader);
+        let class = match next_line.strip_prefix("class:") {
+            Some(class) => Some(class.trim().to_owned()),
+            None => {
+                reader.seek(SeekFrom::Current(-(next_line.len() as i64))).unwrap();
+                None
+            }
+        };
+
+        let properties = properties_from_string(reader, 2).unwrap_or_else(|err| {
+            errors.extend(err.0);
+            vec![]
+        });
+
+        let next_line = next_nonempty_line(reader);
+        let extra_data = match next_line.strip_prefix("extra_data:") {
+            Some(val) => BASE64_STANDARD.decode(val.trim()).unwrap_or_else(|_| {
+                errors.push(reader.text_error(format!("unable to read extra_data for export {export_index} from base64 string")).at_token(val));
+                vec![]
+            }),
+            None => {
+                reader.seek(SeekFrom::Current(-(next_line.len() as i64))).unwrap();
+                Self::DEFAULT_EXTRA_DATA.to_vec()
+            }
+        };
+        if !errors.is_empty() {
+            return Err(TextErrors(errors));
+        }
+
+        Ok(Self {
+            export_index,
+            class,
+            properties,
+            extra_data,
+        })
+    }
+
+    /// Writes the `contents:` section of every export, in order
+    pub(crate) fn all_to_string<W: Write>(exports: &[Self], writer: &mut W, summary: &UObjectSummary) {
+        for export in exports {
+            let object_name = summary.export_map.get(export.export_index).map(|e| e.object_name.as_str()).unwrap_or_default();
+            export.to_string(writer, object_name);
+        }
+    }
+
+    /// Checks there's exactly one export's contents for each entry in the export map
+    fn check_contents(exports: &[Self], export_count: usize) -> Result<(), String> {
+        let mut has_contents = vec![false; export_count];
+        for export in exports {
+            match has_contents.get_mut(export.export_index) {
+                None => Err(format!("contents for export {} don't match any entry in the export map", export.export_index))?,
+                Some(true) => Err(format!("contents for export {} are given more than once", export.export_index))?,
+                Some(seen) => *seen = true,
+            }
+        }
+        match has_contents.iter().position(|seen| !seen) {
+            Some(missing) => Err(format!("contents for export {missing} are missing")),
+            None => Ok(()),
+        }
+    }
+
+    /// Reads `contents:` sections until the end of the text, checking each one against the summary's export map.
+    /// Every section is read, so the errors of all of them are returned together.
+    pub(crate) fn all_from_string<R: BufRead + Seek>(reader: &mut R, summary: &UObjectSummary) -> Result<Vec<Self>, TextErrors> {
+        let export_order = summary.serialized_export_order();
+        let mut has_contents = vec![false; summary.export_map.len()];
+        let mut exports = vec![];
+        let mut errors = vec![];
+        for section in 0.. {
+            let next_line = next_nonempty_line(reader);
+            if next_line.is_empty() {
+                break;
+            }
+
+            let heading_offset = reader.line_offset();
+            let Some(heading) = next_line.strip_prefix("contents:") else {
+                errors.push(reader.text_error("package maps should be followed by 'contents:' sections"));
+                return Err(TextErrors(errors));
+            };
+            let default_export_index = export_order.get(section).copied().unwrap_or(section);
+            match Self::from_string(heading, reader, default_export_index) {
+                Ok(export) => match has_contents.get_mut(export.export_index) {
+                    None => errors.push(TextError::new(heading_offset, format!("contents for export {} don't match any entry in the export map", export.export_index))),
+                    Some(true) => errors.push(TextError::new(heading_offset, format!("contents for export {} are given more than once", export.export_index))),
+                    Some(seen) => {
+                        *seen = true;
+                        exports.push(export);
+                    },
+                },
+                Err(err) => errors.extend(err.0),
+            }
+        }
+        // Sections that failed to read can't be told apart from missing ones, so they're only checked for without errors
+        if errors.is_empty() {
+            let end_offset = reader.line_offset();
+            for missing in has_contents.iter().enumerate().filter(|(_, seen)| !**seen).map(|(i, _)| i) {
+                errors.push(TextError::new(end_offset, format!("contents for export {missing} are missing")));
+            }
+        }
+        match errors.is_empty() {
+            true => Ok(exports),
+            false => Err(TextErrors(errors)),
+        }
+    }
+}
+
+/// How a [`UObjectSummary`] is laid out by serde - the header and name map, without the import map, export map
+/// and export bundles that are written as sections of their own
+#[derive(Serialize, Deserialize)]
+struct UObjectSummaryFields {
+    header: UObjectSummaryHeader,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    zen: Option<ZenSummary>,
+    names: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    other_type_names: Vec<usize>, // Names stored as UTF-16 despite being ASCII, or as one byte despite not being
+    #[serde(with = "base64_string")]
+    graph_data: Vec<u8>,
+}
+
+impl From<&UObjectSummary> for UObjectSummaryFields {
+    fn from(summary: &UObjectSummary) -> Self {
+        let other_type_names = summary.name_map.iter().zip(&summary.name_map_type).enumerate()
+            .filter(|(_, (name, string_type))| name.is_ascii() != matches!(string_type, StringType::Utf8))
+            .map(|(i, _)| i)
+            .collect();
+        Self {
+            header: summary.header.clone(),
+            zen: summary.zen.clone(),
+            names: summary.name_map.clone(),
+            other_type_names,
+            graph_data: summary.graph_data.clone(),
+        }
+    }
+}
+
+impl TryFrom<UObjectSummaryFields> for UObjectSummary {
+    type Error = Box<dyn Error>;
+
+    fn try_from(fields: UObjectSummaryFields) -> Result<Self, Self::Error> {
+        if let Some(i) = fields.other_type_names.iter().find(|i| **i >= fields.names.len()) {
+            Err(format!("other_type_names has name {i}, which isn't in the summary's names"))?;
+        }
+        let name_map_type = fields.names.iter().enumerate().map(|(i, name)| match (fields.other_type_names.contains(&i), name.is_ascii()) {
+            (false, true) | (true, false) => StringType::Utf8,
+            (false, false) | (true, true) => StringType::Utf16,
+        }).collect();
+
+        let summary = Self {
+            engine_version: if fields.zen.is_some() { EngineVersion::Ue5 } else { EngineVersion::Ue4_27 },
+            header: fields.header,
+            zen: fields.zen,
+            name_map: fields.names,
+            name_map_type,
+            import_map: vec![],
+            export_map: vec![],
+            export_bundles: vec![],
+            graph_data: fields.graph_data,
+            import_paths: vec![],
+        };
+        // The names are laid out by the header's sizes and offsets, so they can only change along with them
+        let names_size = match summary.zen {
+            Some(_) => summary.name_batch_to_bytes::<LE>().len(),
+            None => {
+                let mut names = vec![];
+                summary.names_to_bytes::<LE>(&mut names);
+                names.len()
+            },
+        };
+        let hashes_size = match summary.zen {
+            Some(_) => 0,
+            None => (summary.name_map.len() + 1) * std::mem::size_of::<u64>(),
+        };
+        let header = &summary.header;
+        // The names are padded up to the offset after them, which has to be checked before the padding is written
+        let (names_offset, padded_offset) = match &summary.zen {
+            Some(zen) => (44 + zen.versioning_info.as_ref().map(Vec::len).unwrap_or_default(), zen.imported_public_export_hashes_offset),
+            None => (UObjectSummaryHeader::BYTE_LEN, header.name_map_hashes_offset),
+        };
+        if header.name_map_names_offset as usize != names_offset || !(0..8).contains(&(padded_offset as i64 - (names_offset + names_size) as i64)) {
+            Err("Summary names don't match the offsets in its header - new names are added from the package's contents, which keeps them up to date")?;
+        }
+        if names_size != header.name_map_names_size as usize || hashes_size != header.name_map_hashes_size as usize
+            || summary.header_and_name_map_to_bytes::<LE>().len() != header.import_map_offset as usize {
+            Err("Summary names don't match the sizes and offsets in its header - new names are added from the package's contents, which keeps them up to date")?;
+        }
+        Ok(summary)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(try_from = "IoUObjectFields")]
+pub struct IoUObject {
+    summary: UObjectSummary,
+    exports: Vec<UObjectExport>,
+}
+
+/// How an [`IoUObject`] is laid out by serde - the same sections as the text format
+#[derive(Deserialize)]
+struct IoUObjectFields {
+    summary: UObjectSummaryFields,
+    engine: EngineVersion,
+    imports: Vec<PackageObjectIndex>,
+    exports: Vec<UObjectExportMapEntry>,
+    export_bundles: Vec<UObjectExportBundle>,
+    contents: Vec<UObjectExport>,
+}
+
+impl TryFrom<IoUObjectFields> for IoUObject {
+    type Error = Box<dyn Error>;
+
+    fn try_from(fields: IoUObjectFields) -> Result<Self, Self::Error> {
+        let mut summary = UObjectSummary::try_from(fields.summary)?;
+        fields.engine.check_iostore_summary()?;
+        summary.set_engine_version(fields.engine)?;
+        summary.import_map = fields.imports;
+        summary.export_map = fields.exports;
+        summary.export_bundles = fields.export_bundles;
+        UObjectExport::check_contents(&fields.contents, summary.export_map.len())?;
+
+        Ok(Self {
+            summary,
+            exports: fields.contents,
+        })
+    }
+}
+
+impl Serialize for IoUObject {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut fields = serializer.serialize_struct("IoUObject", 6)?;
+        fields.serialize_field("summary", &UObjectSummaryFields::from(&self.summary))?;
+        fields.serialize_field("engine", &self.summary.engine_version())?;
+        fields.serialize_field("imports", &self.summary.import_map)?;
+        fields.serialize_field("exports", &self.summary.export_map)?;
+        fields.serialize_field("export_bundles", &self.summary.export_bundles)?;
+        fields.serialize_field("contents", &self.exports)?;
+        fields.end()
+    }
+}
+
+impl IoUObject {
+    pub fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R) -> Result<Self, ParseError> {
+        Self::from_buffer_with_options::<R,E>(reader, None, None)
+    }
+
+    /// Like [`Self::from_buffer`], with the mappings needed to decode packages that use unversioned properties,
+    /// and the engine version whose package layout to read (detected if None)
+    pub fn from_buffer_with_options<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R, mappings: Option<&Mappings>, engine_version: Option<EngineVersion>) -> Result<Self, ParseError> {
+        let mut summary = UObjectSummary::from_buffer::<R,E>(reader, engine_version)?;
+        if let Some(mappings) = mappings {
+            summary.name_script_imports(mappings);
+        }
+        let mut exports = vec![];
+        for export_index in summary.serialized_export_order() {
+            let export = summary.export_map.get(export_index)
+                .ok_or(ParseError::invalid(reader.offset(), format!("Export bundles reference export {export_index}, which isn't in the export map")))?;
+            exports.push(UObjectExport::from_buffer::<R,E>(reader, export_index, export.cooked_serial_size as usize, &summary, mappings)?);
+        }
+
+        Ok(Self {
+            summary,
+            exports,
+        })
+    }
+
+    pub fn to_bytes<W: Write, E: byteorder::ByteOrder>(&self, writer: &mut W) -> Result<usize, EncodeError> {
+        self.to_bytes_with_mappings::<W,E>(writer, None)
+    }
+
+    /// Like [`Self::to_bytes`], with the mappings needed to encode exports with unversioned properties
+    pub fn to_bytes_with_mappings<W: Write, E: byteorder::ByteOrder>(&self, writer: &mut W, mappings: Option<&Mappings>) -> Result<usize, EncodeError> {
+        let mut summary = self.summary.clone();
+        let mut names = vec!["None"];
+        names.extend(self.summary.export_map.iter().map(|e| e.object_name.as_str()));
+        for export in &self.exports {
+            export.collect_names(&mut names);
+        }
+        summary.add_missing_names(names);
+        UObjectExport::check_contents(&self.exports, summary.export_map.len()).map_err(EncodeError::unsupported)?;
+
+        let mut exports_bytes = vec![];
+        for export in &self.exports {
+            let len = export.to_bytes::<_,E>(&mut exports_bytes, &summary, mappings)?;
+            summary.resize_export(export.export_index, len as u64)?;
+        }
+
+        let summary_bytes = summary.to_bytes::<E>()?;
+        writer.write_all(&summary_bytes)?;
+        writer.write_all(&exports_bytes)?;
+
+        Ok(summary_bytes.len() + exports_bytes.len())
+    }
+
+    pub(crate) fn from_parts(summary: UObjectSummary, exports: Vec<UObjectExport>) -> Self {
+        Self {
+            summary,
+            exports,
+        }
+    }
+
+    pub(crate) fn into_parts(self) -> (UObjectSummary, Vec<UObjectExport>) {
+        (self.summary, self.exports)
+    }
+
+    pub fn engine_version(&self) -> EngineVersion {
+        self.summary.engine_version()
+    }
+
+    /// Changes which engine version the object is encoded for - only possible between versions sharing a summary layout
+    pub fn set_engine_version(&mut self, engine_version: EngineVersion) -> Result<(), Box<dyn Error>> {
+        engine_version.check_iostore_summary()?;
+        self.summary.set_engine_version(engine_version)
+    }
+
+    pub fn to_string<W: Write>(&self, writer: &mut W) {
+        writer.write_all(format!("summary: {}\n", self.summary).as_bytes()).unwrap();
+        writer.write_all(format!("engine: {}\n", self.summary.engine_version()).as_bytes()).unwrap();
+        self.summary.maps_to_string(writer);
+        UObjectExport::all_to_string(&self.exports, writer, &self.summary);
+    }
+
+    /// Writes the object as standard YAML, which [`Self::from_yaml`] reads back to the same object.  Enum values
+    /// are written as single key maps (like `Int32: 5`) instead of YAML tags, so that any YAML tool can edit them.
+    pub fn to_yaml<W: Write>(&self, writer: &mut W) -> Result<(), Box<dyn Error>> {
+        let mut serializer = serde_yaml::Serializer::new(writer);
+        serde_yaml::with::singleton_map_recursive::serialize(self, &mut serializer)?;
+        Ok(())
+    }
 
-        if !line.starts_with("summary:") {
-            Err("IoUObject string should start with 'summary:' property!")?;
-        }
+    /// Reads the YAML written by [`Self::to_yaml`]
+    pub fn from_yaml<R: Read>(reader: R) -> Result<Self, Box<dyn Error>> {
+        Ok(serde_yaml::with::singleton_map_recursive::deserialize(serde_yaml::Deserializer::from_reader(reader))?)
+    }
 
-        let (_, summary) = line.split_once(':').ok_or("Missing summary value")?;
-        let summary = UObjectSummary::from_string(summary.trim())?;
+    /// Reads the text written by [`Self::to_string`].  Errors point at the line (and column) they were found at, and
+    /// reading carries on after most of them, so every error in the text is returned.
+    pub fn from_string<R: BufRead + Seek>(reader: &mut R) -> Result<Self, TextErrors> {
+        Self::read_text(reader).map_err(|errors| errors.locate(reader))
+    }
 
-        line.clear();
+    fn read_text<R: BufRead + Seek>(reader: &mut R) -> Result<Self, TextErrors> {
+        let mut line = String::new();
         reader.read_line(&mut line).unwrap();
 
-        if !line.starts_with("contents:") {
-            Err("IoUObject string should follow 'summary:' with 'contents:'")?;
-        }
-
-        let mut properties = vec![];
-        while let Some(prop) = UObjectProperty::from_string::<R>(reader, 2)? {
-            properties.push(prop);
+        let summary = line.strip_prefix("summary:").ok_or_else(|| reader.text_error("IoUObject string should start with 'summary:' property"))?;
+        let mut summary = UObjectSummary::from_string(summary.trim()).map_err(|err| reader.text_error(err.to_string()).at_token(summary))?;
+        let next_line = next_nonempty_line(reader);
+        match next_line.strip_prefix("engine:") {
+            Some(engine_version) => {
+                let engine_version: EngineVersion = parse_value(reader, engine_version)?;
+                engine_version.check_iostore_summary().map_err(|err| reader.text_error(err))?;
+                summary.set_engine_version(engine_version).map_err(|err| reader.text_error(err.to_string()))?;
+            },
+            None => { reader.seek(SeekFrom::Current(-(next_line.len() as i64))).unwrap(); }, // Older text has no engine version
         }
+        summary.maps_from_string(reader)?;
+        let exports = UObjectExport::all_from_string(reader, &summary)?;
 
         Ok(Self {
             summary,
-            properties,
+            exports,
         })
     }
 }
 
 #[allow(dead_code)]
 #[allow(unused_imports)]
-mod test {
+pub(crate) mod test {
     use byteorder::LE;
     use std::io::{Cursor, Write};
+    use base64::{prelude::BASE64_STANDARD, Engine};
 
-    use super::{IoUObject, StringType, UObjectProperty, UObjectPropertyData, UObjectPropertyHeader, UObjectPropertyMetadata, UObjectSummary, UObjectSummaryHeader};
+    use super::{EngineVersion, ExportCommandType, FTextHistory, IoUObject, ObjectReference, PackageObjectIndex, StringType, UObjectExportBundle, UObjectExportBundleEntry, UObjectExport, UObjectExportMapEntry, UObjectProperty, UObjectPropertyData, UObjectPropertyHeader, UObjectPropertyMetadata, UObjectSummary, UObjectSummaryHeader, ZenSummary};
+    use crate::error::{EncodeError, ParseError};
+    use crate::struct_codecs::StructFieldValue;
+    use crate::usmap::{script_import_hash, Mappings};
 
     fn get_test_object_summary() -> UObjectSummary {
         let summary_header = UObjectSummaryHeader {
             name: 1,
             source_name: 5,
             package_flags: 0,
-            cooked_header_size: 0x40,
+            cooked_header_size: 0x23c,
             name_map_names_offset: 0x40,
-            name_map_names_size: 0x10,
-            name_map_hashes_offset: 0x50,
+            name_map_names_size: 0xf0,
+            name_map_hashes_offset: 0x130,
             name_map_hashes_size: 0x98,
-            import_map_offset: 0x130,
-            export_bundles_offset: 0x140,
-            export_map_offset: 0x150,
-            graph_data_offset: 0x12c,
+            import_map_offset: 0x1c8,
+            export_map_offset: 0x1d8,
+            export_bundles_offset: 0x220,
+            graph_data_offset: 0x238,
             graph_data_size: 0x04,
             pad: 0
         };
         UObjectSummary {
+            engine_version: EngineVersion::Ue4_27,
             header: summary_header,
+            zen: None,
             name_map: vec![
                 "None".to_string(),
                 "ArrayProperty".to_string(),
@@ -1225,7 +3640,35 @@ mod test {
                 StringType::Utf8,
                 StringType::Utf16,
             ],
-            remaining_bytes: vec![0;0]
+            import_map: vec![
+                PackageObjectIndex::NULL,
+                PackageObjectIndex(0x4000_0000_1234_abcd),
+            ],
+            export_map: vec![
+                UObjectExportMapEntry {
+                    cooked_serial_offset: 0x23c,
+                    cooked_serial_size: 0,
+                    object_name: "TestStruct".to_string(),
+                    object_name_number: 0,
+                    outer_index: PackageObjectIndex::NULL,
+                    class_index: PackageObjectIndex(0x4000_0000_1234_abcd),
+                    super_index: PackageObjectIndex::NULL,
+                    template_index: PackageObjectIndex(0x4000_0000_5678_ef01),
+                    global_import_index: PackageObjectIndex(0x8000_1111_2222_3333),
+                    object_flags: 0x48,
+                    filter_flags: 0,
+                }
+            ],
+            export_bundles: vec![
+                UObjectExportBundle {
+                    entries: vec![
+                        UObjectExportBundleEntry { local_export_index: 0, command_type: ExportCommandType::Create },
+                        UObjectExportBundleEntry { local_export_index: 0, command_type: ExportCommandType::Serialize },
+                    ]
+                }
+            ],
+            graph_data: vec![0;4],
+            import_paths: vec![],
         }
     }
 
@@ -1307,95 +3750,104 @@ mod test {
         }
     }
 
-    fn get_test_object() -> IoUObject {        
+    fn mkobject(properties: Vec<UObjectProperty>) -> IoUObject {
         IoUObject {
             summary: get_test_object_summary(),
-            properties: vec![
-                mkbool(true),
-                mkbyte(25,0),
-                mkint(77),
-                mkfloat(192f32),
-                mkstr("TEEESTString"),
-                UObjectProperty {
-                    header: UObjectPropertyHeader {
-                        name: "TestMap".to_string(),
-                        arr_index: 0,
-                        r#type: "MapProperty".to_string(),
-                    },
-                    metadata: UObjectPropertyMetadata::Map("IntProperty".to_string(), "StructProperty".to_string()),
-                    data: UObjectPropertyData::Map(vec![
-                        (
-                            UObjectPropertyData::Int32(0),
-                            UObjectPropertyData::Struct(vec![
-                                mkint(0),
-                                mkint(1),
-                                mkint(2),
-                                mkfloat(3f32),
-                            ], vec![])
-                        ),
-                        (
-                            UObjectPropertyData::Int32(1),
-                            UObjectPropertyData::Struct(vec![
-                                mkbool(true),
-                                mkbool(false),
-                                mkbyte(7,7),
-                                mkstr("MoreTesting"),
-                            ], vec![])
-                        ),
-                        (
-                            UObjectPropertyData::Int32(2),
-                            UObjectPropertyData::Struct(vec![
-                                UObjectProperty {
-                                    header: UObjectPropertyHeader {
-                                        name: "TestMap".to_string(), 
-                                        arr_index: 0,
-                                        r#type: "MapProperty".to_string()
-                                    },
-                                    metadata: UObjectPropertyMetadata::Map("StrProperty".to_string(), "IntProperty".to_string()),
-                                    data: UObjectPropertyData::Map(vec![
-                                        (UObjectPropertyData::String("Prop1".to_string()), UObjectPropertyData::Int32(5)),
-                                        (UObjectPropertyData::String("TestProp2".to_string()), UObjectPropertyData::Int32(7)),
-                                    ])
-                                }
-                            ], vec![])
-                        ),
-                        (
-                            UObjectPropertyData::Int32(30),
-                            UObjectPropertyData::Struct(vec![
-                                mkstr("SkipMapKeys")
-                            ], vec![])
-                        ),
-                        (
-                            UObjectPropertyData::Int32(2),
-                            UObjectPropertyData::Struct(vec![
-                                UObjectProperty { 
-                                    header: UObjectPropertyHeader {
-                                        name: "TestMap".to_string(), 
-                                        arr_index: 0,
-                                        r#type: "MapProperty".to_string()
-                                    },
-                                    metadata: UObjectPropertyMetadata::Map("StrProperty".to_string(), "StructProperty".to_string()),
-                                    data: UObjectPropertyData::Map(vec![
-                                        (UObjectPropertyData::String("Prop1".to_string()), UObjectPropertyData::Struct(vec![
-                                            mkstr("NestedStruct"),
-                                            mkfloat(77f32),
-                                        ], vec![])),
-                                        (UObjectPropertyData::String("TestProp2".to_string()), UObjectPropertyData::Struct(vec![
-                                            mkbyte(25,6),
-                                            mkstr("TestEndMapOnNestedStruct"),
-                                        ], vec![])),
-                                    ])
-                                }
-                            ], vec![])
-                        ),
-                    ])
-                },
-                mkfloat(999f32),
-                mkstr("End of the object"),
-            ]
+            exports: vec![UObjectExport {
+                export_index: 0,
+                class: None,
+                properties,
+                extra_data: vec![0;4],
+            }]
         }
     }
 
+    pub(crate) fn get_test_object() -> IoUObject {
+        mkobject(vec![
+            mkbool(true),
+            mkbyte(25,0),
+            mkint(77),
+            mkfloat(192f32),
+            mkstr("TEEESTString"),
+            UObjectProperty {
+                header: UObjectPropertyHeader {
+                    name: "TestMap".to_string(),
+                    arr_index: 0,
+                    r#type: "MapProperty".to_string(),
+                },
+                metadata: UObjectPropertyMetadata::Map("IntProperty".to_string(), "StructProperty".to_string()),
+                data: UObjectPropertyData::Map(vec![
+                    (
+                        UObjectPropertyData::Int32(0),
+                        UObjectPropertyData::Struct(vec![
+                            mkint(0),
+                            mkint(1),
+                            mkint(2),
+                            mkfloat(3f32),
+                        ], vec![])
+                    ),
+                    (
+                        UObjectPropertyData::Int32(1),
+                        UObjectPropertyData::Struct(vec![
+                            mkbool(true),
+                            mkbool(false),
+                            mkbyte(7,7),
+                            mkstr("MoreTesting"),
+                        ], vec![])
+                    ),
+                    (
+                        UObjectPropertyData::Int32(2),
+                        UObjectPropertyData::Struct(vec![
+                            UObjectProperty {
+                                header: UObjectPropertyHeader {
+                                    name: "TestMap".to_string(), 
+                                    arr_index: 0,
+                                    r#type: "MapProperty".to_string()
+                                },
+                                metadata: UObjectPropertyMetadata::Map("StrProperty".to_string(), "IntProperty".to_string()),
+                                data: UObjectPropertyData::Map(vec![
+                                    (UObjectPropertyData::String("Prop1".to_string()), UObjectPropertyData::Int32(5)),
+                                    (UObjectPropertyData::String("TestProp2".to_string()), UObjectPropertyData::Int32(7)),
+                                ])
+                            }
+                        ], vec![])
+                    ),
+                    (
+                        UObjectPropertyData::Int32(30),
+                        UObjectPropertyData::Struct(vec![
+                            mkstr("SkipMapKeys")
+                        ], vec![])
+                    ),
+                    (
+                        UObjectPropertyData::Int32(2),
+                        UObjectPropertyData::Struct(vec![
+                            UObjectProperty { 
+                                header: UObjectPropertyHeader {
+                                    name: "TestMap".to_string(), 
+                                    arr_index: 0,
+                                    r#type: "MapProperty".to_string()
+                                },
+                                metadata: UObjectPropertyMetadata::Map("StrProperty".to_string(), "StructProperty".to_string()),
+                                data: UObjectPropertyData::Map(vec![
+                                    (UObjectPropertyData::String("Prop1".to_string()), UObjectPropertyData::Struct(vec![
+                                        mkstr("NestedStruct"),
+                                        mkfloat(77f32),
+                                    ], vec![])),
+                                    (UObjectPropertyData::String("TestProp2".to_string()), UObjectPropertyData::Struct(vec![
+                                        mkbyte(25,6),
+                                        mkstr("TestEndMapOnNestedStruct"),
+                                    ], vec![])),
+                                ])
+                            }
+                        ], vec![])
+                    ),
+                ])
+            },
+            mkfloat(999f32),
+            mkstr("End of the object"),
+        ])
+    }
+
     fn assert_equality(a: &IoUObject, b: &IoUObject) {
 
         // Summary Header
@@ -1416,14 +3868,38 @@ mod test {
 
         // Summary other properties
         assert_eq!(a.summary.name_map, b.summary.name_map);
-        assert_eq!(a.summary.remaining_bytes, b.summary.remaining_bytes);
-
-        // Properties
-        assert_eq!(a.properties.len(), b.properties.len());
-        for i in 0..a.properties.len() {
-            assert_eq!(a.properties[i].header.name, b.properties[i].header.name);
-            assert_eq!(a.properties[i].header.arr_index, b.properties[i].header.arr_index);
-            assert_eq!(a.properties[i].data, b.properties[i].data);
+        assert_eq!(a.summary.zen, b.summary.zen);
+        assert_eq!(a.summary.engine_version, b.summary.engine_version);
+        assert_eq!(a.summary.import_map, b.summary.import_map);
+        assert_eq!(a.summary.export_map.len(), b.summary.export_map.len());
+        for i in 0..a.summary.export_map.len() {
+            // Serial offsets/sizes are recomputed when encoding, so only compare the rest of the entry
+            let (a, b) = (&a.summary.export_map[i], &b.summary.export_map[i]);
+            assert_eq!(a.object_name, b.object_name);
+            assert_eq!(a.object_name_number, b.object_name_number);
+            assert_eq!(a.outer_index, b.outer_index);
+            assert_eq!(a.class_index, b.class_index);
+            assert_eq!(a.super_index, b.super_index);
+            assert_eq!(a.template_index, b.template_index);
+            assert_eq!(a.global_import_index, b.global_import_index);
+            assert_eq!(a.object_flags, b.object_flags);
+            assert_eq!(a.filter_flags, b.filter_flags);
+        }
+        assert_eq!(a.summary.export_bundles, b.summary.export_bundles);
+        assert_eq!(a.summary.graph_data, b.summary.graph_data);
+
+        // Exports
+        assert_eq!(a.exports.len(), b.exports.len());
+        for (a, b) in a.exports.iter().zip(&b.exports) {
+            assert_eq!(a.export_index, b.export_index);
+            assert_eq!(a.class, b.class);
+            assert_eq!(a.extra_data, b.extra_data);
+            assert_eq!(a.properties.len(), b.properties.len());
+            for i in 0..a.properties.len() {
+                assert_eq!(a.properties[i].header.name, b.properties[i].header.name);
+                assert_eq!(a.properties[i].header.arr_index, b.properties[i].header.arr_index);
+                assert_eq!(a.properties[i].data, b.properties[i].data);
+            }
         }
     }
 
@@ -1432,12 +3908,173 @@ mod test {
         let test = get_test_object();
 
         let mut serialized_bytes = Cursor::new(vec![]);
-        test.to_bytes::<_,LE>(&mut serialized_bytes);
+        test.to_bytes::<_,LE>(&mut serialized_bytes).unwrap();
         serialized_bytes.set_position(0);
         match IoUObject::from_buffer::<_,LE>(&mut serialized_bytes) {
             Ok(deserialized) => assert_equality(&deserialized, &test),
-            Err(err) => panic!("{:?}",err.source()),
+            Err(err) => panic!("{err}"),
+        }
+    }
+
+    #[test]
+    fn truncated_packages_are_errors() {
+        let mut bytes = vec![];
+        get_test_object().to_bytes::<_,LE>(&mut bytes).unwrap();
+        for len in 0..bytes.len() {
+            assert!(IoUObject::from_buffer::<_,LE>(&mut Cursor::new(&bytes[..len])).is_err(), "Package truncated to {len} bytes was read");
         }
+
+        let err = IoUObject::from_buffer::<_,LE>(&mut Cursor::new(&bytes[..bytes.len() - 1])).err().unwrap();
+        assert!(matches!(err, ParseError::UnexpectedEof { .. }), "{err}");
+        assert_eq!(err.path(), "exports[0]");
+    }
+
+    #[test]
+    fn bad_summary_sizes_are_errors() {
+        let mut bytes = vec![];
+        get_test_object().to_bytes::<_,LE>(&mut bytes).unwrap();
+
+        let mut negative_graph_data = bytes.clone();
+        negative_graph_data[56..60].copy_from_slice(&(-1i32).to_le_bytes());
+        let err = IoUObject::from_buffer::<_,LE>(&mut Cursor::new(&negative_graph_data)).err().unwrap();
+        assert!(matches!(err, ParseError::Invalid { offset: 0, .. }), "{err}");
+
+        let mut huge_name_map = bytes.clone();
+        huge_name_map[36..40].copy_from_slice(&i32::MAX.to_le_bytes());
+        assert!(IoUObject::from_buffer::<_,LE>(&mut Cursor::new(&huge_name_map)).is_err());
+
+        // A UE4 summary read as a Zen one has its counts and sizes in the wrong places
+        assert!(IoUObject::from_buffer_with_options::<_,LE>(&mut Cursor::new(&bytes), None, Some(EngineVersion::Ue5)).is_err());
+    }
+
+    #[test]
+    fn bad_name_indices_report_their_offset_and_property() {
+        let mut bytes = vec![];
+        get_test_object().to_bytes::<_,LE>(&mut bytes).unwrap();
+
+        // The second int in the first struct of TestMap
+        let mut tag = vec![];
+        tag.extend(8u64.to_le_bytes()); // TestInt
+        tag.extend(7u64.to_le_bytes()); // IntProperty
+        tag.extend([4, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0]);
+        let offset = bytes.windows(tag.len()).position(|w| w == tag).unwrap();
+        bytes[offset] = 99;
+
+        let err = IoUObject::from_buffer::<_,LE>(&mut Cursor::new(&bytes)).err().unwrap();
+        assert_eq!(err, ParseError::BadNameIndex { index: 99, offset: offset as u64, path: "exports[0].TestMap[0].value".to_string() });
+    }
+
+    #[test]
+    fn missing_object_references_report_their_property() {
+        let mut test = get_test_object();
+        test.exports[0].properties.push(mkprop("TestArray", UObjectPropertyMetadata::Array("ObjectProperty".to_string()), UObjectPropertyData::Array(vec![
+            UObjectPropertyData::Object(ObjectReference::Null),
+            UObjectPropertyData::Object(ObjectReference::Export("Missing".to_string())),
+        ], None)));
+
+        let err = test.to_bytes::<_,LE>(&mut vec![]).unwrap_err();
+        assert_eq!(err, EncodeError::MissingObject { reference: "export:Missing".to_string(), path: "exports[0].TestArray[1]".to_string() });
+        assert_eq!(err.to_string(), "Object reference [export:Missing] isn't in the package in exports[0].TestArray[1]");
+    }
+
+    #[test]
+    fn renamed_exports_add_their_name() {
+        let mut test = get_test_object();
+        test.summary.export_map[0].object_name = "RenamedExport".to_string();
+        let err = test.summary.to_bytes::<LE>().unwrap_err();
+        assert_eq!(err, EncodeError::MissingName { name: "RenamedExport".to_string(), path: "exports[0].object_name".to_string() });
+
+        let mut bytes = vec![];
+        test.to_bytes::<_,LE>(&mut bytes).unwrap();
+        let reread = IoUObject::from_buffer::<_,LE>(&mut Cursor::new(&bytes)).unwrap();
+        assert_eq!(reread.summary.export_map[0].object_name, "RenamedExport");
+    }
+
+    #[test]
+    fn out_of_range_serial_offsets_are_errors() {
+        let mut test = get_test_object();
+        test.summary.export_map[0].cooked_serial_size = 1;
+        test.summary.export_map[0].cooked_serial_offset = u64::MAX;
+        let err = test.to_bytes::<_,LE>(&mut vec![]).unwrap_err();
+        assert!(matches!(err, EncodeError::Unsupported { .. }), "{err}");
+        assert_eq!(err.path(), "exports[0]");
+    }
+
+    #[test]
+    fn zero_values_are_errors_in_tagged_exports() {
+        let mut test = get_test_object();
+        test.exports[0].properties.push(mkprop("TestInt", UObjectPropertyMetadata::None, UObjectPropertyData::Zero("IntProperty".to_string())));
+
+        let err = test.to_bytes::<_,LE>(&mut vec![]).unwrap_err();
+        assert!(matches!(err, EncodeError::Unsupported { .. }), "{err}");
+        assert_eq!(err.path(), "exports[0].TestInt");
+    }
+
+    #[test]
+    fn text_errors_report_their_line_and_column() {
+        let mut text = vec![];
+        get_test_object().to_string(&mut text);
+        let text = String::from_utf8(text).unwrap();
+        let line_of = |needle: &str| text.lines().position(|l| l.contains(needle)).unwrap() + 1;
+
+        let bad_value = text.replace("TestInt: !i32 77", "TestInt: !i32 7x7");
+        let errors = IoUObject::from_string(&mut Cursor::new(bad_value.as_bytes())).err().unwrap();
+        let err = &errors.0[0];
+        assert_eq!((err.line, err.column), (line_of("TestInt: !i32 77"), 17));
+        assert_eq!(err.snippet, "  TestInt: !i32 7x7");
+
+        let bad_type = text.replace("- TestProp2:!i32 7", "- TestProp2: 7.5");
+        let errors = IoUObject::from_string(&mut Cursor::new(bad_type.as_bytes())).err().unwrap();
+        let err = &errors.0[0];
+        assert_eq!(err.message, "expected value type 'IntProperty', but got 'FloatProperty'");
+        assert_eq!((err.line, err.column), (line_of("- TestProp2:!i32 7"), 28));
+        assert!(err.to_string().starts_with(&format!("{}:28: expected value type", err.line)), "{err}");
+    }
+
+    #[test]
+    fn export_contents_must_match_the_export_map() {
+        let mut text = vec![];
+        get_test_object().to_string(&mut text);
+        let text = String::from_utf8(text).unwrap();
+        let contents = text.find("contents:").unwrap();
+        let last_line_of = |text: &str, needle: &str| text.lines().enumerate().filter(|(_, l)| l.contains(needle)).last().unwrap().0 + 1;
+
+        let duplicate = format!("{text}{}", &text[contents..]);
+        let errors = IoUObject::from_string(&mut Cursor::new(duplicate.as_bytes())).err().unwrap();
+        assert_eq!(errors.0.len(), 1, "{errors}");
+        assert_eq!(errors.0[0].message, "contents for export 0 are given more than once");
+        assert_eq!(errors.0[0].line, last_line_of(&duplicate, "contents:"));
+
+        let missing = &text[..contents];
+        let errors = IoUObject::from_string(&mut Cursor::new(missing.as_bytes())).err().unwrap();
+        assert_eq!(errors.0[0].message, "contents for export 0 are missing");
+        assert_ne!(errors.0[0].line, 0);
+
+        let mut test = get_test_object();
+        test.exports.clear();
+        let err = test.to_bytes::<_,LE>(&mut vec![]).err().unwrap();
+        assert_eq!(err.to_string(), EncodeError::unsupported("contents for export 0 are missing").to_string());
+    }
+
+    #[test]
+    fn text_errors_are_collected_from_every_property() {
+        let mut text = vec![];
+        get_test_object().to_string(&mut text);
+        let text = String::from_utf8(text).unwrap()
+            .replace("TestInt: !i32 77", "TestInt: !i32 7x7")
+            .replace("TestByte: !ByteProperty 7 0 7", "TestByte: !ByteProperty 7")
+            .replace("- TestProp2:!i32 7", "- TestProp2: 7.5")
+            .replace("TestString: \"End of the object\"", "TestString: \"End of the object");
+        let line_of = |needle: &str| text.lines().position(|l| l.contains(needle)).unwrap() + 1;
+
+        let errors = IoUObject::from_string(&mut Cursor::new(text.as_bytes())).err().unwrap();
+        let lines: Vec<usize> = errors.0.iter().map(|err| err.line).collect();
+        assert_eq!(lines, [
+            line_of("TestInt: !i32 7x7"),
+            line_of("!ByteProperty 7"),
+            line_of("- TestProp2: 7.5"),
+            line_of("\"End of the object"),
+        ], "{errors}");
     }
 
     #[test]
@@ -1473,31 +4110,77 @@ mod test {
         }
 
         let mut serialized_bytes = Cursor::new(vec![]);
-        test.to_bytes::<_,LE>(&mut serialized_bytes);
+        test.to_bytes::<_,LE>(&mut serialized_bytes).unwrap();
         serialized_bytes.set_position(0);
         match IoUObject::from_buffer::<_,LE>(&mut serialized_bytes) {
             Ok(deserialized) => assert_equality(&deserialized, &test),
             Err(err) => panic!("{:?}",err),
         }
+
+        verify_yaml_round_trip(&test);
+    }
+
+    /// Writes the object as YAML, re-emits it from a YAML library's own document model, and checks that it reads
+    /// back to the same object and bytes
+    fn verify_yaml_round_trip(test: &IoUObject) {
+        let mut yaml = vec![];
+        test.to_yaml(&mut yaml).unwrap();
+        let yaml = String::from_utf8(yaml).unwrap();
+        println!("{yaml}");
+
+        let document: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+        let reemitted = serde_yaml::to_string(&document).unwrap();
+        assert_eq!(reemitted, yaml);
+
+        let deserialized = match IoUObject::from_yaml(reemitted.as_bytes()) {
+            Ok(deserialized) => deserialized,
+            Err(err) => panic!("{err}"),
+        };
+        assert_equality(&deserialized, test);
+
+        let (mut expected, mut actual) = (vec![], vec![]);
+        test.to_bytes::<_,LE>(&mut expected).unwrap();
+        deserialized.to_bytes::<_,LE>(&mut actual).unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn yaml_serialization_is_consistent() {
+        let test = get_test_object();
+        verify_yaml_round_trip(&test);
+
+        let mut yaml = vec![];
+        test.to_yaml(&mut yaml).unwrap();
+        let yaml = String::from_utf8(yaml).unwrap();
+        assert!(!yaml.contains('!'), "YAML shouldn't need tags:\n{yaml}");
+        assert!(yaml.starts_with("summary:\n  header:\n    name: 1\n"), "{yaml}");
+        assert!(yaml.contains("\n  names:\n  - None\n"), "{yaml}");
+
+        // Summary names are laid out by the header's offsets, so they can't be changed on their own
+        let renamed = yaml.replace("\n  - TestInt\n", "\n  - TestIntRenamed\n");
+        assert!(IoUObject::from_yaml(renamed.as_bytes()).is_err());
+
+        let yaml = yaml.replace("Int32: 77", "Int32: 78");
+        let edited = IoUObject::from_yaml(yaml.as_bytes()).unwrap();
+        let (_, exports) = edited.into_parts();
+        assert!(exports[0].properties.iter().any(|prop| prop.data == UObjectPropertyData::Int32(78)));
+
+        let err = IoUObject::from_yaml(yaml.replace("Int32: 78", "Int32: seventy").as_bytes()).err().unwrap();
+        assert!(err.to_string().contains("line"), "{err}");
     }
 
     #[test]
     pub fn utf16_str_property() {
-        let test = IoUObject {
-            summary: get_test_object_summary(),
-            properties: vec![
+        let test = mkobject(vec![
                 mkstr16("Zażółć gęślą jaźń")
-            ]
-        };
+            ]);
 
         verify_serialize_and_deserialize(test);
     }
 
     #[test]
     pub fn array_property() {
-        let test = IoUObject {
-            summary: get_test_object_summary(),
-            properties: vec![
+        let test = mkobject(vec![
                 UObjectProperty {
                     header: UObjectPropertyHeader {
                         name: "TestArray".to_string(),
@@ -1525,19 +4208,827 @@ mod test {
                         UObjectPropertyData::Struct(vec![mkstr("Test struct 3"), mkbool(true), mkstr("No"), mkint(11)], vec![]),
                     ], Some((UObjectPropertyHeader { name: "TestStruct".to_string(), r#type: "StructProperty".to_string(), arr_index: 0}, "TestArray".to_string())))
                 },
+            ]);
+
+        verify_serialize_and_deserialize(test);
+    }
+
+    #[test]
+    fn summary_maps_are_byte_exact() {
+        let summary = get_test_object_summary();
+        let bytes = summary.to_bytes::<LE>().unwrap();
+        assert_eq!(bytes.len(), 0x23c);
+
+        let deserialized = UObjectSummary::from_buffer::<_,LE>(&mut Cursor::new(bytes.clone()), None).unwrap();
+        assert_eq!(bytes, deserialized.to_bytes::<LE>().unwrap());
+    }
+
+    #[test]
+    fn export_serial_size_tracks_property_length() {
+        let short = mkobject(vec![mkstr("Short")]);
+        let long = mkobject(vec![mkstr("A much longer string value")]);
+
+        for test in [short, long] {
+            let mut serialized_bytes = Cursor::new(vec![]);
+            let total_len = test.to_bytes::<_,LE>(&mut serialized_bytes).unwrap();
+            serialized_bytes.set_position(0);
+
+            let deserialized = IoUObject::from_buffer::<_,LE>(&mut serialized_bytes).unwrap();
+            let export = &deserialized.summary.export_map[0];
+            assert_eq!(export.cooked_serial_offset, 0x23c);
+            assert_eq!(export.cooked_serial_size as usize, total_len - 0x23c);
+        }
+    }
+
+    #[test]
+    fn name_map_hashes_are_regenerated() {
+        let summary = get_test_object_summary();
+        let mut bytes = summary.to_bytes::<LE>().unwrap();
+
+        let hashes_offset = summary.header.name_map_hashes_offset as usize;
+        let read_hash = |bytes: &[u8], i: usize| u64::from_le_bytes(bytes[hashes_offset + i*8..hashes_offset + (i+1)*8].try_into().unwrap());
+        assert_eq!(read_hash(&bytes, 0), 0xC1640000);
+        assert_eq!(read_hash(&bytes, 1), super::name_hash("None", &StringType::Utf8));
+        assert_eq!(read_hash(&bytes, 1), super::name_hash("NONE", &StringType::Utf8));
+        assert_eq!(read_hash(&bytes, 18), super::name_hash("someutf16property", &StringType::Utf16));
+
+        // Corrupt a hash - it should be replaced with the correct value when reencoding
+        let expected = bytes.clone();
+        bytes[hashes_offset + 8] ^= 0xff;
+        let deserialized = UObjectSummary::from_buffer::<_,LE>(&mut Cursor::new(bytes), None).unwrap();
+        assert_eq!(deserialized.to_bytes::<LE>().unwrap(), expected);
+    }
+
+    #[test]
+    fn new_names_are_added_to_name_map() {
+        let mut new_prop = mkint(5);
+        new_prop.header.name = "BrandNewProperty".to_string();
+        let test = mkobject(vec![
+                new_prop,
+                UObjectProperty {
+                    header: UObjectPropertyHeader {
+                        name: "TestEnum".to_string(),
+                        arr_index: 0,
+                        r#type: "EnumProperty".to_string(),
+                    },
+                    metadata: UObjectPropertyMetadata::Enum("ENewEnum".to_string()),
+                    data: UObjectPropertyData::Enum("ENewEnum::Wąż".to_string()),
+                },
+                mkstr("After the new names"),
+            ]);
+
+        let mut serialized_bytes = Cursor::new(vec![]);
+        test.to_bytes::<_,LE>(&mut serialized_bytes).unwrap();
+        serialized_bytes.set_position(0);
+        let deserialized = IoUObject::from_buffer::<_,LE>(&mut serialized_bytes).unwrap();
+
+        let original = &test.summary;
+        let summary = &deserialized.summary;
+        assert_eq!(&summary.name_map[..original.name_map.len()], &original.name_map[..]);
+        assert_eq!(&summary.name_map[original.name_map.len()..], &["BrandNewProperty", "TestEnum", "EnumProperty", "ENewEnum", "ENewEnum::Wąż"]);
+        assert!(matches!(summary.name_map_type.last(), Some(StringType::Utf16)));
+        assert_eq!(summary.header.name_map_hashes_size as usize, (summary.name_map.len() + 1) * 8);
+        assert_eq!(summary.header.name_map_hashes_offset % 8, 0);
+
+        let delta = summary.header.import_map_offset - original.header.import_map_offset;
+        assert!(delta > 0);
+        assert_eq!(summary.header.export_map_offset, original.header.export_map_offset + delta);
+        assert_eq!(summary.header.export_bundles_offset, original.header.export_bundles_offset + delta);
+        assert_eq!(summary.header.graph_data_offset, original.header.graph_data_offset + delta);
+        assert_eq!(summary.import_map, original.import_map);
+        assert_eq!(summary.export_bundles, original.export_bundles);
+
+        assert_eq!(deserialized.exports, test.exports);
+    }
+
+    #[test]
+    fn multiple_exports() {
+        let mut test = mkobject(vec![mkint(1), mkstr("First export")]);
+
+        let summary = &mut test.summary;
+        let mut second_export = summary.export_map[0].clone();
+        second_export.object_name = "TestArray".to_string();
+        second_export.object_flags = 0x11;
+        summary.export_map.push(second_export);
+        summary.export_bundles[0].entries.extend([
+            UObjectExportBundleEntry { local_export_index: 1, command_type: ExportCommandType::Create },
+            UObjectExportBundleEntry { local_export_index: 1, command_type: ExportCommandType::Serialize },
+        ]);
+        summary.header.export_bundles_offset += 0x48;
+        summary.header.graph_data_offset += 0x48 + 0x10;
+
+        test.exports.push(UObjectExport {
+            export_index: 1,
+            class: None,
+            properties: vec![mkfloat(2f32), mkbool(true)],
+            extra_data: vec![],
+        });
+
+        verify_serialize_and_deserialize(test);
+    }
+
+    #[test]
+    fn serial_offsets_follow_previous_exports() {
+        let mut test = mkobject(vec![mkstr("Export data that grows")]);
+        let summary = &mut test.summary;
+        let second_export = summary.export_map[0].clone();
+        summary.export_map.push(second_export);
+        summary.export_bundles.push(UObjectExportBundle {
+            entries: vec![UObjectExportBundleEntry { local_export_index: 1, command_type: ExportCommandType::Serialize }]
+        });
+        summary.header.export_bundles_offset += 0x48;
+        summary.header.graph_data_offset += 0x48 + 0x10;
+        test.exports.push(UObjectExport { export_index: 1, class: None, properties: vec![mkint(2)], extra_data: vec![1,2,3] });
+
+        let mut serialized_bytes = Cursor::new(vec![]);
+        test.to_bytes::<_,LE>(&mut serialized_bytes).unwrap();
+        serialized_bytes.set_position(0);
+        let deserialized = IoUObject::from_buffer::<_,LE>(&mut serialized_bytes).unwrap();
+
+        let exports = &deserialized.summary.export_map;
+        assert_eq!(exports[1].cooked_serial_offset, exports[0].cooked_serial_offset + exports[0].cooked_serial_size);
+        assert_eq!(deserialized.exports, test.exports);
+    }
+
+    fn mkprop(name: &str, metadata: UObjectPropertyMetadata, data: UObjectPropertyData) -> UObjectProperty {
+        UObjectProperty {
+            header: UObjectPropertyHeader {
+                name: name.to_string(),
+                arr_index: 0,
+                r#type: data.get_string_type().to_owned(),
+            },
+            metadata,
+            data
+        }
+    }
+
+    fn mkobject_with_new_names(properties: Vec<UObjectProperty>) -> IoUObject {
+        let mut test = mkobject(properties);
+        let mut names = vec![];
+        for prop in &test.exports[0].properties {
+            prop.collect_names(&mut names);
+        }
+        let names: Vec<String> = names.into_iter().map(str::to_owned).collect();
+        test.summary.add_missing_names(names.iter().map(String::as_str));
+        test
+    }
+
+    #[test]
+    fn int64_uint64_and_double_properties() {
+        let test = mkobject_with_new_names(vec![
+            mkprop("TestInt64", UObjectPropertyMetadata::None, UObjectPropertyData::Int64(-9_000_000_000)),
+            mkprop("TestInt64", UObjectPropertyMetadata::None, UObjectPropertyData::Int64(i64::MAX)),
+            mkprop("TestUInt64", UObjectPropertyMetadata::None, UObjectPropertyData::UInt64(u64::MAX)),
+            mkprop("TestDouble", UObjectPropertyMetadata::None, UObjectPropertyData::Double(0.1)),
+            mkprop("TestDouble", UObjectPropertyMetadata::None, UObjectPropertyData::Double(-12345.678901234567)),
+            mkprop("TestDouble", UObjectPropertyMetadata::None, UObjectPropertyData::Double(3.0)),
+        ]);
+
+        verify_serialize_and_deserialize(test);
+    }
+
+    #[test]
+    fn new_numeric_and_bool_container_items() {
+        let test = mkobject_with_new_names(vec![
+            mkprop("TestArray", UObjectPropertyMetadata::Array("Int64Property".to_string()), UObjectPropertyData::Array(vec![
+                UObjectPropertyData::Int64(-1),
+                UObjectPropertyData::Int64(1 << 40),
+            ], None)),
+            mkprop("TestArray", UObjectPropertyMetadata::Array("DoubleProperty".to_string()), UObjectPropertyData::Array(vec![
+                UObjectPropertyData::Double(1.5),
+                UObjectPropertyData::Double(-0.25),
+            ], None)),
+            mkprop("TestArray", UObjectPropertyMetadata::Array("BoolProperty".to_string()), UObjectPropertyData::Array(vec![
+                UObjectPropertyData::BoolValue(true),
+                UObjectPropertyData::BoolValue(false),
+                UObjectPropertyData::BoolValue(true),
+            ], None)),
+            mkprop("TestMap", UObjectPropertyMetadata::Map("UInt64Property".to_string(), "DoubleProperty".to_string()), UObjectPropertyData::Map(vec![
+                (UObjectPropertyData::UInt64(u64::MAX), UObjectPropertyData::Double(2.75)),
+                (UObjectPropertyData::UInt64(7), UObjectPropertyData::Double(-8.0)),
+            ])),
+            mkprop("TestMap", UObjectPropertyMetadata::Map("Int64Property".to_string(), "BoolProperty".to_string()), UObjectPropertyData::Map(vec![
+                (UObjectPropertyData::Int64(-5), UObjectPropertyData::BoolValue(false)),
+                (UObjectPropertyData::Int64(5), UObjectPropertyData::BoolValue(true)),
+            ])),
+            mkprop("TestMap", UObjectPropertyMetadata::Map("BoolProperty".to_string(), "Int64Property".to_string()), UObjectPropertyData::Map(vec![
+                (UObjectPropertyData::BoolValue(true), UObjectPropertyData::Int64(i64::MIN)),
+            ])),
+        ]);
+
+        verify_serialize_and_deserialize(test);
+    }
+
+    #[test]
+    fn text_property() {
+        let mktext = |flags: u32, history: FTextHistory| UObjectPropertyData::Text(flags, Box::new(history));
+        let test = mkobject_with_new_names(vec![
+            mkprop("TestText", UObjectPropertyMetadata::None, mktext(0, FTextHistory::Base(
+                UObjectPropertyData::String("Namespace".to_string()),
+                UObjectPropertyData::String("8A1C3F0E4B9D2E7F".to_string()),
+                UObjectPropertyData::String("Hello\nworld".to_string()),
+            ))),
+            mkprop("TestText", UObjectPropertyMetadata::None, mktext(0x10, FTextHistory::Base(
+                UObjectPropertyData::String(String::new()),
+                UObjectPropertyData::String("Key".to_string()),
+                UObjectPropertyData::StringUtf16("Zażółć gęślą jaźń".to_string()),
+            ))),
+            mkprop("TestText", UObjectPropertyMetadata::None, mktext(2, FTextHistory::None(Some(UObjectPropertyData::String("Invariant".to_string()))))),
+            mkprop("TestText", UObjectPropertyMetadata::None, mktext(0, FTextHistory::None(None))),
+            mkprop("TestArray", UObjectPropertyMetadata::Array("TextProperty".to_string()), UObjectPropertyData::Array(vec![
+                mktext(0, FTextHistory::None(None)),
+                mktext(0, FTextHistory::Base(
+                    UObjectPropertyData::String("Namespace".to_string()),
+                    UObjectPropertyData::String("Key".to_string()),
+                    UObjectPropertyData::String("Array text".to_string()),
+                )),
+            ], None)),
+            mkstr("After text"),
+        ]);
+
+        verify_serialize_and_deserialize(test);
+    }
+
+    #[test]
+    fn object_references() {
+        let test = mkobject_with_new_names(vec![
+            mkprop("TestObject", UObjectPropertyMetadata::None, UObjectPropertyData::Object(ObjectReference::Null)),
+            mkprop("TestObject", UObjectPropertyMetadata::None, UObjectPropertyData::Object(ObjectReference::Export("TestStruct".to_string()))),
+            mkprop("TestObject", UObjectPropertyMetadata::None, UObjectPropertyData::Object(ObjectReference::Import("script:0x1234abcd".to_string()))),
+            mkprop("TestClass", UObjectPropertyMetadata::None, UObjectPropertyData::Class(ObjectReference::Import("null".to_string()))),
+            mkprop("TestWeak", UObjectPropertyMetadata::None, UObjectPropertyData::WeakObject(ObjectReference::Export("TestStruct".to_string()))),
+            mkprop("TestLazy", UObjectPropertyMetadata::None, UObjectPropertyData::LazyObject([0xab;16])),
+            mkprop("TestSoft", UObjectPropertyMetadata::None, UObjectPropertyData::SoftObject("/Game/Test/Asset.Asset".to_string(), String::new())),
+            mkprop("TestSoft", UObjectPropertyMetadata::None, UObjectPropertyData::SoftObject("/Game/Test/Asset.Asset".to_string(), "Sub.Path".to_string())),
+            mkprop("TestSoftClass", UObjectPropertyMetadata::None, UObjectPropertyData::SoftClass("/Game/Test/Actor.Actor_C".to_string(), String::new())),
+            mkprop("TestArray", UObjectPropertyMetadata::Array("ObjectProperty".to_string()), UObjectPropertyData::Array(vec![
+                UObjectPropertyData::Object(ObjectReference::Export("TestStruct".to_string())),
+                UObjectPropertyData::Object(ObjectReference::Null),
+            ], None)),
+            mkprop("TestMap", UObjectPropertyMetadata::Map("ObjectProperty".to_string(), "IntProperty".to_string()), UObjectPropertyData::Map(vec![
+                (UObjectPropertyData::Object(ObjectReference::Export("TestStruct".to_string())), UObjectPropertyData::Int32(1)),
+                (UObjectPropertyData::Object(ObjectReference::Import("script:0x1234abcd".to_string())), UObjectPropertyData::Int32(2)),
+                (UObjectPropertyData::Object(ObjectReference::Null), UObjectPropertyData::Int32(3)),
+            ])),
+            mkprop("TestMap", UObjectPropertyMetadata::Map("ClassProperty".to_string(), "ObjectProperty".to_string()), UObjectPropertyData::Map(vec![
+                (UObjectPropertyData::Class(ObjectReference::Import("null".to_string())), UObjectPropertyData::Object(ObjectReference::Export("TestStruct".to_string()))),
+            ])),
+            mkstr("After objects"),
+        ]);
+
+        let mut text = Vec::new();
+        test.to_string(&mut text);
+        let text = String::from_utf8(text).unwrap();
+        assert!(text.contains("!object export:TestStruct\n"));
+        assert!(text.contains("!object import:script:0x1234abcd\n"));
+        assert!(text.contains("!softobject /Game/Test/Asset.Asset Sub.Path\n"));
+        assert!(text.contains("- import:script:0x1234abcd: !i32 2\n"));
+
+        verify_serialize_and_deserialize(test);
+    }
+
+    #[test]
+    fn write_failures_are_errors() {
+        let test = get_test_object();
+        let mut full = [0u8;16];
+        let err = test.summary.export_map[0].to_bytes::<_,LE>(&mut full.as_mut_slice(), &test.summary.name_map).err().unwrap();
+        assert!(matches!(err, EncodeError::Io { .. }), "{err}");
+        let err = UObjectExportBundle::all_to_bytes::<_,LE>(&test.summary.export_bundles, &mut full[..4].as_mut()).err().unwrap();
+        assert!(matches!(err, EncodeError::Io { .. }), "{err}");
+        assert_eq!(UObjectExportBundle::all_to_bytes::<_,LE>(&test.summary.export_bundles, &mut vec![]).unwrap(), UObjectExportBundle::all_byte_len(&test.summary.export_bundles));
+    }
+
+    #[test]
+    fn out_of_range_summary_offsets_are_errors() {
+        let mut test = get_test_object();
+        assert!(test.summary.resize_export(test.summary.export_map.len(), 16).is_err());
+
+        let valid = test.summary.header_and_name_map_to_bytes::<LE>();
+        for hashes_offset in [i32::MAX, -1, UObjectSummaryHeader::BYTE_LEN as i32] {
+            let mut bytes = valid.clone();
+            bytes[32..36].copy_from_slice(&hashes_offset.to_le_bytes());
+            assert!(UObjectSummary::header_and_name_map_from_buffer::<_,LE>(&mut Cursor::new(bytes)).is_err());
+        }
+    }
+
+    #[test]
+    fn unprintable_map_keys_are_errors() {
+        let test = mkobject_with_new_names(vec![
+            mkprop("TestMap", UObjectPropertyMetadata::Map("SoftObjectProperty".to_string(), "IntProperty".to_string()), UObjectPropertyData::Map(vec![
+                (UObjectPropertyData::SoftObject("/Game/Test/Asset.Asset".to_string(), String::new()), UObjectPropertyData::Int32(1)),
+            ])),
+        ]);
+        let mut bytes = vec![];
+        test.to_bytes::<_,LE>(&mut bytes).unwrap();
+        let err = IoUObject::from_buffer::<_,LE>(&mut Cursor::new(&bytes)).err().unwrap();
+        assert!(err.to_string().contains("Maps with SoftObjectProperty keys aren't supported"), "{err}");
+        assert!(err.path().ends_with("TestMap[0].key"), "{}", err.path());
+    }
+
+    #[test]
+    fn script_imports_are_named_from_mappings() {
+        let mappings = get_test_mappings();
+        let mut test = mkobject_with_new_names(vec![
+            mkprop("TestObject", UObjectPropertyMetadata::None, UObjectPropertyData::Object(ObjectReference::Import("/Script/Engine.TestClass".to_string()))),
+            mkstr("After objects"),
+        ]);
+        test.summary.import_map[1] = PackageObjectIndex::from_import_path("/Script/Engine.TestClass");
+        let mut bytes = vec![];
+        test.to_bytes::<_,LE>(&mut bytes).unwrap();
+
+        let deserialized = IoUObject::from_buffer_with_options::<_,LE>(&mut Cursor::new(&bytes), Some(&mappings), None).unwrap();
+        assert_equality(&deserialized, &test);
+        let mut text = vec![];
+        deserialized.to_string(&mut text);
+        assert!(String::from_utf8(text.clone()).unwrap().contains("!object import:/Script/Engine.TestClass\n"));
+        let mut reencoded = vec![];
+        IoUObject::from_string(&mut Cursor::new(&text)).unwrap().to_bytes::<_,LE>(&mut reencoded).unwrap();
+        assert_eq!(reencoded, bytes);
+
+        // Without mappings, the import can only be written as its hash
+        let deserialized = IoUObject::from_buffer::<_,LE>(&mut Cursor::new(&bytes)).unwrap();
+        let mut text = vec![];
+        deserialized.to_string(&mut text);
+        assert!(String::from_utf8(text.clone()).unwrap().contains(&format!("!object import:{}\n", test.summary.import_map[1])));
+        let mut reencoded = vec![];
+        IoUObject::from_string(&mut Cursor::new(&text)).unwrap().to_bytes::<_,LE>(&mut reencoded).unwrap();
+        assert_eq!(reencoded, bytes);
+    }
+
+    #[test]
+    fn set_property() {
+        let test = mkobject_with_new_names(vec![
+            mkprop("TestSet", UObjectPropertyMetadata::Set("IntProperty".to_string()), UObjectPropertyData::Set(vec![], vec![
+                UObjectPropertyData::Int32(1),
+                UObjectPropertyData::Int32(-2),
+            ])),
+            mkprop("TestSet", UObjectPropertyMetadata::Set("NameProperty".to_string()), UObjectPropertyData::Set(vec![
+                UObjectPropertyData::Name("Removed".to_string()),
+            ], vec![
+                UObjectPropertyData::Name("Kept".to_string()),
+            ])),
+            mkprop("TestSet", UObjectPropertyMetadata::Set("BoolProperty".to_string()), UObjectPropertyData::Set(vec![], vec![
+                UObjectPropertyData::BoolValue(true),
+            ])),
+            mkprop("TestSet", UObjectPropertyMetadata::Set("StructProperty".to_string()), UObjectPropertyData::Set(vec![], vec![
+                UObjectPropertyData::Struct(vec![mkint(3), mkstr("In set")], vec![]),
+            ])),
+            mkprop("TestSet", UObjectPropertyMetadata::Set("StrProperty".to_string()), UObjectPropertyData::Set(vec![], vec![])),
+            mkstr("After sets"),
+        ]);
+
+        verify_serialize_and_deserialize(test);
+    }
+
+    #[test]
+    fn set_items_must_match_item_type() {
+        let text = "!Set\n    item_type: IntProperty\n    items:\n    - 0: !i32 1\n    - 1: \"Not an int\"\n";
+        let (first_line, rest) = text.split_once('\n').unwrap();
+        let mut reader = Cursor::new(rest.as_bytes().to_vec());
+        let err = UObjectPropertyData::from_string(first_line, &mut reader, 2).unwrap_err();
+        assert!(err.to_string().contains("expected item type 'IntProperty', but got 'StrProperty'"), "{err}");
+    }
+
+    #[test]
+    fn struct_metadata() {
+        let test = mkobject_with_new_names(vec![
+            mkprop("TestStruct", UObjectPropertyMetadata::Struct("MyVector".to_string(), [0;16]), UObjectPropertyData::Struct(vec![], vec![0;12])),
+            mkprop("TestStruct", UObjectPropertyMetadata::Struct("MyRowStruct".to_string(), core::array::from_fn(|i| i as u8 * 17)), UObjectPropertyData::Struct(vec![mkint(1), mkstr("Row")], vec![])),
+            mkstr("After structs"),
+        ]);
+
+        let mut text = Vec::new();
+        test.to_string(&mut text);
+        let text = String::from_utf8(text).unwrap();
+        assert!(text.contains("!struct MyVector AAAAAAAAAAAAAAAA\n"));
+        assert!(text.contains("!struct MyRowStruct {00112233-4455-6677-8899-aabbccddeeff}\n"));
+
+        verify_serialize_and_deserialize(test);
+    }
+
+    #[test]
+    fn native_structs() {
+        use crate::struct_codecs::StructFieldValue::*;
+        let mknative = |struct_type: &str, values: Vec<StructFieldValue>| mkprop(
+            "TestStruct",
+            UObjectPropertyMetadata::Struct(struct_type.to_string(), [0;16]),
+            UObjectPropertyData::NativeStruct(struct_type.to_string(), values)
+        );
+        let test = mkobject_with_new_names(vec![
+            mknative("Vector", vec![Float(1.0), Float(-2.5), Float(1e-7)]),
+            mknative("Vector2D", vec![Float(0.0), Float(-0.0)]),
+            mknative("Rotator", vec![Float(90.0), Float(0.1), Float(f32::MAX)]),
+            mknative("Quat", vec![Float(0.0), Float(0.0), Float(0.0), Float(1.0)]),
+            mknative("Color", vec![UInt8(0), UInt8(128), UInt8(255), UInt8(255)]),
+            mknative("LinearColor", vec![Float(1.0), Float(0.5), Float(0.25), Float(1.0)]),
+            mknative("Guid", vec![UInt32(1), UInt32(0xdeadbeef), UInt32(3), UInt32(4)]),
+            mknative("IntPoint", vec![Int32(-1), Int32(7)]),
+            mknative("Box", vec![Float(-1.0), Float(-2.0), Float(-3.0), Float(1.0), Float(2.0), Float(3.0), UInt8(1)]),
+            mknative("DateTime", vec![Int64(637_000_000_000_000_000)]),
+            mknative("Timespan", vec![Int64(-600_000_000)]),
+            mkprop("TestArray", UObjectPropertyMetadata::Array("StructProperty".to_string()), UObjectPropertyData::Array(vec![
+                UObjectPropertyData::NativeStruct("Vector".to_string(), vec![Float(1.0), Float(2.0), Float(3.0)]),
+                UObjectPropertyData::NativeStruct("Vector".to_string(), vec![Float(4.0), Float(5.0), Float(6.0)]),
+            ], Some((UObjectPropertyHeader { name: "TestArray".to_string(), r#type: "StructProperty".to_string(), arr_index: 0}, "Vector".to_string())))),
+            mkstr("After structs"),
+        ]);
+
+        let mut text = Vec::new();
+        test.to_string(&mut text);
+        let text = String::from_utf8(text).unwrap();
+        assert!(text.contains("TestStruct: !Vector {x: 1.0, y: -2.5, z: 1e-7}\n"));
+        assert!(text.contains("- 1:!Vector {x: 4.0, y: 5.0, z: 6.0}\n"));
+
+        verify_serialize_and_deserialize(test);
+    }
+
+    #[test]
+    fn native_structs_without_a_codec_are_written_as_raw_data() {
+        use crate::struct_codecs::StructFieldValue::*;
+        let test = mkobject_with_new_names(vec![
+            mkprop("TestStruct", UObjectPropertyMetadata::Struct("NoCodec".to_string(), [0;16]), UObjectPropertyData::NativeStruct("NoCodec".to_string(), vec![Float(1.0), Int32(2)])),
+        ]);
+
+        let mut text = Vec::new();
+        test.to_string(&mut text);
+        let text = String::from_utf8(text).unwrap();
+        assert!(text.contains(&format!("TestStruct: !struct NoCodec {}\n", BASE64_STANDARD.encode([0, 0, 0x80, 0x3f, 2, 0, 0, 0]))), "{text}");
+
+        let (mut expected, mut actual) = (vec![], vec![]);
+        test.to_bytes::<_,LE>(&mut expected).unwrap();
+        IoUObject::from_string(&mut Cursor::new(text.as_bytes())).unwrap().to_bytes::<_,LE>(&mut actual).unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn native_struct_fields_are_validated() {
+        let mut reader = Cursor::new(vec![]);
+        for (line, expected) in [
+            ("!Vector {x: 1.0, y: 2.0}", "missing field [z]"),
+            ("!Vector {x: 1.0, y: 2.0, z: 3.0, w: 4.0}", "no field named [w]"),
+            ("!Color {b: 1, g: 2, r: 3, a: 256}", "field [a]"),
+        ] {
+            let err = UObjectPropertyData::from_string(line, &mut reader, 2).unwrap_err();
+            assert!(err.to_string().contains(expected), "{err}");
+        }
+    }
+
+    #[test]
+    fn schema_structs_are_decoded_into_fields() {
+        crate::struct_codecs::register_schema(r#"
+            [[struct]]
+            name = "TestSchemaRow"
+            fields = [
+                { name = "health", type = "f32" },
+                { name = "level", type = "i32" },
+                { name = "flags", type = "u16" },
+                { name = "tier", type = "i8" },
             ]
-        };
+        "#).unwrap();
+
+        let mut raw = vec![];
+        raw.extend_from_slice(&75.5f32.to_le_bytes());
+        raw.extend_from_slice(&12i32.to_le_bytes());
+        raw.extend_from_slice(&0x8001u16.to_le_bytes());
+        raw.push(0xff);
+        let mut test = mkobject_with_new_names(vec![
+            mkprop("TestRow", UObjectPropertyMetadata::Struct("TestSchemaRow".to_string(), [0;16]), UObjectPropertyData::Struct(vec![], raw)),
+            mkprop("TestOther", UObjectPropertyMetadata::Struct("TestUnknownRow".to_string(), [0;16]), UObjectPropertyData::Struct(vec![], vec![1,2,3])),
+        ]);
+
+        let mut bytes = Cursor::new(vec![]);
+        test.to_bytes::<_,LE>(&mut bytes).unwrap();
+        bytes.set_position(0);
+        let decoded = IoUObject::from_buffer::<_,LE>(&mut bytes).unwrap();
+        use crate::struct_codecs::StructFieldValue::*;
+        test.exports[0].properties[0].data = UObjectPropertyData::NativeStruct("TestSchemaRow".to_string(), vec![Float(75.5), Int32(12), UInt16(0x8001), Int8(-1)]);
+        assert_equality(&decoded, &test);
+
+        let mut text = Vec::new();
+        decoded.to_string(&mut text);
+        assert!(String::from_utf8(text).unwrap().contains("TestRow: !TestSchemaRow {health: 75.5, level: 12, flags: 32769, tier: -1}\n"));
+        verify_serialize_and_deserialize(decoded);
+    }
+
+    #[test]
+    fn invalid_schemas_are_rejected() {
+        for (schema, expected) in [
+            ("[[struct]]\nname = \"Bad\"\nfields = []", "has no fields"),
+            ("[[struct]]\nname = \"Bad\"\nfields = [{ name = \"x\", type = \"f16\" }]", "f16"),
+            ("[[struct]]\nname = \"Bad\"\nfields = [{ name = \"x\", type = \"f32\" }, { name = \"x\", type = \"f32\" }]", "more than one field named [x]"),
+            ("[[struct]]\nname = \"Bad Name\"\nfields = [{ name = \"x\", type = \"f32\" }]", "isn't a valid struct name"),
+        ] {
+            let err = crate::struct_codecs::register_schema(schema).unwrap_err();
+            assert!(err.to_string().contains(expected), "{err}");
+        }
+    }
+
+    #[test]
+    fn base64_struct_metadata_is_still_accepted() {
+        let mut reader = Cursor::new(vec![]);
+        let line = format!("!struct {} {}", BASE64_STANDARD.encode([7;25]), BASE64_STANDARD.encode([1,2,3]));
+        let (data, meta) = UObjectPropertyData::from_string(&line, &mut reader, 2).unwrap();
+        assert_eq!(meta, UObjectPropertyMetadata::StructRaw(vec![7;25]));
+        assert_eq!(data, UObjectPropertyData::Struct(vec![], vec![1,2,3]));
+    }
+
+    #[test]
+    fn unsupported_text_history_is_kept_as_raw_data() {
+        let mut raw = vec![0u8;4]; // flags
+        raw.push(11); // string table entry history
+        raw.extend_from_slice(&[1,2,3,4,5,6,7,8,9]);
+        let test = mkobject_with_new_names(vec![
+            mkprop("TestText", UObjectPropertyMetadata::None, UObjectPropertyData::TextRaw(raw.clone())),
+        ]);
+
+        let mut text = vec![];
+        test.to_string(&mut text);
+        let text = String::from_utf8(text).unwrap();
+        assert!(text.contains(&format!("TestText: !rawtext {}\n", BASE64_STANDARD.encode(&raw))), "{text}");
 
         verify_serialize_and_deserialize(test);
     }
 
     #[test]
     fn empty_string() {
-        let test = IoUObject {
-            summary: get_test_object_summary(),
-            properties: vec![mkstr("")]
-        };
+        let test = mkobject(vec![mkstr("")]);
+
+        verify_serialize_and_deserialize(test);
+    }
+
+    /// Mappings for TestClass (TestInt, TestFloat, an unmapped slot, TestNames, TestEnum, TestVector), whose super
+    /// TestBase has BaseInt
+    fn get_test_mappings() -> Mappings {
+        let names = ["TestBase", "BaseInt", "TestClass", "TestInt", "TestFloat", "TestNames", "TestEnum", "EColor", "Red", "Green", "TestVector", "Vector"];
+        let name = |n: &str| (names.iter().position(|x| *x == n).unwrap() as i32).to_le_bytes();
+        let mut data = vec![];
+        data.extend((names.len() as u32).to_le_bytes());
+        for n in names {
+            data.push(n.len() as u8);
+            data.extend(n.as_bytes());
+        }
+
+        data.extend(1u32.to_le_bytes()); // Enums
+        data.extend(name("EColor"));
+        data.push(2);
+        data.extend(name("Red"));
+        data.extend(name("Green"));
+
+        data.extend(2u32.to_le_bytes()); // Structs
+        data.extend(name("TestBase"));
+        data.extend((-1i32).to_le_bytes());
+        data.extend([1, 0, 1, 0]);
+        data.extend([0, 0, 1]);
+        data.extend(name("BaseInt"));
+        data.push(2);
+
+        data.extend(name("TestClass"));
+        data.extend(name("TestBase"));
+        data.extend([6, 0, 5, 0]);
+        for (index, prop_name, r#type) in [
+            (0u8, "TestInt", vec![2]),
+            (1, "TestFloat", vec![3]),
+            (3, "TestNames", vec![8, 5]),
+            (4, "TestEnum", [vec![26, 0], name("EColor").to_vec()].concat()),
+            (5, "TestVector", [vec![9], name("Vector").to_vec()].concat()),
+        ] {
+            data.extend([index, 0, 1]);
+            data.extend(name(prop_name));
+            data.extend(r#type);
+        }
+
+        let mut usmap = vec![0xC4, 0x30, 0, 0];
+        usmap.extend((data.len() as u32).to_le_bytes());
+        usmap.extend((data.len() as u32).to_le_bytes());
+        usmap.extend(data);
+        Mappings::from_buffer(&mut usmap.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn oversized_mapping_counts_are_errors() {
+        for (size, name_count) in [(u32::MAX, 0), (4, u32::MAX)] {
+            let mut usmap = vec![0xC4, 0x30, 0, 0];
+            usmap.extend(size.to_le_bytes());
+            usmap.extend(size.to_le_bytes());
+            usmap.extend(name_count.to_le_bytes());
+            assert!(Mappings::from_buffer(&mut usmap.as_slice()).is_err());
+        }
+    }
+
+    fn mkunversioned(properties: Vec<UObjectProperty>) -> IoUObject {
+        let mut test = mkobject(properties);
+        test.summary.header.package_flags |= 0x2000;
+        test.summary.export_map[0].class_index = PackageObjectIndex(1 << 62 | script_import_hash("/Script/Engine.TestClass"));
+        test.exports[0].class = Some("TestClass".to_string());
+        let mut names = vec![];
+        test.exports[0].collect_names(&mut names);
+        let names: Vec<String> = names.into_iter().map(str::to_owned).collect();
+        test.summary.add_missing_names(names.iter().map(String::as_str));
+        test
+    }
 
+    #[test]
+    fn usmap_mappings() {
+        let mappings = get_test_mappings();
+        assert_eq!(mappings.find_script_struct(script_import_hash("/Script/Engine.TestClass")), Some("TestClass"));
+        assert_eq!(mappings.find_script_struct(script_import_hash("/Script/MyGame.TestClass")), None);
+        assert_eq!(mappings.enum_entry_name("EColor", 1), Some("Green"));
+
+        let schema = mappings.schema("TestClass").unwrap();
+        let names: Vec<Option<&str>> = schema.iter().map(|slot| slot.map(|(prop, _)| prop.name.as_str())).collect();
+        assert_eq!(names, [Some("TestInt"), Some("TestFloat"), None, Some("TestNames"), Some("TestEnum"), Some("TestVector"), Some("BaseInt")]);
+    }
+
+    #[test]
+    fn unversioned_properties() {
+        let mappings = get_test_mappings();
+        let test = mkunversioned(vec![
+            mkprop("TestInt", UObjectPropertyMetadata::None, UObjectPropertyData::Int32(5)),
+            mkprop("TestFloat", UObjectPropertyMetadata::None, UObjectPropertyData::Zero("FloatProperty".to_string())),
+            mkprop("TestNames", UObjectPropertyMetadata::Array("NameProperty".to_string()), UObjectPropertyData::Array(vec![
+                UObjectPropertyData::Name("FirstName".to_string()),
+            ], None)),
+            mkprop("TestEnum", UObjectPropertyMetadata::Enum("EColor".to_string()), UObjectPropertyData::Enum("Green".to_string())),
+            mkprop("BaseInt", UObjectPropertyMetadata::None, UObjectPropertyData::Int32(7)),
+        ]);
+
+        let mut bytes = vec![];
+        test.to_bytes_with_mappings::<_,LE>(&mut bytes, Some(&mappings)).unwrap();
+        // TestInt and TestFloat (zero), skip the unmapped slot, TestNames and TestEnum, then skip TestVector for BaseInt
+        let fragments: [u16; 3] = [2 << 9 | 1 << 7, 2 << 9 | 1, 1 << 9 | 1 << 8 | 1];
+        let mut expected: Vec<u8> = fragments.iter().flat_map(|f| f.to_le_bytes()).collect();
+        expected.push(0b10); // Zero mask - TestFloat
+        expected.extend(5i32.to_le_bytes());
+        expected.extend(1u32.to_le_bytes());
+        expected.extend((test.summary.name_map.iter().position(|n| n == "FirstName").unwrap() as u64).to_le_bytes());
+        expected.push(1);
+        expected.extend(7i32.to_le_bytes());
+        expected.extend([0;4]);
+        assert_eq!(&bytes[bytes.len() - expected.len()..], &expected[..]);
+
+        let deserialized = IoUObject::from_buffer_with_options::<_,LE>(&mut Cursor::new(&bytes), Some(&mappings), None).unwrap();
+        assert_equality(&deserialized, &test);
+
+        let mut text = Cursor::new(vec![]);
+        test.to_string(&mut text);
+        text.set_position(0);
+        let from_text = IoUObject::from_string(&mut text).unwrap();
+        assert_equality(&from_text, &test);
+        let mut reencoded = vec![];
+        from_text.to_bytes_with_mappings::<_,LE>(&mut reencoded, Some(&mappings)).unwrap();
+        assert_eq!(reencoded, bytes);
+    }
+
+    #[test]
+    fn unversioned_class_defaults_and_native_structs() {
+        let mappings = get_test_mappings();
+        let mut test = mkunversioned(vec![
+            mkprop("TestVector", UObjectPropertyMetadata::Struct("Vector".to_string(), [0;16]), UObjectPropertyData::NativeStruct("Vector".to_string(), vec![
+                StructFieldValue::Float(1.0), StructFieldValue::Float(2.0), StructFieldValue::Float(3.0),
+            ])),
+        ]);
+        test.summary.export_map[0].class_index = PackageObjectIndex::NULL;
+        test.summary.export_map[0].object_name = "Default__TestClass".to_string();
+        test.summary.add_missing_names(["Default__TestClass"]);
+
+        let mut bytes = vec![];
+        test.to_bytes_with_mappings::<_,LE>(&mut bytes, Some(&mappings)).unwrap();
+        let deserialized = IoUObject::from_buffer_with_options::<_,LE>(&mut Cursor::new(&bytes), Some(&mappings), None).unwrap();
+        assert_equality(&deserialized, &test);
+    }
+
+    #[test]
+    fn unversioned_exports_without_mappings_are_kept_raw() {
+        let mappings = get_test_mappings();
+        let test = mkunversioned(vec![
+            mkprop("TestInt", UObjectPropertyMetadata::None, UObjectPropertyData::Int32(5)),
+        ]);
+        let mut bytes = vec![];
+        test.to_bytes_with_mappings::<_,LE>(&mut bytes, Some(&mappings)).unwrap();
+
+        let deserialized = IoUObject::from_buffer::<_,LE>(&mut Cursor::new(&bytes)).unwrap();
+        assert_eq!(deserialized.exports[0].class, None);
+        assert!(deserialized.exports[0].properties.is_empty());
+        let mut reencoded = vec![];
+        deserialized.to_bytes::<_,LE>(&mut reencoded).unwrap();
+        assert_eq!(reencoded, bytes);
+    }
+
+    /// Lays the test object out as a UE5 Zen package
+    fn mkzen(properties: Vec<UObjectProperty>, versioning_info: Option<Vec<u8>>) -> IoUObject {
+        let mut test = mkobject(properties);
+        let summary = &mut test.summary;
+        summary.engine_version = EngineVersion::Ue5;
+        summary.zen = Some(ZenSummary {
+            versioning_info: versioning_info.clone(),
+            imported_public_export_hashes_offset: 0,
+            imported_public_export_hashes: vec![0x1122_3344_5566_7788],
+        });
+
+        let name_batch_offset = 44 + versioning_info.map(|v| v.len()).unwrap_or_default();
+        let name_batch_size = summary.name_batch_to_bytes::<LE>().len();
+        let hashes_offset = (name_batch_offset + name_batch_size).next_multiple_of(8);
+        summary.zen.as_mut().unwrap().imported_public_export_hashes_offset = hashes_offset as i32;
+
+        let header = &mut summary.header;
+        header.source_name = 0;
+        header.name_map_names_offset = name_batch_offset as i32;
+        header.name_map_names_size = name_batch_size as i32;
+        header.name_map_hashes_offset = 0;
+        header.name_map_hashes_size = 0;
+        header.import_map_offset = hashes_offset as i32 + 8;
+        header.export_map_offset = header.import_map_offset + 8 * summary.import_map.len() as i32;
+        header.export_bundles_offset = header.export_map_offset + UObjectExportMapEntry::byte_len() as i32 * summary.export_map.len() as i32;
+        header.graph_data_offset = header.export_bundles_offset + 8 * 2;
+        test
+    }
+
+    #[test]
+    fn ue5_zen_summary() {
+        let test = mkzen(vec![mkint(1), mkstr16("ハロー"), mkstr("Zen")], None);
+
+        let mut bytes = vec![];
+        test.to_bytes::<_,LE>(&mut bytes).unwrap();
+        assert_eq!(EngineVersion::detect::<_,LE>(&mut Cursor::new(&bytes)).unwrap(), EngineVersion::Ue5);
+        let header_size = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+        let summary = UObjectSummary::from_buffer::<_,LE>(&mut Cursor::new(&bytes), None).unwrap();
+        assert_eq!(bytes.len() - header_size, summary.export_map[0].cooked_serial_size as usize); // Export data follows the header
+
+        let mut text = vec![];
+        test.to_string(&mut text);
+        assert!(String::from_utf8(text).unwrap().contains("public_export_hash: 0x8000111122223333\n"));
+
+        verify_serialize_and_deserialize(test);
+    }
+
+    #[test]
+    fn ue5_zen_summary_with_versioning_and_new_names() {
+        let mut versioning_info = vec![0;16];
+        versioning_info.extend(1i32.to_le_bytes());
+        versioning_info.extend([0xab;20]);
+        let test = mkzen(vec![mkint(1)], Some(versioning_info));
         verify_serialize_and_deserialize(test);
+
+        let mut test = mkzen(vec![mkint(1), mkprop("NewZenName", UObjectPropertyMetadata::None, UObjectPropertyData::Int32(2))], None);
+        let mut bytes = Cursor::new(vec![]);
+        test.to_bytes::<_,LE>(&mut bytes).unwrap();
+        bytes.set_position(0);
+        let deserialized = IoUObject::from_buffer_with_options::<_,LE>(&mut bytes, None, Some(EngineVersion::Ue5)).unwrap();
+        test.summary.add_missing_names(["NewZenName"]);
+        assert_equality(&deserialized, &test);
+        assert!(deserialized.summary.name_map.contains(&"NewZenName".to_string()));
+    }
+
+    #[test]
+    fn engine_versions_are_parsed_and_detected() {
+        assert_eq!("4.27".parse::<EngineVersion>().unwrap(), EngineVersion::Ue4_27);
+        assert_eq!("5.x".parse::<EngineVersion>().unwrap(), EngineVersion::Ue5);
+        assert_eq!("4.25".parse::<EngineVersion>().unwrap(), EngineVersion::Ue4_25);
+        assert_eq!("4.26".parse::<EngineVersion>().unwrap(), EngineVersion::Ue4_26);
+        assert!("4.20".parse::<EngineVersion>().is_err());
+
+        let mut bytes = vec![];
+        get_test_object().to_bytes::<_,LE>(&mut bytes).unwrap();
+        assert_eq!(EngineVersion::detect::<_,LE>(&mut Cursor::new(&bytes)).unwrap(), EngineVersion::Ue4_27);
+    }
+
+    #[test]
+    fn engine_version_is_kept_in_text() {
+        let mut test = get_test_object();
+        assert!(test.set_engine_version(EngineVersion::Ue5).is_err());
+
+        let mut text = vec![];
+        test.to_string(&mut text);
+        let text = String::from_utf8(text).unwrap();
+        assert!(text.contains("\nengine: 4.27\n"));
+        let deserialized = IoUObject::from_string(&mut Cursor::new(text.as_bytes())).unwrap();
+        assert_equality(&deserialized, &test);
+
+        // Text without an engine version gets the one detected from the summary
+        let text = text.replace("engine: 4.27\n", "");
+        let deserialized = IoUObject::from_string(&mut Cursor::new(text.as_bytes())).unwrap();
+        assert_eq!(deserialized.engine_version(), EngineVersion::Ue4_27);
+
+        let mut bytes = Cursor::new(vec![]);
+        test.to_bytes::<_,LE>(&mut bytes).unwrap();
+        bytes.set_position(0);
+        assert!(IoUObject::from_buffer_with_options::<_,LE>(&mut bytes, None, Some(EngineVersion::Ue5)).is_err());
+    }
+
+    #[test]
+    fn iostore_packages_reject_global_name_map_versions() {
+        let mut test = get_test_object();
+        assert!(test.set_engine_version(EngineVersion::Ue4_26).is_err());
+
+        let mut bytes = vec![];
+        test.to_bytes::<_,LE>(&mut bytes).unwrap();
+        let err = IoUObject::from_buffer_with_options::<_,LE>(&mut Cursor::new(&bytes), None, Some(EngineVersion::Ue4_25)).err().unwrap();
+        assert!(err.to_string().contains("global name map"));
+
+        let mut text = vec![];
+        test.to_string(&mut text);
+        let text = String::from_utf8(text).unwrap().replace("engine: 4.27\n", "engine: 4.26\n");
+        let errors = IoUObject::from_string(&mut Cursor::new(text.as_bytes())).err().unwrap();
+        assert_eq!(errors.0[0].line, 2);
     }
 }
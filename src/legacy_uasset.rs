@@ -0,0 +1,1252 @@
+use base64::{prelude::BASE64_STANDARD, Engine};
+use byteorder::{ByteOrder, ReadBytesExt, WriteBytesExt};
+use crate::error::{EncodeError, ParseError, ParseRead, TextError, TextErrors, TextRead};
+use crate::iostore_uasset::{EngineVersion, IoUObject, PackageObjectIndex, StringType, UObjectExport, UObjectExportMapEntry, UObjectSummary};
+use crate::usmap::Mappings;
+use std::{error::Error, io::{self, BufRead, Cursor, Read, Seek, SeekFrom, Write}};
+
+/// PACKAGE_FILE_TAG - starts every legacy package summary, and ends every .uexp
+pub const PACKAGE_FILE_TAG: u32 = 0x9E2A83C1;
+
+/// PKG_FilterEditorOnly - cooked packages leave the editor-only summary fields out
+const PKG_FILTER_EDITOR_ONLY: u32 = 0x80000000;
+
+// FileVersionUE4 values that changed the package header layout
+const VER_UE4_WORLD_LEVEL_INFO: i32 = 224;
+const VER_UE4_ADDED_CHUNKID_TO_ASSETDATA_AND_UPACKAGE: i32 = 278;
+const VER_UE4_CHANGED_CHUNKID_TO_BE_AN_ARRAY_OF_CHUNKIDS: i32 = 326;
+const VER_UE4_ENGINE_VERSION_OBJECT: i32 = 336;
+const VER_UE4_LOAD_FOR_EDITOR_GAME: i32 = 365;
+const VER_UE4_ADD_STRING_ASSET_REFERENCES_MAP: i32 = 384;
+const VER_UE4_PACKAGE_SUMMARY_HAS_COMPATIBLE_ENGINE_VERSION: i32 = 444;
+const VER_UE4_SERIALIZE_TEXT_IN_PACKAGES: i32 = 459;
+const VER_UE4_COOKED_ASSETS_IN_EDITOR_SUPPORT: i32 = 485;
+const VER_UE4_NAME_HASHES_SERIALIZED: i32 = 504;
+const VER_UE4_PRELOAD_DEPENDENCIES_IN_COOKED_EXPORTS: i32 = 507;
+const VER_UE4_TEMPLATE_INDEX_IN_COOKED_EXPORTS: i32 = 508;
+const VER_UE4_ADDED_SEARCHABLE_NAMES: i32 = 510;
+const VER_UE4_64BIT_EXPORTMAP_SERIALSIZES: i32 = 511;
+const VER_UE4_ADDED_PACKAGE_SUMMARY_LOCALIZATION_ID: i32 = 516;
+const VER_UE4_ADDED_PACKAGE_OWNER: i32 = 518;
+const VER_UE4_NON_OUTER_PACKAGE_IMPORT: i32 = 520;
+
+/// The FileVersionUE4 each engine version saves with, used for packages cooked without one
+fn file_version_ue4(engine_version: EngineVersion) -> i32 {
+    match engine_version {
+        EngineVersion::Ue4_25 => 518,
+        EngineVersion::Ue4_26 => 519,
+        EngineVersion::Ue4_27 | EngineVersion::Ue5 => 522,
+    }
+}
+
+fn engine_version_from_file_version(file_version: i32) -> EngineVersion {
+    match file_version {
+        ..=518 => EngineVersion::Ue4_25,
+        519..=521 => EngineVersion::Ue4_26,
+        _ => EngineVersion::Ue4_27,
+    }
+}
+
+/// Whether a package starts with a legacy FPackageFileSummary rather than an IoStore summary
+pub fn is_legacy_package<R: Read + Seek, E: ByteOrder>(reader: &mut R) -> bool {
+    let start_position = reader.stream_position().unwrap();
+    let tag = reader.read_u32::<E>();
+    reader.seek(SeekFrom::Start(start_position)).unwrap();
+    matches!(tag, Ok(PACKAGE_FILE_TAG))
+}
+
+/// Reads an FString - a positive length means a null-terminated Latin-1 string, and a negative one UTF-16
+pub(crate) fn read_fstring<R: Read + Seek, E: ByteOrder>(reader: &mut R) -> Result<(String, StringType), ParseError> {
+    let offset = reader.offset();
+    let len = reader.parse_i32::<E>()?;
+    if len >= 0 {
+        let bytes = reader.parse_vec(len as usize)?;
+        Ok((bytes.iter().take_while(|b| **b != 0).map(|b| *b as char).collect(), StringType::Utf8))
+    } else {
+        let bytes = reader.parse_vec(len.unsigned_abs() as usize * 2)?;
+        let chars: Vec<u16> = bytes.chunks(2).map(E::read_u16).collect();
+        let end = chars.iter().position(|c| *c == 0).unwrap_or(chars.len());
+        let string = String::from_utf16(&chars[..end]).map_err(|_| ParseError::MalformedFString { offset, path: String::new() })?;
+        Ok((string, StringType::Utf16))
+    }
+}
+
+/// Writes an FString the way the engine does - a one-byte string if it's ASCII, or UTF-16
+pub(crate) fn write_fstring<W: Write, E: ByteOrder>(writer: &mut W, value: &str) -> io::Result<()> {
+    write_fstring_as::<_,E>(writer, value, &StringType::for_string(value))
+}
+
+/// Writes an FString as the type [`read_fstring`] returned for it, so one-byte Latin-1 strings are written back
+/// byte for byte.  Strings with characters past Latin-1 can only be written as UTF-16.
+pub(crate) fn write_fstring_as<W: Write, E: ByteOrder>(writer: &mut W, value: &str, string_type: &StringType) -> io::Result<()> {
+    if is_one_byte(value, string_type) {
+        writer.write_i32::<E>(value.chars().count() as i32 + 1)?;
+        for c in value.chars() {
+            writer.write_u8(c as u8)?;
+        }
+        writer.write_u8(0)
+    } else {
+        let chars: Vec<u16> = value.encode_utf16().collect();
+        writer.write_i32::<E>(-(chars.len() as i32 + 1))?;
+        for c in chars {
+            writer.write_u16::<E>(c)?;
+        }
+        writer.write_u16::<E>(0)
+    }
+}
+
+/// Whether [`write_fstring_as`] stores a string with one byte per character
+fn is_one_byte(value: &str, string_type: &StringType) -> bool {
+    matches!(string_type, StringType::Utf8) && value.chars().all(|c| (c as u32) <= 0xFF)
+}
+
+/// Reads an FName - a name map index and number
+fn read_fname<R: Read + Seek, E: ByteOrder>(reader: &mut R, names: &[String]) -> Result<(String, u32), ParseError> {
+    let offset = reader.offset();
+    let index = reader.parse_i32::<E>()?;
+    let number = reader.parse_u32::<E>()?;
+    let name = usize::try_from(index).ok().and_then(|i| names.get(i))
+        .ok_or(ParseError::BadNameIndex { index: index as u32 as u64, offset, path: String::new() })?;
+    Ok((name.clone(), number))
+}
+
+/// Reads an array count, which can't be negative.  Counts come from the file, so arrays are only preallocated
+/// as far as the remaining data could fill them.
+fn read_count<E: ByteOrder>(reader: &mut Cursor<&[u8]>) -> Result<usize, ParseError> {
+    let offset = reader.offset();
+    let count = reader.parse_i32::<E>()?;
+    usize::try_from(count).map_err(|_| ParseError::invalid(offset, format!("Array count {count} is negative")))
+}
+
+fn preallocated<T>(count: usize, reader: &Cursor<&[u8]>) -> Vec<T> {
+    Vec::with_capacity(count.min(reader.get_ref().len().saturating_sub(reader.position() as usize)))
+}
+
+fn write_fname<W: Write, E: ByteOrder>(writer: &mut W, names: &[String], name: &str, number: u32) -> Result<(), EncodeError> {
+    let index = names.iter().position(|n| n == name).ok_or_else(|| EncodeError::MissingName { name: name.to_owned(), path: String::new() })?;
+    writer.write_i32::<E>(index as i32)?;
+    writer.write_u32::<E>(number)?;
+    Ok(())
+}
+
+/// A name map entry - the name, followed by its hashes in newer packages
+fn write_name_entry<W: Write, E: ByteOrder>(writer: &mut W, name: &str, string_type: &StringType, file_version: i32) -> io::Result<()> {
+    write_fstring_as::<_,E>(writer, name, string_type)?;
+    if file_version >= VER_UE4_NAME_HASHES_SERIALIZED {
+        writer.write_u16::<E>(name_hash_non_case_preserving(name, string_type))?;
+        writer.write_u16::<E>(name_hash_case_preserving(name))?;
+    }
+    Ok(())
+}
+
+/// The IoStore index of an FPackageIndex - an import's global index, a local export index, or null
+fn package_object_index(index: i32, import_map: &[PackageObjectIndex]) -> PackageObjectIndex {
+    match index {
+        0 => PackageObjectIndex::NULL,
+        index if index < 0 => import_map.get((-index - 1) as usize).copied().unwrap_or(PackageObjectIndex::NULL),
+        index => PackageObjectIndex::export(index as usize - 1),
+    }
+}
+
+/// FCrc::Strihash_DEPRECATED - the case insensitive name hash stored in the name map, which runs an MSB-first
+/// CRC table through a reflected update.  Each character is upper cased (ASCII only, like TChar::ToUpper) and
+/// hashed as one byte in one-byte strings, or as its two UTF-16 bytes.
+fn name_hash_non_case_preserving(name: &str, string_type: &StringType) -> u16 {
+    let mut table = [0u32;256];
+    for (i, entry) in table.iter_mut().enumerate() {
+        let mut crc = (i as u32) << 24;
+        for _ in 0..8 {
+            crc = if crc & 0x80000000 != 0 { (crc << 1) ^ 0x04C11DB7 } else { crc << 1 };
+        }
+        *entry = crc;
+    }
+
+    let char_len = if is_one_byte(name, string_type) { 1 } else { 2 };
+    let mut hash = 0u32;
+    for c in name.encode_utf16() {
+        let c = if (b'a' as u16..=b'z' as u16).contains(&c) { c - 32 } else { c };
+        for b in &c.to_le_bytes()[..char_len] {
+            hash = ((hash >> 8) & 0x00FFFFFF) ^ table[((hash ^ *b as u32) & 0xFF) as usize];
+        }
+    }
+    hash as u16
+}
+
+/// FCrc::StrCrc32 - a standard CRC32 over every character (or UTF-16 code unit) widened to 4 bytes
+fn name_hash_case_preserving(name: &str) -> u16 {
+    let mut crc = !0u32;
+    for b in name.encode_utf16().flat_map(|c| u32::from(c).to_le_bytes()) {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+        }
+    }
+    !crc as u16
+}
+
+/// RF_Public - exports other packages can import
+const RF_PUBLIC: u32 = 0x1;
+
+/// FObjectImport
+#[derive(Clone)]
+struct LegacyImport {
+    class_package: String,
+    class_name: String,
+    outer_index: i32,
+    object_name: String,
+}
+
+/// FObjectExport, without the fields cooked packages leave empty
+#[derive(Clone)]
+struct LegacyExport {
+    class_index: i32,
+    super_index: i32,
+    template_index: i32,
+    outer_index: i32,
+    object_name: String,
+    object_name_number: u32,
+    object_flags: u32,
+    serial_size: u64,
+    serial_offset: u64,
+    serial_position: usize, // Where the serial size and offset are stored in the header
+    not_for_client: bool,
+    not_for_server: bool,
+}
+
+/// A legacy FPackageFileSummary, along with the rest of the .uasset (name, import and export maps, etc.).  The
+/// header is kept as it was read, with the positions of every value that adding names or resizing exports changes.
+#[derive(Clone)]
+struct LegacyPackageSummary {
+    bytes: Vec<u8>,
+    file_version: i32, // FileVersionUE4, or the engine version's if the package was cooked unversioned
+    package_flags: u32,
+    total_header_size: i32,
+    total_header_size_position: usize,
+    name_count_position: usize,
+    name_map_end: usize,
+    generation_name_count_positions: Vec<usize>,
+    offset_positions: Vec<usize>, // Offsets of the header sections that follow the summary
+    bulk_data_start_offset_position: usize,
+    names: Vec<String>,
+    name_types: Vec<StringType>,
+    imports: Vec<LegacyImport>,
+    exports: Vec<LegacyExport>,
+}
+
+impl LegacyPackageSummary {
+    fn from_bytes<E: ByteOrder>(bytes: Vec<u8>, engine_version: EngineVersion) -> Result<Self, Box<dyn Error>> {
+        Self::parse::<E>(bytes, engine_version).map_err(|err| format!("Unable to read legacy package summary: {err}").into())
+    }
+
+    /// Writes the header of a cooked, unversioned package holding the given maps, then reads it back.  Export
+    /// serial sizes and offsets are left for [`Self::to_bytes`] to fill in.
+    fn new<E: ByteOrder>(engine_version: EngineVersion, package_flags: u32, names: &[String], imports: &[LegacyImport], exports: &[LegacyExport]) -> Result<Self, Box<dyn Error>> {
+        let package_flags = package_flags | PKG_FILTER_EDITOR_ONLY;
+        let file_version = file_version_ue4(engine_version);
+
+        let mut name_map = vec![];
+        for name in names {
+            write_name_entry::<_,E>(&mut name_map, name, &StringType::for_string(name), file_version)?;
+        }
+        let mut import_map = vec![];
+        for (i, import) in imports.iter().enumerate() {
+            let in_import = |field: &'static str| move |err: EncodeError| err.in_property(&format!("imports[{i}].{field}"));
+            write_fname::<_,E>(&mut import_map, names, &import.class_package, 0).map_err(in_import("class_package"))?;
+            write_fname::<_,E>(&mut import_map, names, &import.class_name, 0).map_err(in_import("class_name"))?;
+            import_map.write_i32::<E>(import.outer_index)?;
+            write_fname::<_,E>(&mut import_map, names, &import.object_name, 0).map_err(in_import("object_name"))?;
+        }
+        let mut export_map = vec![];
+        for (i, export) in exports.iter().enumerate() {
+            for index in [export.class_index, export.super_index, export.template_index, export.outer_index] {
+                export_map.write_i32::<E>(index)?;
+            }
+            write_fname::<_,E>(&mut export_map, names, &export.object_name, export.object_name_number)
+                .map_err(|err| err.in_property(&format!("exports[{i}].object_name")))?;
+            export_map.write_u32::<E>(export.object_flags)?;
+            export_map.write_i64::<E>(0)?; // Serial size
+            export_map.write_i64::<E>(0)?; // Serial offset
+            for flag in [false, export.not_for_client, export.not_for_server] { // Forced export, not for client/server
+                export_map.write_u32::<E>(flag as u32)?;
+            }
+            export_map.write_all(&[0;16 + 4 + 4])?; // Package guid and flags, not always loaded for editor game
+            export_map.write_u32::<E>((export.outer_index == 0 && export.object_flags & RF_PUBLIC != 0) as u32)?; // Is asset
+            for index in [-1, 0, 0, 0, 0] { // No preload dependencies
+                export_map.write_i32::<E>(index)?;
+            }
+        }
+        let depends_map = vec![0;exports.len() * 4]; // An empty array per export
+        let asset_registry_data = [0;4]; // No asset registry objects
+
+        let summary = |total_header_size: i32, name_offset: i32, import_offset: i32, export_offset: i32, depends_offset: i32| {
+            let asset_registry_offset = depends_offset + depends_map.len() as i32;
+            let mut summary = vec![];
+            summary.write_u32::<E>(PACKAGE_FILE_TAG)?;
+            for version in [-7, 864, 0, 0, 0] { // Legacy and UE3 versions, then unversioned UE4, licensee and custom versions
+                summary.write_i32::<E>(version)?;
+            }
+            summary.write_i32::<E>(total_header_size)?;
+            write_fstring::<_,E>(&mut summary, "None")?; // Folder name
+            summary.write_u32::<E>(package_flags)?;
+            for value in [names.len() as i32, name_offset, 0, 0, exports.len() as i32, export_offset, imports.len() as i32, import_offset, depends_offset, 0, 0, 0, 0] {
+                summary.write_i32::<E>(value)?; // Name, gatherable text, export, import, depends, soft package references, searchable names and thumbnail maps
+            }
+            summary.write_all(&[0;16])?; // Guid
+            for value in [1, exports.len() as i32, names.len() as i32] { // One generation
+                summary.write_i32::<E>(value)?;
+            }
+            for _ in 0..2 { // Saved by and compatible with engine versions
+                summary.write_all(&[0;2 + 2 + 2 + 4])?;
+                write_fstring::<_,E>(&mut summary, "")?;
+            }
+            for value in [0, 0, 0, 0, asset_registry_offset] { // Compression flags and chunks, package source, packages to cook
+                summary.write_i32::<E>(value)?;
+            }
+            summary.write_i64::<E>(total_header_size as i64 + LegacyUObject::DEFAULT_UEXP_TAIL.len() as i64)?; // Bulk data start
+            for value in [0, 0, 0, total_header_size] { // World tile info, chunk ids, preload dependencies
+                summary.write_i32::<E>(value)?;
+            }
+            Ok::<_, io::Error>(summary)
+        };
+
+        let name_offset = summary(0, 0, 0, 0, 0)?.len() as i32;
+        let import_offset = name_offset + name_map.len() as i32;
+        let export_offset = import_offset + import_map.len() as i32;
+        let depends_offset = export_offset + export_map.len() as i32;
+        let total_header_size = depends_offset + (depends_map.len() + asset_registry_data.len()) as i32;
+
+        let mut bytes = summary(total_header_size, name_offset, import_offset, export_offset, depends_offset)?;
+        for section in [name_map, import_map, export_map, depends_map, asset_registry_data.to_vec()] {
+            bytes.extend(section);
+        }
+        Self::from_bytes::<E>(bytes, engine_version)
+    }
+
+    fn parse<E: ByteOrder>(bytes: Vec<u8>, engine_version: EngineVersion) -> Result<Self, ParseError> {
+        let mut reader = Cursor::new(bytes.as_slice());
+        let tag = reader.parse_u32::<E>()?;
+        if tag != PACKAGE_FILE_TAG {
+            Err(ParseError::invalid(0, format!("expected the package tag 0x{PACKAGE_FILE_TAG:x}, but got 0x{tag:x}")))?;
+        }
+        let legacy_file_version = reader.parse_i32::<E>()?;
+        match legacy_file_version {
+            -7 | -6 => {},
+            -8 => Err(ParseError::invalid(4, "UE5 legacy packages aren't supported"))?,
+            other => Err(ParseError::invalid(4, format!("unsupported legacy file version {other} - only UE4 packages (-6 and -7) can be read")))?,
+        }
+        let _legacy_ue3_version = reader.parse_i32::<E>()?;
+        let saved_file_version = reader.parse_i32::<E>()?;
+        let _licensee_version = reader.parse_i32::<E>()?;
+        let custom_version_count = read_count::<E>(&mut reader)?;
+        reader.parse_seek(SeekFrom::Current(custom_version_count as i64 * 20))?; // Guid, version
+        let file_version = match saved_file_version {
+            0 => file_version_ue4(engine_version), // Cooked unversioned
+            version => version,
+        };
+
+        let total_header_size_position = reader.position() as usize;
+        let total_header_size = reader.parse_i32::<E>()?;
+        read_fstring::<_,E>(&mut reader)?; // Folder name
+        let package_flags = reader.parse_u32::<E>()?;
+        let filter_editor_only = package_flags & PKG_FILTER_EDITOR_ONLY != 0;
+        let name_count_position = reader.position() as usize;
+        let name_count = read_count::<E>(&mut reader)?;
+        let name_offset = reader.parse_i32::<E>()?;
+
+        let mut offset_positions = vec![];
+        let mut read_offset = |reader: &mut Cursor<&[u8]>| -> Result<i32, ParseError> {
+            offset_positions.push(reader.position() as usize);
+            reader.parse_i32::<E>()
+        };
+        if !filter_editor_only && file_version >= VER_UE4_ADDED_PACKAGE_SUMMARY_LOCALIZATION_ID {
+            read_fstring::<_,E>(&mut reader)?;
+        }
+        if file_version >= VER_UE4_SERIALIZE_TEXT_IN_PACKAGES {
+            let _gatherable_text_data_count = reader.parse_i32::<E>()?;
+            read_offset(&mut reader)?;
+        }
+        let export_count = read_count::<E>(&mut reader)?;
+        let export_offset = read_offset(&mut reader)?;
+        let import_count = read_count::<E>(&mut reader)?;
+        let import_offset = read_offset(&mut reader)?;
+        read_offset(&mut reader)?; // Depends
+        if file_version >= VER_UE4_ADD_STRING_ASSET_REFERENCES_MAP {
+            let _soft_package_references_count = reader.parse_i32::<E>()?;
+            read_offset(&mut reader)?;
+        }
+        if file_version >= VER_UE4_ADDED_SEARCHABLE_NAMES {
+            read_offset(&mut reader)?;
+        }
+        read_offset(&mut reader)?; // Thumbnail table
+        reader.parse_seek(SeekFrom::Current(16))?; // Guid
+        if !filter_editor_only && file_version >= VER_UE4_ADDED_PACKAGE_OWNER {
+            let guid_count = if file_version < VER_UE4_NON_OUTER_PACKAGE_IMPORT { 2 } else { 1 }; // Persistent and owner persistent guids
+            reader.parse_seek(SeekFrom::Current(16 * guid_count))?;
+        }
+
+        let generation_count = read_count::<E>(&mut reader)?;
+        let mut generation_name_count_positions = vec![];
+        for _ in 0..generation_count {
+            let _export_count = reader.parse_i32::<E>()?;
+            generation_name_count_positions.push(reader.position() as usize);
+            reader.parse_i32::<E>()?;
+        }
+
+        let engine_version_count = if file_version >= VER_UE4_PACKAGE_SUMMARY_HAS_COMPATIBLE_ENGINE_VERSION { 2 } else { 1 }; // Saved by and compatible with
+        for _ in 0..engine_version_count {
+            if file_version >= VER_UE4_ENGINE_VERSION_OBJECT {
+                reader.parse_seek(SeekFrom::Current(2 + 2 + 2 + 4))?; // Major, minor, patch, changelist
+                read_fstring::<_,E>(&mut reader)?; // Branch
+            } else {
+                reader.parse_i32::<E>()?; // Changelist
+            }
+        }
+
+        let _compression_flags = reader.parse_u32::<E>()?;
+        let compressed_chunks_offset = reader.offset();
+        if reader.parse_i32::<E>()? != 0 {
+            Err(ParseError::invalid(compressed_chunks_offset, "compressed packages aren't supported"))?;
+        }
+        let _package_source = reader.parse_u32::<E>()?;
+        let additional_packages_to_cook = read_count::<E>(&mut reader)?;
+        for _ in 0..additional_packages_to_cook {
+            read_fstring::<_,E>(&mut reader)?;
+        }
+        if legacy_file_version > -7 {
+            let _num_texture_allocations = reader.parse_i32::<E>()?;
+        }
+        read_offset(&mut reader)?; // Asset registry data
+        let bulk_data_start_offset_position = reader.position() as usize;
+        reader.parse_i64::<E>()?;
+        if file_version >= VER_UE4_WORLD_LEVEL_INFO {
+            read_offset(&mut reader)?;
+        }
+        if file_version >= VER_UE4_CHANGED_CHUNKID_TO_BE_AN_ARRAY_OF_CHUNKIDS {
+            let chunk_id_count = read_count::<E>(&mut reader)?;
+            reader.parse_seek(SeekFrom::Current(chunk_id_count as i64 * 4))?;
+        } else if file_version >= VER_UE4_ADDED_CHUNKID_TO_ASSETDATA_AND_UPACKAGE {
+            reader.parse_i32::<E>()?;
+        }
+        if file_version >= VER_UE4_PRELOAD_DEPENDENCIES_IN_COOKED_EXPORTS {
+            let _preload_dependency_count = reader.parse_i32::<E>()?;
+            read_offset(&mut reader)?;
+        }
+
+        reader.parse_seek(SeekFrom::Start(name_offset as u64))?;
+        let mut names = preallocated(name_count, &reader);
+        let mut name_types = preallocated(name_count, &reader);
+        for _ in 0..name_count {
+            let (name, string_type) = read_fstring::<_,E>(&mut reader)?;
+            if file_version >= VER_UE4_NAME_HASHES_SERIALIZED {
+                let _non_case_preserving_hash = reader.parse_u16::<E>()?;
+                let _case_preserving_hash = reader.parse_u16::<E>()?;
+            }
+            names.push(name);
+            name_types.push(string_type);
+        }
+        let name_map_end = reader.position() as usize;
+
+        reader.parse_seek(SeekFrom::Start(import_offset as u64))?;
+        let mut imports = preallocated(import_count, &reader);
+        for _ in 0..import_count {
+            let (class_package, _) = read_fname::<_,E>(&mut reader, &names)?;
+            let (class_name, _) = read_fname::<_,E>(&mut reader, &names)?;
+            let outer_index = reader.parse_i32::<E>()?;
+            let (object_name, _) = read_fname::<_,E>(&mut reader, &names)?;
+            if !filter_editor_only && file_version >= VER_UE4_NON_OUTER_PACKAGE_IMPORT {
+                read_fname::<_,E>(&mut reader, &names)?; // Package name
+            }
+            imports.push(LegacyImport { class_package, class_name, outer_index, object_name });
+        }
+
+        reader.parse_seek(SeekFrom::Start(export_offset as u64))?;
+        let mut exports = preallocated(export_count, &reader);
+        for _ in 0..export_count {
+            let class_index = reader.parse_i32::<E>()?;
+            let super_index = reader.parse_i32::<E>()?;
+            let template_index = match file_version >= VER_UE4_TEMPLATE_INDEX_IN_COOKED_EXPORTS {
+                true => reader.parse_i32::<E>()?,
+                false => 0,
+            };
+            let outer_index = reader.parse_i32::<E>()?;
+            let (object_name, object_name_number) = read_fname::<_,E>(&mut reader, &names)?;
+            let object_flags = reader.parse_u32::<E>()?;
+            let serial_position = reader.position() as usize;
+            let (serial_size, serial_offset) = if file_version >= VER_UE4_64BIT_EXPORTMAP_SERIALSIZES {
+                (reader.parse_i64::<E>()?, reader.parse_i64::<E>()?)
+            } else {
+                (reader.parse_i32::<E>()? as i64, reader.parse_i32::<E>()? as i64)
+            };
+            let _forced_export = reader.parse_u32::<E>()?;
+            let not_for_client = reader.parse_u32::<E>()? != 0;
+            let not_for_server = reader.parse_u32::<E>()? != 0;
+            reader.parse_seek(SeekFrom::Current(16 + 4))?; // Package guid and flags
+            if file_version >= VER_UE4_LOAD_FOR_EDITOR_GAME {
+                reader.parse_i32::<E>()?;
+            }
+            if file_version >= VER_UE4_COOKED_ASSETS_IN_EDITOR_SUPPORT {
+                reader.parse_i32::<E>()?;
+            }
+            if file_version >= VER_UE4_PRELOAD_DEPENDENCIES_IN_COOKED_EXPORTS {
+                reader.parse_seek(SeekFrom::Current(5 * 4))?;
+            }
+            exports.push(LegacyExport {
+                class_index,
+                super_index,
+                template_index,
+                outer_index,
+                object_name,
+                object_name_number,
+                object_flags,
+                serial_size: serial_size as u64,
+                serial_offset: serial_offset as u64,
+                serial_position,
+                not_for_client,
+                not_for_server,
+            });
+        }
+
+        if bytes.len() != total_header_size as usize {
+            Err(ParseError::invalid(total_header_size_position as u64, format!("the .uasset is {} bytes, but its header is {total_header_size} - only packages with exports in a .uexp are supported", bytes.len())))?;
+        }
+
+        Ok(Self {
+            bytes,
+            file_version,
+            package_flags,
+            total_header_size,
+            total_header_size_position,
+            name_count_position,
+            name_map_end,
+            generation_name_count_positions,
+            offset_positions,
+            bulk_data_start_offset_position,
+            names,
+            name_types,
+            imports,
+            exports,
+        })
+    }
+
+    /// The path of an import, following its outers
+    fn import_path(&self, import_index: usize) -> String {
+        let mut import = &self.imports[import_index];
+        let mut path = import.object_name.clone();
+        for _ in 0..self.imports.len() { // Bounded, in case outers form a cycle
+            let Some(outer) = usize::try_from(-(import.outer_index as i64) - 1).ok().and_then(|i| self.imports.get(i)) else {
+                break;
+            };
+            let separator = if outer.outer_index == 0 { '.' } else { ':' };
+            path = format!("{}{separator}{path}", outer.object_name);
+            import = outer;
+        }
+        path
+    }
+
+    /// The path of an export within the package, following its outers
+    fn export_path(&self, export_index: usize) -> String {
+        let mut export = &self.exports[export_index];
+        let mut path = export.object_name.clone();
+        for _ in 0..self.exports.len() { // Bounded, in case outers form a cycle
+            let Some(outer) = usize::try_from(export.outer_index - 1).ok().and_then(|i| self.exports.get(i)) else {
+                break;
+            };
+            path = format!("{}:{path}", outer.object_name);
+            export = outer;
+        }
+        path
+    }
+
+    /// Builds the summary that export properties are decoded against, giving imports the global index IoStore would
+    fn property_context(&self, engine_version: EngineVersion) -> UObjectSummary {
+        let import_map = (0..self.imports.len()).map(|i| match self.imports[i].outer_index {
+            0 => PackageObjectIndex::NULL, // Packages themselves
+            _ => PackageObjectIndex::from_import_path(&self.import_path(i)),
+        }).collect::<Vec<_>>();
+        let import_paths = (0..self.imports.len()).map(|i| (self.imports[i].outer_index != 0).then(|| self.import_path(i))).collect();
+        let exports = self.exports.iter().map(|e| (e.object_name.clone(), package_object_index(e.class_index, &import_map))).collect();
+
+        let mut context = UObjectSummary::for_legacy_package(engine_version, self.package_flags, self.names.clone(), self.name_types.clone(), import_map, exports);
+        context.set_import_paths(import_paths);
+        context
+    }
+
+    /// Writes the header with new names added to the end of the name map, and the exports laid out one after
+    /// another in the given order, with the given sizes
+    fn to_bytes<E: ByteOrder>(&self, new_names: &[String], export_sizes: &[(usize, u64)]) -> io::Result<Vec<u8>> {
+        let mut name_entries = vec![];
+        for name in new_names {
+            write_name_entry::<_,E>(&mut name_entries, name, &StringType::for_string(name), self.file_version)?;
+        }
+        let delta = name_entries.len();
+        let shifted = |position: usize| if position >= self.name_map_end { position + delta } else { position };
+
+        let mut bytes = self.bytes.clone();
+        bytes.splice(self.name_map_end..self.name_map_end, name_entries);
+
+        let total_header_size = self.total_header_size + delta as i32;
+        E::write_i32(&mut bytes[self.total_header_size_position..], total_header_size);
+        let name_count = self.names.len() as i32;
+        E::write_i32(&mut bytes[self.name_count_position..], name_count + new_names.len() as i32);
+        for position in &self.generation_name_count_positions {
+            if E::read_i32(&bytes[*position..]) == name_count {
+                E::write_i32(&mut bytes[*position..], name_count + new_names.len() as i32);
+            }
+        }
+        for position in &self.offset_positions {
+            let offset = E::read_i32(&bytes[*position..]);
+            if offset >= self.name_map_end as i32 {
+                E::write_i32(&mut bytes[*position..], offset + delta as i32);
+            }
+        }
+
+        let mut serial_offset = total_header_size as u64;
+        for (export_index, serial_size) in export_sizes {
+            let position = shifted(self.exports[*export_index].serial_position);
+            if self.file_version >= VER_UE4_64BIT_EXPORTMAP_SERIALSIZES {
+                E::write_i64(&mut bytes[position..], *serial_size as i64);
+                E::write_i64(&mut bytes[position + 8..], serial_offset as i64);
+            } else {
+                E::write_i32(&mut bytes[position..], *serial_size as i32);
+                E::write_i32(&mut bytes[position + 4..], serial_offset as i32);
+            }
+            serial_offset += serial_size;
+        }
+
+        // Bulk data is stored after the exports, so it moves with both the header and export sizes
+        let old_exports_size: u64 = self.exports.iter().map(|e| e.serial_size).sum();
+        let new_exports_size: u64 = export_sizes.iter().map(|(_, size)| size).sum();
+        let bulk_data_start_offset = E::read_i64(&bytes[self.bulk_data_start_offset_position..]);
+        if bulk_data_start_offset >= self.total_header_size as i64 {
+            let moved = bulk_data_start_offset + delta as i64 + new_exports_size as i64 - old_exports_size as i64;
+            E::write_i64(&mut bytes[self.bulk_data_start_offset_position..], moved);
+        }
+
+        Ok(bytes)
+    }
+}
+
+/// A legacy package, split into a .uasset header and a .uexp holding the exports
+pub struct LegacyUObject {
+    summary: LegacyPackageSummary,
+    context: UObjectSummary, // What export properties are decoded and encoded against
+    exports: Vec<UObjectExport>,
+    uexp_tail: Vec<u8>,
+}
+
+impl LegacyUObject {
+    /// What follows the exports in a .uexp - the package tag
+    const DEFAULT_UEXP_TAIL: [u8;4] = PACKAGE_FILE_TAG.to_le_bytes();
+
+    pub fn from_buffers<R: Read + Seek, U: Read + Seek, E: ByteOrder>(uasset: &mut R, uexp: &mut U) -> Result<Self, Box<dyn Error>> {
+        Self::from_buffers_with_options::<R,U,E>(uasset, uexp, None, None)
+    }
+
+    /// Like [`Self::from_buffers`], with the mappings needed to decode unversioned properties, and the engine
+    /// version to read the package as.  If it's None, it's found from the package's file version.
+    pub fn from_buffers_with_options<R: Read + Seek, U: Read + Seek, E: ByteOrder>(uasset: &mut R, uexp: &mut U, mappings: Option<&Mappings>, engine_version: Option<EngineVersion>) -> Result<Self, Box<dyn Error>> {
+        let mut header = vec![];
+        uasset.read_to_end(&mut header)?;
+        let engine_version = match engine_version {
+            Some(engine_version) if engine_version.is_zen() => Err(format!("Legacy packages can't be read as {engine_version} packages - only UE4 versions are supported"))?,
+            Some(engine_version) => engine_version,
+            None => Self::detect_engine_version::<E>(&header),
+        };
+        let summary = LegacyPackageSummary::from_bytes::<E>(header, engine_version)?;
+        let context = summary.property_context(engine_version);
+
+        let mut export_order: Vec<usize> = (0..summary.exports.len()).collect();
+        export_order.sort_by_key(|i| summary.exports[*i].serial_offset);
+        let mut expected_offset = summary.total_header_size as u64;
+        let mut exports = vec![];
+        for export_index in export_order {
+            let export = &summary.exports[export_index];
+            if export.serial_offset != expected_offset {
+                Err(format!("Export {export_index} starts at 0x{:x}, but the previous export ends at 0x{expected_offset:x} - exports must be stored one after another", export.serial_offset))?;
+            }
+            exports.push(UObjectExport::from_buffer::<U,E>(uexp, export_index, export.serial_size as usize, &context, mappings)?);
+            expected_offset += export.serial_size;
+        }
+
+        let mut uexp_tail = vec![];
+        uexp.read_to_end(&mut uexp_tail)?;
+
+        Ok(Self {
+            summary,
+            context,
+            exports,
+            uexp_tail,
+        })
+    }
+
+    /// Rebuilds the package as a UE4 IoStore package, named after its path in the game's content (e.g.
+    /// /Game/Data/MyTable).  Imports get the global indices IoStore gives them, and the exports are unchanged.
+    pub fn to_iostore(self, package_name: &str) -> Result<IoUObject, Box<dyn Error>> {
+        if !package_name.starts_with('/') {
+            Err(format!("Package name [{package_name}] should be a path like /Game/Data/MyTable"))?;
+        }
+        self.engine_version().check_iostore_summary()?;
+        let summary = &self.summary;
+        let import_map = self.context.import_map().to_vec();
+        let export_map = summary.exports.iter().enumerate().map(|(i, e)| UObjectExportMapEntry {
+            cooked_serial_offset: e.serial_offset,
+            cooked_serial_size: e.serial_size,
+            object_name: e.object_name.clone(),
+            object_name_number: e.object_name_number,
+            outer_index: package_object_index(e.outer_index, &import_map),
+            class_index: package_object_index(e.class_index, &import_map),
+            super_index: package_object_index(e.super_index, &import_map),
+            template_index: package_object_index(e.template_index, &import_map),
+            global_import_index: match e.object_flags & RF_PUBLIC {
+                0 => PackageObjectIndex::NULL,
+                _ => PackageObjectIndex::from_import_path(&format!("{package_name}.{}", summary.export_path(i))),
+            },
+            object_flags: e.object_flags,
+            filter_flags: e.not_for_client as u8 | (e.not_for_server as u8) << 1,
+        }).collect();
+        let name_map_type = summary.names.iter().map(|n| StringType::for_string(n)).collect();
+        let imported_packages: Vec<String> = summary.imports.iter()
+            .filter(|i| i.outer_index == 0 && !i.object_name.starts_with("/Script/"))
+            .map(|i| i.object_name.clone())
+            .collect();
+
+        let iostore_summary = UObjectSummary::for_converted_package(self.engine_version(), package_name, summary.package_flags, summary.total_header_size as u32, summary.names.clone(), name_map_type, import_map, export_map, &imported_packages);
+        Ok(IoUObject::from_parts(iostore_summary, self.exports))
+    }
+
+    /// Rebuilds a UE4 IoStore package as a legacy package.  IoStore imports only hold a hash of their path, so script
+    /// imports are named by searching the mappings, and packages importing from other packages can't be converted.
+    pub fn from_iostore(object: IoUObject, mappings: Option<&Mappings>) -> Result<Self, Box<dyn Error>> {
+        let engine_version = object.engine_version();
+        if engine_version.is_zen() {
+            Err(format!("Only UE4 IoStore packages can be converted to legacy packages, not {engine_version} packages"))?;
+        }
+        let (summary, exports) = object.into_parts();
+        let import_map = summary.import_map();
+
+        // Null imports were the package imports - they're reused for the packages the script imports are in
+        let mut imports: Vec<Option<LegacyImport>> = vec![None; import_map.len()];
+        let mut free_slots = import_map.iter().enumerate().filter(|(_, i)| **i == PackageObjectIndex::NULL).map(|(i, _)| i).collect::<Vec<_>>().into_iter();
+        for (i, import) in import_map.iter().enumerate() {
+            if *import == PackageObjectIndex::NULL {
+                continue;
+            }
+            let Some(hash) = import.script_import_hash() else {
+                Err(format!("Import {i} ({import}) is from another package, which can't be named from its hash - only packages with script imports alone can be converted"))?
+            };
+            let mappings = mappings.ok_or(format!("Import {i} ({import}) can only be named using mappings - pass a .usmap with --usmap"))?;
+            let path = mappings.find_script_object(hash).ok_or(format!("Import {i} ({import}) isn't a struct, class or class default object in the mappings"))?;
+            let (package, object_name) = path.split_once('.').unwrap();
+
+            let package_index = match imports.iter().position(|p| p.as_ref().is_some_and(|p| p.outer_index == 0 && p.object_name == package)) {
+                Some(package_index) => package_index,
+                None => {
+                    let package_import = LegacyImport { class_package: "/Script/CoreUObject".to_owned(), class_name: "Package".to_owned(), outer_index: 0, object_name: package.to_owned() };
+                    match free_slots.next() {
+                        Some(slot) => { imports[slot] = Some(package_import); slot },
+                        None => { imports.push(Some(package_import)); imports.len() - 1 },
+                    }
+                }
+            };
+            let (class_package, class_name) = match object_name.strip_prefix("Default__") {
+                Some(class) => (package, class),
+                None if mappings.is_class(object_name) => ("/Script/CoreUObject", "Class"),
+                None => ("/Script/CoreUObject", "ScriptStruct"),
+            };
+            imports[i] = Some(LegacyImport {
+                class_package: class_package.to_owned(),
+                class_name: class_name.to_owned(),
+                outer_index: -(package_index as i32) - 1,
+                object_name: object_name.to_owned(),
+            });
+        }
+        let imports: Vec<LegacyImport> = imports.into_iter().map(|i| i.unwrap_or_else(|| {
+            LegacyImport { class_package: "/Script/CoreUObject".to_owned(), class_name: "Package".to_owned(), outer_index: 0, object_name: "/Script/CoreUObject".to_owned() }
+        })).collect();
+
+        let legacy_index = |index: PackageObjectIndex| -> Result<i32, Box<dyn Error>> {
+            if index == PackageObjectIndex::NULL {
+                Ok(0)
+            } else if let Some(export_index) = index.export_index() {
+                Ok(export_index as i32 + 1)
+            } else {
+                let import_index = import_map.iter().position(|i| *i == index).ok_or(format!("Export map references {index}, which isn't in the import map"))?;
+                Ok(-(import_index as i32) - 1)
+            }
+        };
+        let mut legacy_exports = vec![];
+        for export in summary.export_map() {
+            legacy_exports.push(LegacyExport {
+                class_index: legacy_index(export.class_index)?,
+                super_index: legacy_index(export.super_index)?,
+                template_index: legacy_index(export.template_index)?,
+                outer_index: legacy_index(export.outer_index)?,
+                object_name: export.object_name.clone(),
+                object_name_number: export.object_name_number,
+                object_flags: export.object_flags,
+                serial_size: 0,
+                serial_offset: 0,
+                serial_position: 0,
+                not_for_client: export.filter_flags & 1 != 0,
+                not_for_server: export.filter_flags & 2 != 0,
+            });
+        }
+
+        let mut names = summary.name_map().to_vec();
+        let import_names = imports.iter().flat_map(|i| [&i.class_package, &i.class_name, &i.object_name]);
+        for name in import_names.chain(legacy_exports.iter().map(|e| &e.object_name)) {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+
+        let legacy_summary = LegacyPackageSummary::new::<byteorder::LE>(engine_version, summary.package_flags(), &names, &imports, &legacy_exports)?;
+        let context = legacy_summary.property_context(engine_version);
+        Ok(Self {
+            summary: legacy_summary,
+            context,
+            exports,
+            uexp_tail: Self::DEFAULT_UEXP_TAIL.to_vec(),
+        })
+    }
+
+    /// Finds the engine version from the saved file version, assuming UE4.27 if the package was cooked unversioned
+    fn detect_engine_version<E: ByteOrder>(header: &[u8]) -> EngineVersion {
+        match header.get(12..16).map(E::read_i32) {
+            Some(0) | None => EngineVersion::Ue4_27,
+            Some(file_version) => engine_version_from_file_version(file_version),
+        }
+    }
+
+    pub fn to_bytes<W: Write, U: Write, E: ByteOrder>(&self, uasset: &mut W, uexp: &mut U) -> Result<usize, EncodeError> {
+        self.to_bytes_with_mappings::<W,U,E>(uasset, uexp, None)
+    }
+
+    /// Like [`Self::to_bytes`], with the mappings needed to encode exports with unversioned properties
+    pub fn to_bytes_with_mappings<W: Write, U: Write, E: ByteOrder>(&self, uasset: &mut W, uexp: &mut U, mappings: Option<&Mappings>) -> Result<usize, EncodeError> {
+        let mut context = self.context.clone();
+        let mut names = vec!["None"];
+        for export in &self.exports {
+            export.collect_names(&mut names);
+        }
+        context.add_missing_names(names);
+        let new_names = &context.name_map()[self.summary.names.len()..];
+
+        let mut exports_bytes = vec![];
+        let mut export_sizes = vec![];
+        for export in &self.exports {
+            let len = export.to_bytes::<_,E>(&mut exports_bytes, &context, mappings)?;
+            export_sizes.push((export.export_index, len as u64));
+        }
+        exports_bytes.extend_from_slice(&self.uexp_tail);
+
+        let header_bytes = self.summary.to_bytes::<E>(new_names, &export_sizes)?;
+        uasset.write_all(&header_bytes)?;
+        uexp.write_all(&exports_bytes)?;
+
+        Ok(header_bytes.len() + exports_bytes.len())
+    }
+
+    pub fn engine_version(&self) -> EngineVersion {
+        self.context.engine_version()
+    }
+
+    /// Changes which engine version the object is encoded for - only UE4 versions are possible
+    pub fn set_engine_version(&mut self, engine_version: EngineVersion) -> Result<(), Box<dyn Error>> {
+        self.context.set_engine_version(engine_version)
+    }
+
+    pub fn to_string<W: Write>(&self, writer: &mut W) {
+        writer.write_all(format!("legacy_summary: {}\n", BASE64_STANDARD.encode(&self.summary.bytes)).as_bytes()).unwrap();
+        if self.uexp_tail != Self::DEFAULT_UEXP_TAIL {
+            writer.write_all(format!("uexp_tail: {}\n", BASE64_STANDARD.encode(&self.uexp_tail)).as_bytes()).unwrap();
+        }
+        writer.write_all(format!("engine: {}\n", self.engine_version()).as_bytes()).unwrap();
+        UObjectExport::all_to_string(&self.exports, writer, &self.context);
+    }
+
+    /// Reads the text written by [`Self::to_string`].  Errors point at the line (and column) they were found at, and
+    /// reading carries on after most of them, so every error in the text is returned.
+    pub fn from_string<R: BufRead + Seek>(reader: &mut R) -> Result<Self, TextErrors> {
+        Self::read_text(reader).map_err(|errors| errors.locate(reader))
+    }
+
+    fn read_text<R: BufRead + Seek>(reader: &mut R) -> Result<Self, TextErrors> {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let summary = line.strip_prefix("legacy_summary:").ok_or_else(|| reader.text_error("LegacyUObject string should start with 'legacy_summary:' property"))?;
+        let header = BASE64_STANDARD.decode(summary.trim())
+            .map_err(|_| reader.text_error("unable to read legacy summary from base64 string. This value shouldn't be manually edited.").at_token(summary))?;
+
+        let mut uexp_tail = Self::DEFAULT_UEXP_TAIL.to_vec();
+        let mut engine_version = None;
+        let mut engine_version_offset = 0;
+        loop {
+            let start_position = reader.stream_position().unwrap();
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+            if let Some(tail) = line.strip_prefix("uexp_tail:") {
+                uexp_tail = BASE64_STANDARD.decode(tail.trim()).map_err(|_| reader.text_error("unable to read uexp_tail from base64 string").at_token(tail))?;
+            } else if let Some(version) = line.strip_prefix("engine:") {
+                engine_version_offset = reader.line_offset();
+                engine_version = Some(version.trim().parse::<EngineVersion>().map_err(|err| reader.text_error(err.to_string()).at_token(version))?);
+            } else {
+                reader.seek(SeekFrom::Start(start_position)).unwrap();
+                break;
+            }
+        }
+
+        let engine_version = match engine_version {
+            Some(engine_version) if engine_version.is_zen() => {
+                Err(TextError::new(engine_version_offset, format!("legacy packages can't be written as {engine_version} packages - only UE4 versions are supported")))?
+            },
+            Some(engine_version) => engine_version,
+            None => Self::detect_engine_version::<byteorder::LE>(&header),
+        };
+        let summary = LegacyPackageSummary::from_bytes::<byteorder::LE>(header, engine_version).map_err(|err| TextError::new(0, err.to_string()))?;
+        let context = summary.property_context(engine_version);
+        let exports = UObjectExport::all_from_string(reader, &context)?;
+
+        Ok(Self {
+            summary,
+            context,
+            exports,
+            uexp_tail,
+        })
+    }
+}
+
+#[allow(dead_code)]
+#[allow(unused_imports)]
+mod test {
+    use byteorder::{WriteBytesExt, LE};
+    use std::io::{Cursor, Write};
+
+    use super::{file_version_ue4, name_hash_case_preserving, name_hash_non_case_preserving, read_fstring, write_fstring, write_fstring_as, LegacyUObject, PACKAGE_FILE_TAG, PKG_FILTER_EDITOR_ONLY};
+    use crate::iostore_uasset::{EngineVersion, IoUObject, PackageObjectIndex, StringType};
+    use crate::usmap::Mappings;
+
+    const TEST_NAMES: [&str; 9] = ["None", "IntProperty", "MyInt", "/Script/Engine", "DataTable", "/Script/CoreUObject", "Package", "Class", "MyAsset"];
+
+    /// A 4.27 cooked package holding one DataTable export with an IntProperty, as a .uasset and .uexp
+    fn get_test_package() -> (Vec<u8>, Vec<u8>) {
+        let name_index = |name: &str| TEST_NAMES.iter().position(|n| *n == name).unwrap() as i32;
+        let write_fname = |writer: &mut Vec<u8>, name: &str| {
+            writer.write_i32::<LE>(name_index(name)).unwrap();
+            writer.write_i32::<LE>(0).unwrap();
+        };
+
+        let mut export_data = vec![];
+        export_data.write_u64::<LE>(name_index("MyInt") as u64).unwrap();
+        export_data.write_u64::<LE>(name_index("IntProperty") as u64).unwrap();
+        export_data.write_u32::<LE>(4).unwrap();
+        export_data.write_u32::<LE>(0).unwrap();
+        export_data.write_u8(0).unwrap();
+        export_data.write_i32::<LE>(5).unwrap();
+        export_data.write_u64::<LE>(name_index("None") as u64).unwrap();
+        export_data.write_u32::<LE>(0).unwrap();
+
+        let mut names = vec![];
+        for name in TEST_NAMES {
+            write_fstring::<_,LE>(&mut names, name).unwrap();
+            names.write_u16::<LE>(name_hash_non_case_preserving(name, &StringType::Utf8)).unwrap();
+            names.write_u16::<LE>(name_hash_case_preserving(name)).unwrap();
+        }
+        let mut imports = vec![];
+        for (class_name, outer_index, object_name) in [("Package", 0, "/Script/Engine"), ("Class", -1, "DataTable")] {
+            write_fname(&mut imports, "/Script/CoreUObject");
+            write_fname(&mut imports, class_name);
+            imports.write_i32::<LE>(outer_index).unwrap();
+            write_fname(&mut imports, object_name);
+        }
+
+        let summary = |total_header_size: i32, name_offset: i32, import_offset: i32, export_offset: i32, depends_offset: i32, export_serial_offset: i64| {
+            let mut summary = vec![];
+            summary.write_u32::<LE>(PACKAGE_FILE_TAG).unwrap();
+            for version in [-7, 864, 522, 0, 0] { // Legacy, UE3, UE4 and licensee file versions, custom version count
+                summary.write_i32::<LE>(version).unwrap();
+            }
+            summary.write_i32::<LE>(total_header_size).unwrap();
+            write_fstring::<_,LE>(&mut summary, "None").unwrap();
+            summary.write_u32::<LE>(PKG_FILTER_EDITOR_ONLY).unwrap();
+            for value in [TEST_NAMES.len() as i32, name_offset, 0, 0, 1, export_offset, 2, import_offset, depends_offset, 0, 0, 0, 0] {
+                summary.write_i32::<LE>(value).unwrap();
+            }
+            summary.write_all(&[0;16]).unwrap(); // Guid
+            for value in [1, 1, TEST_NAMES.len() as i32] { // Generations
+                summary.write_i32::<LE>(value).unwrap();
+            }
+            for _ in 0..2 { // Saved by and compatible with engine versions
+                summary.write_all(&[4, 0, 27, 0, 2, 0, 0, 0, 0, 0]).unwrap();
+                write_fstring::<_,LE>(&mut summary, "").unwrap();
+            }
+            for value in [0, 0, 0, 0, depends_offset + 4] { // Compression, chunks, source, packages to cook, asset registry
+                summary.write_i32::<LE>(value).unwrap();
+            }
+            summary.write_i64::<LE>(export_serial_offset + export_data.len() as i64 + 4).unwrap(); // Bulk data start
+            for value in [0, 0, 0, depends_offset + 4] { // World tile info, chunk ids, preload dependencies
+                summary.write_i32::<LE>(value).unwrap();
+            }
+            summary
+        };
+
+        let name_offset = summary(0, 0, 0, 0, 0, 0).len() as i32;
+        let import_offset = name_offset + names.len() as i32;
+        let export_offset = import_offset + imports.len() as i32;
+        let depends_offset = export_offset + 104;
+        let total_header_size = depends_offset + 4 + 4;
+
+        let mut uasset = summary(total_header_size, name_offset, import_offset, export_offset, depends_offset, total_header_size as i64);
+        uasset.extend_from_slice(&names);
+        uasset.extend_from_slice(&imports);
+        for value in [-2, 0, 0, 0] { // Class, super, template, outer
+            uasset.write_i32::<LE>(value).unwrap();
+        }
+        write_fname(&mut uasset, "MyAsset");
+        uasset.write_u32::<LE>(0x1).unwrap(); // RF_Public
+        uasset.write_i64::<LE>(export_data.len() as i64).unwrap();
+        uasset.write_i64::<LE>(total_header_size as i64).unwrap();
+        uasset.write_all(&[0;12 + 16 + 4 + 8 + 20]).unwrap();
+        uasset.write_all(&[0;8]).unwrap(); // Depends and asset registry data
+        assert_eq!(uasset.len(), total_header_size as usize);
+
+        let mut uexp = export_data;
+        uexp.write_u32::<LE>(PACKAGE_FILE_TAG).unwrap();
+        (uasset, uexp)
+    }
+
+    fn encode(object: &LegacyUObject) -> (Vec<u8>, Vec<u8>) {
+        let (mut uasset, mut uexp) = (vec![], vec![]);
+        object.to_bytes::<_,_,LE>(&mut uasset, &mut uexp).unwrap();
+        (uasset, uexp)
+    }
+
+    fn to_text(object: &LegacyUObject) -> String {
+        let mut text = vec![];
+        object.to_string(&mut text);
+        String::from_utf8(text).unwrap()
+    }
+
+    #[test]
+    fn legacy_package_round_trips() {
+        let (uasset, uexp) = get_test_package();
+        let object = LegacyUObject::from_buffers::<_,_,LE>(&mut Cursor::new(&uasset), &mut Cursor::new(&uexp)).unwrap();
+        assert_eq!(object.engine_version(), EngineVersion::Ue4_27);
+        assert_eq!(encode(&object), (uasset.clone(), uexp.clone()));
+
+        let text = to_text(&object);
+        assert!(text.starts_with("legacy_summary: "));
+        assert!(text.contains("engine: 4.27\n"));
+        assert!(text.contains("contents: 0 (MyAsset)\n"));
+        assert!(!text.contains("uexp_tail:"));
+
+        let from_text = LegacyUObject::from_string(&mut Cursor::new(text)).unwrap();
+        assert_eq!(encode(&from_text), (uasset, uexp));
+    }
+
+    #[test]
+    fn legacy_imports_get_iostore_indices() {
+        let (uasset, uexp) = get_test_package();
+        let object = LegacyUObject::from_buffers::<_,_,LE>(&mut Cursor::new(&uasset), &mut Cursor::new(&uexp)).unwrap();
+        let import_map: Vec<String> = object.summary.imports.iter().enumerate().map(|(i, _)| object.summary.import_path(i)).collect();
+        assert_eq!(import_map, ["/Script/Engine", "/Script/Engine.DataTable"]);
+
+        let mut context = vec![];
+        object.context.maps_to_string(&mut context);
+        let context = String::from_utf8(context).unwrap();
+        assert!(context.contains(&format!("  - 0: {}\n", PackageObjectIndex::NULL)));
+        assert!(context.contains(&format!("class_index: {}\n", PackageObjectIndex::from_import_path("/Script/Engine.DataTable"))));
+    }
+
+    #[test]
+    fn legacy_package_new_names_are_added() {
+        let (uasset, uexp) = get_test_package();
+        let object = LegacyUObject::from_buffers::<_,_,LE>(&mut Cursor::new(&uasset), &mut Cursor::new(&uexp)).unwrap();
+        let text = to_text(&object).replace("MyInt", "Renamed");
+        let (new_uasset, new_uexp) = encode(&LegacyUObject::from_string(&mut Cursor::new(text)).unwrap());
+
+        // One name entry: its length, "Renamed" and a null, and two hashes
+        let delta = 4 + 8 + 4;
+        assert_eq!(new_uasset.len(), uasset.len() + delta);
+        assert_eq!(new_uexp.len(), uexp.len());
+
+        let reread = LegacyUObject::from_buffers::<_,_,LE>(&mut Cursor::new(&new_uasset), &mut Cursor::new(&new_uexp)).unwrap();
+        assert_eq!(reread.summary.names.last().map(String::as_str), Some("Renamed"));
+        assert_eq!(reread.summary.total_header_size as usize, new_uasset.len());
+        assert_eq!(reread.summary.exports[0].serial_offset as usize, new_uasset.len());
+        assert!(to_text(&reread).contains("Renamed"));
+
+        let generation_name_count = reread.summary.generation_name_count_positions[0];
+        assert_eq!(i32::from_le_bytes(new_uasset[generation_name_count..generation_name_count + 4].try_into().unwrap()), TEST_NAMES.len() as i32 + 1);
+        let bulk_data_start_offset = reread.summary.bulk_data_start_offset_position;
+        assert_eq!(i64::from_le_bytes(new_uasset[bulk_data_start_offset..bulk_data_start_offset + 8].try_into().unwrap()) as usize, new_uasset.len() + new_uexp.len());
+    }
+
+    #[test]
+    fn legacy_name_hashes() {
+        let hashes = |name: &str, string_type: StringType| (name_hash_non_case_preserving(name, &string_type), name_hash_case_preserving(name));
+
+        // As stored after these names in cooked UE4 name maps
+        assert_eq!(hashes("None", StringType::Utf8), (0x03F4, 0x0DC5));
+        assert_eq!(hashes("/Script/CoreUObject", StringType::Utf8), (0x49F8, 0x3E2D));
+        assert_eq!(hashes("ArrayProperty", StringType::Utf8), (0xB049, 0x69E3));
+
+        // Only ASCII is upper cased, and UTF-16 names hash their code units - surrogates included
+        assert_eq!(hashes("Café", StringType::Utf8), (0xB962, 0x8EB4));
+        assert_eq!(hashes("Café", StringType::Utf16), (0x5C06, 0x8EB4));
+        assert_eq!(hashes("straße", StringType::Utf16), (0x5270, 0x7D07));
+        assert_eq!(hashes("😀", StringType::Utf16), (0x802A, 0xCCDD));
+        assert_eq!(name_hash_non_case_preserving("none", &StringType::Utf8), name_hash_non_case_preserving("NONE", &StringType::Utf8));
+    }
+
+    #[test]
+    fn fstrings_round_trip() {
+        let latin1 = [5, 0, 0, 0, b'C', b'a', b'f', 0xE9, 0];
+        let utf16 = [0xFB, 0xFF, 0xFF, 0xFF, b'C', 0, b'a', 0, b'f', 0, 0xE9, 0, 0, 0];
+        for bytes in [&latin1[..], &utf16[..]] {
+            let (value, string_type) = read_fstring::<_,LE>(&mut Cursor::new(bytes)).unwrap();
+            assert_eq!(value, "Caf\u{e9}");
+            let mut written = vec![];
+            write_fstring_as::<_,LE>(&mut written, &value, &string_type).unwrap();
+            assert_eq!(written, bytes);
+        }
+
+        // New strings are written like the engine does, as UTF-16 if they aren't ASCII
+        let mut written = vec![];
+        write_fstring::<_,LE>(&mut written, "Caf\u{e9}").unwrap();
+        assert_eq!(written, utf16);
+    }
+
+    #[test]
+    fn engine_version_is_found_from_file_version() {
+        let (mut uasset, uexp) = get_test_package();
+        uasset[12..16].copy_from_slice(&519i32.to_le_bytes());
+        let object = LegacyUObject::from_buffers::<_,_,LE>(&mut Cursor::new(&uasset), &mut Cursor::new(&uexp)).unwrap();
+        assert_eq!(object.engine_version(), EngineVersion::Ue4_26);
+        assert!(to_text(&object).contains("\nengine: 4.26\n"));
+        assert_eq!(encode(&object), (uasset.clone(), uexp.clone()));
+        let err = object.to_iostore("/Game/MyAsset").err().unwrap();
+        assert!(err.to_string().contains("global name map"), "{err}");
+
+        let object = LegacyUObject::from_buffers_with_options::<_,_,LE>(&mut Cursor::new(&uasset), &mut Cursor::new(&uexp), None, Some(EngineVersion::Ue4_25)).unwrap();
+        assert_eq!(object.engine_version(), EngineVersion::Ue4_25);
+        assert_eq!(file_version_ue4(EngineVersion::Ue4_25), 518);
+    }
+
+    #[test]
+    fn ue5_legacy_packages_are_rejected() {
+        let (mut uasset, uexp) = get_test_package();
+        uasset[4..8].copy_from_slice(&(-8i32).to_le_bytes());
+        let err = LegacyUObject::from_buffers::<_,_,LE>(&mut Cursor::new(&uasset), &mut Cursor::new(&uexp)).err().unwrap();
+        assert!(err.to_string().contains("UE5 legacy packages aren't supported"), "{err}");
+
+        let (uasset, uexp) = get_test_package();
+        assert!(LegacyUObject::from_buffers_with_options::<_,_,LE>(&mut Cursor::new(&uasset), &mut Cursor::new(&uexp), None, Some(EngineVersion::Ue5)).is_err());
+    }
+
+    #[test]
+    fn bad_name_counts_are_errors() {
+        // Tag, versions, total header size, folder name ("None") and package flags come before the name count
+        let name_count = 4 + 20 + 4 + 9 + 4;
+        for count in [-1, i32::MAX] {
+            let (mut uasset, uexp) = get_test_package();
+            assert_eq!(uasset[name_count..name_count + 4], (TEST_NAMES.len() as i32).to_le_bytes());
+            uasset[name_count..name_count + 4].copy_from_slice(&count.to_le_bytes());
+            assert!(LegacyUObject::from_buffers::<_,_,LE>(&mut Cursor::new(&uasset), &mut Cursor::new(&uexp)).is_err());
+        }
+    }
+
+    /// Mappings holding DataTable, as a class
+    fn get_test_mappings() -> Mappings {
+        let mut data = vec![];
+        data.extend(2u32.to_le_bytes()); // Names
+        for name in ["Object", "DataTable"] {
+            data.push(name.len() as u8);
+            data.extend(name.as_bytes());
+        }
+        data.extend(0u32.to_le_bytes()); // Enums
+        data.extend(2u32.to_le_bytes()); // Structs
+        for (name, super_type) in [(0i32, -1i32), (1, 0)] {
+            data.extend(name.to_le_bytes());
+            data.extend(super_type.to_le_bytes());
+            data.extend([0, 0, 0, 0]);
+        }
+
+        let mut usmap = vec![0xC4, 0x30, 0, 0];
+        usmap.extend((data.len() as u32).to_le_bytes());
+        usmap.extend((data.len() as u32).to_le_bytes());
+        usmap.extend(data);
+        Mappings::from_buffer(&mut usmap.as_slice()).unwrap()
+    }
+
+    fn contents_section(text: &str) -> &str {
+        &text[text.find("contents:").unwrap()..]
+    }
+
+    #[test]
+    fn legacy_package_converts_to_iostore() {
+        let (uasset, uexp) = get_test_package();
+        let object = LegacyUObject::from_buffers::<_,_,LE>(&mut Cursor::new(&uasset), &mut Cursor::new(&uexp)).unwrap();
+        let legacy_text = to_text(&object);
+        assert!(object.to_iostore("MyAsset").is_err());
+
+        let object = LegacyUObject::from_buffers::<_,_,LE>(&mut Cursor::new(&uasset), &mut Cursor::new(&uexp)).unwrap();
+        let mut iostore = vec![];
+        object.to_iostore("/Game/MyAsset").unwrap().to_bytes::<_,LE>(&mut iostore).unwrap();
+        let object = IoUObject::from_buffer::<_,LE>(&mut Cursor::new(&iostore)).unwrap();
+        assert_eq!(object.engine_version(), EngineVersion::Ue4_27);
+
+        let mut reencoded = vec![];
+        object.to_bytes::<_,LE>(&mut reencoded).unwrap();
+        assert_eq!(reencoded, iostore);
+        assert!(iostore.ends_with(&uexp[..uexp.len() - 4]));
+
+        let mut text = vec![];
+        object.to_string(&mut text);
+        let text = String::from_utf8(text).unwrap();
+        assert_eq!(contents_section(&text), contents_section(&legacy_text));
+        assert!(text.contains(&format!("  - 1: {}\n", PackageObjectIndex::from_import_path("/Script/Engine.DataTable"))));
+        assert!(text.contains(&format!("global_import_index: {}\n", PackageObjectIndex::from_import_path("/Game/MyAsset.MyAsset"))));
+        assert!(text.contains("serialize: 0\n"));
+    }
+
+    #[test]
+    fn iostore_package_converts_to_legacy() {
+        let (uasset, uexp) = get_test_package();
+        let object = LegacyUObject::from_buffers::<_,_,LE>(&mut Cursor::new(&uasset), &mut Cursor::new(&uexp)).unwrap();
+        let legacy_text = to_text(&object);
+        let mut iostore = vec![];
+        object.to_iostore("/Game/MyAsset").unwrap().to_bytes::<_,LE>(&mut iostore).unwrap();
+
+        let object = IoUObject::from_buffer::<_,LE>(&mut Cursor::new(&iostore)).unwrap();
+        assert!(LegacyUObject::from_iostore(object, None).is_err());
+
+        let object = IoUObject::from_buffer::<_,LE>(&mut Cursor::new(&iostore)).unwrap();
+        let (new_uasset, new_uexp) = encode(&LegacyUObject::from_iostore(object, Some(&get_test_mappings())).unwrap());
+        assert_eq!(new_uexp, uexp);
+
+        let object = LegacyUObject::from_buffers::<_,_,LE>(&mut Cursor::new(&new_uasset), &mut Cursor::new(&new_uexp)).unwrap();
+        assert_eq!(encode(&object), (new_uasset, new_uexp));
+        assert_eq!(contents_section(&to_text(&object)), contents_section(&legacy_text));
+
+        let imports: Vec<(String, String)> = object.summary.imports.iter().enumerate().map(|(i, import)| (object.summary.import_path(i), import.class_name.clone())).collect();
+        assert_eq!(imports, [("/Script/Engine".to_owned(), "Package".to_owned()), ("/Script/Engine.DataTable".to_owned(), "Class".to_owned())]);
+        assert_eq!(object.summary.exports[0].class_index, -2);
+    }
+
+    #[test]
+    fn renamed_iostore_exports_convert_to_legacy() {
+        let (uasset, uexp) = get_test_package();
+        let object = LegacyUObject::from_buffers::<_,_,LE>(&mut Cursor::new(&uasset), &mut Cursor::new(&uexp)).unwrap();
+        let mut text = vec![];
+        object.to_iostore("/Game/MyAsset").unwrap().to_string(&mut text);
+        let text = String::from_utf8(text).unwrap().replace("object_name: MyAsset\n", "object_name: Renamed\n");
+
+        let object = IoUObject::from_string(&mut Cursor::new(text.as_bytes())).unwrap();
+        let object = LegacyUObject::from_iostore(object, Some(&get_test_mappings())).unwrap();
+        assert_eq!(object.summary.exports[0].object_name, "Renamed");
+        let (new_uasset, new_uexp) = encode(&object);
+        let reread = LegacyUObject::from_buffers::<_,_,LE>(&mut Cursor::new(&new_uasset), &mut Cursor::new(&new_uexp)).unwrap();
+        assert_eq!(reread.summary.exports[0].object_name, "Renamed");
+    }
+}
@@ -1,3 +1,20 @@
+mod cityhash;
+mod compression;
+mod error;
+mod iostore_container;
 mod iostore_uasset;
+mod legacy_uasset;
+mod pak;
+mod sha1;
+mod struct_codecs;
+mod unversioned;
+mod usmap;
 
-pub use iostore_uasset::IoUObject;
\ No newline at end of file
+pub use compression::register_decompressor;
+pub use error::{EncodeError, ParseError, TextError, TextErrors};
+pub use iostore_container::IoStoreContainer;
+pub use iostore_uasset::{EngineVersion, IoUObject, PropertyLayout};
+pub use legacy_uasset::{is_legacy_package, LegacyUObject};
+pub use pak::PakFile;
+pub use struct_codecs::register_schema;
+pub use usmap::Mappings;
\ No newline at end of file
@@ -1,11 +1,27 @@
 use byteorder::LE;
 use config::{Config, Command};
-use std::{env, error::Error, fs::File, io::{BufReader, Cursor, Read}, process};
+use std::{env, error::Error, fs::{self, File}, io::{BufRead, BufReader, Cursor, Read, Write}, process};
 
+mod cityhash;
+mod compression;
+mod error;
+mod iostore_container;
 mod iostore_uasset;
+mod legacy_uasset;
+mod pak;
+mod sha1;
+mod struct_codecs;
+mod unversioned;
+mod usmap;
 mod config;
 
+pub use compression::register_decompressor;
+pub use error::{EncodeError, ParseError, TextError, TextErrors};
+pub use iostore_container::IoStoreContainer;
 pub use iostore_uasset::IoUObject;
+pub use legacy_uasset::LegacyUObject;
+pub use pak::PakFile;
+use usmap::Mappings;
 
 fn main() {
     let config = Config::new(env::args()).unwrap_or_else(|err| {
@@ -21,22 +37,61 @@ fn main() {
 }
 
 fn execute(config: Config) -> Result<(), Box<dyn Error>> {
+    if let Some(schema_path) = &config.schema {
+        let contents = fs::read_to_string(schema_path).map_err(|e| format!("Unable to read schema file {schema_path}: {e}"))?;
+        struct_codecs::register_schema(&contents)?;
+    }
+    let mappings = match &config.usmap {
+        Some(usmap_path) => {
+            let bytes = fs::read(usmap_path).map_err(|e| format!("Unable to read mappings file {usmap_path}: {e}"))?;
+            let mut mappings = Mappings::from_buffer(&mut Cursor::new(bytes)).map_err(|e| format!("Unable to load mappings file {usmap_path}: {e}"))?;
+            for module in &config.script_modules {
+                mappings.add_script_module(module);
+            }
+            Some(mappings)
+        },
+        None => None,
+    };
+    let mappings = mappings.as_ref();
+
     let infile = File::open(&config.inpath)?;
     match config.command {
         Command::Encode => {
-            if !config.inpath.ends_with(".yaml_uasset") {
+            let yaml = config.yaml || config.inpath.ends_with(".yaml");
+            if !yaml && !config.inpath.ends_with(".yaml_uasset") {
                 println!("WARNING: Encoding a file that does not have the '.yaml_uasset' extension");
             }
-            let mut outfile = match config.outpath {
-                Some(path) => File::create(path)?,
+            let outpath = match config.outpath {
+                Some(path) => path,
                 None => {
                     let infilename = config.inpath.rsplit_once(std::path::MAIN_SEPARATOR_STR).map(|f| f.1).unwrap_or(&config.inpath);
                     let outfilename = infilename.rsplit_once('.').map(|f| f.0).unwrap_or(infilename);
-                    File::create(format!("{outfilename}.uasset"))?
+                    format!("{outfilename}.uasset")
                 }
             };
-            let object = IoUObject::from_string(&mut BufReader::new(infile))?;
-            object.to_bytes::<_, LE>(&mut outfile);
+            let mut reader = BufReader::new(infile);
+            if !yaml && reader.fill_buf()?.starts_with(b"legacy_summary:") {
+                let mut object = LegacyUObject::from_string(&mut reader).map_err(|errors| describe_text_errors(&config.inpath, &errors, config.max_errors))?;
+                if let Some(engine_version) = config.engine_version {
+                    object.set_engine_version(engine_version)?;
+                }
+                // Encode into memory first, so a value that can't be encoded doesn't leave a partial file behind
+                let (mut uasset, mut uexp) = (vec![], vec![]);
+                object.to_bytes_with_mappings::<_, _, LE>(&mut uasset, &mut uexp, mappings).map_err(|err| format!("Unable to encode {}: {err}", config.inpath))?;
+                std::fs::write(uexp_path(&outpath), uexp)?;
+                std::fs::write(outpath, uasset)?;
+            } else {
+                let mut object = match yaml {
+                    true => IoUObject::from_yaml(reader).map_err(|err| format!("Unable to read {}: {err}", config.inpath))?,
+                    false => IoUObject::from_string(&mut reader).map_err(|errors| describe_text_errors(&config.inpath, &errors, config.max_errors))?,
+                };
+                if let Some(engine_version) = config.engine_version {
+                    object.set_engine_version(engine_version)?;
+                }
+                let mut bytes = vec![];
+                object.to_bytes_with_mappings::<_, LE>(&mut bytes, mappings).map_err(|err| format!("Unable to encode {}: {err}", config.inpath))?;
+                std::fs::write(outpath, bytes)?;
+            }
         },
         Command::Decode => {
             if !config.inpath.ends_with(".uasset") {
@@ -47,11 +102,21 @@ fn execute(config: Config) -> Result<(), Box<dyn Error>> {
                 None => {
                     let infilename = config.inpath.rsplit_once(std::path::MAIN_SEPARATOR_STR).map(|f| f.1).unwrap_or(&config.inpath);
                     let outfilename = infilename.rsplit_once('.').map(|f| f.0).unwrap_or(infilename);
-                    File::create(format!("{outfilename}.yaml_uasset"))?
+                    File::create(format!("{outfilename}.{}", text_extension(config.yaml)))?
                 }
             };
-            let object = IoUObject::from_buffer::<_, LE>(&mut BufReader::new(infile))?;
-            object.to_string(&mut outfile);
+            let mut reader = BufReader::new(infile);
+            if legacy_uasset::is_legacy_package::<_, LE>(&mut reader) {
+                if config.yaml {
+                    Err(LEGACY_YAML_UNSUPPORTED)?;
+                }
+                let mut uexp = BufReader::new(open_uexp(&config.inpath)?);
+                let object = LegacyUObject::from_buffers_with_options::<_, _, LE>(&mut reader, &mut uexp, mappings, config.engine_version)?;
+                object.to_string(&mut outfile);
+            } else {
+                let object = IoUObject::from_buffer_with_options::<_, LE>(&mut reader, mappings, config.engine_version)?;
+                write_text(&object, config.yaml, &mut outfile)?;
+            }
         },
         Command::Test => {
             if !config.inpath.ends_with(".uasset") {
@@ -65,23 +130,227 @@ fn execute(config: Config) -> Result<(), Box<dyn Error>> {
                 Cursor::new(file_bytes)
             };
 
-            let mut stringified = IoUObject::from_buffer::<_, LE>(&mut BufReader::new(&mut original_file_bytes)).map(|o| {
-                let mut s = Cursor::new(vec![]);
-                o.to_string(&mut s);            
-                s.set_position(0);
-                s
-            })?;
-            let result = IoUObject::from_string(&mut stringified).map(|r| {
-                let mut bytes = vec![];
-                r.to_bytes::<_,byteorder::LE>(&mut bytes);
-                bytes
-            })?;
-    
-            for (i, byte) in original_file_bytes.into_inner().iter().enumerate() {
-                assert_eq!(byte, &result[i], "File bytes differ at 0x{i:x}");
+            if legacy_uasset::is_legacy_package::<_, LE>(&mut original_file_bytes) {
+                if config.yaml {
+                    Err(LEGACY_YAML_UNSUPPORTED)?;
+                }
+                let mut original_uexp_bytes = vec![];
+                open_uexp(&config.inpath)?.read_to_end(&mut original_uexp_bytes)?;
+
+                let mut stringified = LegacyUObject::from_buffers_with_options::<_, _, LE>(&mut original_file_bytes, &mut Cursor::new(&original_uexp_bytes), mappings, config.engine_version).map(|o| {
+                    let mut s = Cursor::new(vec![]);
+                    o.to_string(&mut s);
+                    s.set_position(0);
+                    s
+                })?;
+                let (mut result, mut uexp_result) = (vec![], vec![]);
+                LegacyUObject::from_string(&mut stringified)?.to_bytes_with_mappings::<_,_,byteorder::LE>(&mut result, &mut uexp_result, mappings)?;
+
+                compare_bytes("File", &original_file_bytes.into_inner(), &result)?;
+                compare_bytes(".uexp", &original_uexp_bytes, &uexp_result)?;
+            } else {
+                let object = IoUObject::from_buffer_with_options::<_, LE>(&mut BufReader::new(&mut original_file_bytes), mappings, config.engine_version)?;
+                let mut stringified = Cursor::new(vec![]);
+                write_text(&object, config.yaml, &mut stringified)?;
+                stringified.set_position(0);
+                let object = match config.yaml {
+                    true => IoUObject::from_yaml(stringified)?,
+                    false => IoUObject::from_string(&mut stringified)?,
+                };
+                let mut result = vec![];
+                object.to_bytes_with_mappings::<_,byteorder::LE>(&mut result, mappings)?;
+
+                compare_bytes("File", &original_file_bytes.into_inner(), &result)?;
             }
             println!("SUCCESS: Decode/reencode resulted in same binary.")
+        },
+        Command::Convert => {
+            let outpath = config.outpath.ok_or("Converting needs an output path")?;
+            let mut reader = BufReader::new(infile);
+            if legacy_uasset::is_legacy_package::<_, LE>(&mut reader) {
+                let mut uexp = BufReader::new(open_uexp(&config.inpath)?);
+                let object = LegacyUObject::from_buffers_with_options::<_, _, LE>(&mut reader, &mut uexp, mappings, config.engine_version)?;
+                let package_name = match config.package_name {
+                    Some(package_name) => package_name,
+                    None => package_name_from_path(&config.inpath)?,
+                };
+                let object = object.to_iostore(&package_name)?;
+                // Encode into memory first, as with encode, so a failure doesn't leave a partial file behind
+                let mut uasset = vec![];
+                object.to_bytes_with_mappings::<_, LE>(&mut uasset, mappings)?;
+                std::fs::write(outpath, uasset)?;
+            } else {
+                let object = IoUObject::from_buffer_with_options::<_, LE>(&mut reader, mappings, config.engine_version)?;
+                let object = LegacyUObject::from_iostore(object, mappings)?;
+                let (mut uasset, mut uexp) = (vec![], vec![]);
+                object.to_bytes_with_mappings::<_, _, LE>(&mut uasset, &mut uexp, mappings)?;
+                std::fs::write(uexp_path(&outpath), uexp)?;
+                std::fs::write(outpath, uasset)?;
+            }
+        },
+        Command::List => {
+            if config.inpath.ends_with(".pak") {
+                let pak = PakFile::from_buffer(&mut BufReader::new(infile))?;
+                if !pak.has_directory_index() {
+                    Err("The pak has no full directory index, so its files can't be listed")?;
+                }
+                for package in pak.packages() {
+                    println!("{package}");
+                }
+            } else {
+                if !config.inpath.ends_with(".utoc") {
+                    println!("WARNING: Listing a file that does not have the '.utoc' or '.pak' extension");
+                }
+                let container = IoStoreContainer::from_buffer(&mut BufReader::new(infile))?;
+                for package in container.packages() {
+                    println!("{package}");
+                }
+            }
+        },
+        Command::ExtractDecode => {
+            let package_path = config.package_path.ok_or("Extracting needs the path of the package in the container")?;
+            let mut outfile_bytes = Cursor::new(vec![]);
+            if config.inpath.ends_with(".pak") {
+                let mut reader = BufReader::new(infile);
+                let pak = PakFile::from_buffer(&mut reader)?;
+                let mut bytes = Cursor::new(pak.read_file(&package_path, &mut reader)?);
+                if legacy_uasset::is_legacy_package::<_, LE>(&mut bytes) {
+                    if config.yaml {
+                        Err(LEGACY_YAML_UNSUPPORTED)?;
+                    }
+                    let uexp = pak.read_file(&uexp_path(&package_path), &mut reader)?;
+                    let object = LegacyUObject::from_buffers_with_options::<_, _, LE>(&mut bytes, &mut Cursor::new(uexp), mappings, config.engine_version)?;
+                    object.to_string(&mut outfile_bytes);
+                } else {
+                    let object = IoUObject::from_buffer_with_options::<_, LE>(&mut bytes, mappings, config.engine_version)?;
+                    write_text(&object, config.yaml, &mut outfile_bytes)?;
+                }
+            } else {
+                if !config.inpath.ends_with(".utoc") {
+                    println!("WARNING: Extracting from a file that does not have the '.utoc' or '.pak' extension");
+                }
+                let container = IoStoreContainer::from_buffer(&mut BufReader::new(infile))?;
+                let mut partitions = open_partitions(&config.inpath, container.partition_count())?;
+                let bytes = container.read_file(&package_path, &mut partitions)?;
+                let object = IoUObject::from_buffer_with_options::<_, LE>(&mut Cursor::new(bytes), mappings, config.engine_version)?;
+                write_text(&object, config.yaml, &mut outfile_bytes)?;
+            }
+
+            let outpath = match config.outpath {
+                Some(path) => path,
+                None => {
+                    let filename = package_path.rsplit_once('/').map(|f| f.1).unwrap_or(&package_path);
+                    let stem = filename.rsplit_once('.').map(|f| f.0).unwrap_or(filename);
+                    format!("{stem}.{}", text_extension(config.yaml))
+                }
+            };
+            fs::write(outpath, outfile_bytes.into_inner())?;
+        },
+        Command::Pack => {
+            let outpath = config.outpath.ok_or("Packing needs an output .utoc path")?;
+            let stem = outpath.strip_suffix(".utoc").ok_or("Packing needs an output path ending in '.utoc'")?;
+            let name = stem.rsplit(['/', '\\']).next().unwrap_or(stem);
+            let mut packages = vec![];
+            for inpath in &config.inpaths {
+                let bytes = fs::read(inpath).map_err(|e| format!("Unable to read {inpath}: {e}"))?;
+                if legacy_uasset::is_legacy_package::<_, LE>(&mut Cursor::new(&bytes)) {
+                    Err(format!("{inpath} is a legacy package - convert it to IoStore before packing"))?;
+                }
+                packages.push((container_path_from_path(inpath)?, bytes));
+            }
+            let mut utoc = File::create(&outpath)?;
+            let mut ucas = File::create(format!("{stem}.ucas"))?;
+            let mut pak = File::create(format!("{stem}.pak"))?;
+            IoStoreContainer::pack(name, &packages, &mut utoc, &mut ucas, &mut pak)?;
         }
     }
     Ok(())
-}
\ No newline at end of file
+}
+
+const LEGACY_YAML_UNSUPPORTED: &str = "Standard YAML is only supported for IoStore packages - decode legacy packages without --yaml";
+
+/// Writes a decoded package as standard YAML, or in the default yaml-like format
+fn write_text<W: Write>(object: &IoUObject, yaml: bool, writer: &mut W) -> Result<(), Box<dyn Error>> {
+    match yaml {
+        true => object.to_yaml(writer),
+        false => {
+            object.to_string(writer);
+            Ok(())
+        }
+    }
+}
+
+/// The extension of decoded packages
+fn text_extension(yaml: bool) -> &'static str {
+    match yaml {
+        true => "yaml",
+        false => "yaml_uasset",
+    }
+}
+
+/// Checks a reencoded file matches the original, naming the first offset where they differ
+fn compare_bytes(what: &str, original: &[u8], result: &[u8]) -> Result<(), String> {
+    match original.iter().zip(result).position(|(a, b)| a != b) {
+        Some(i) => Err(format!("{what} bytes differ at 0x{i:x}")),
+        None if original.len() != result.len() => Err(format!(
+            "{what} bytes differ at 0x{:x}: the original is 0x{:x} bytes, but reencoding produced 0x{:x}",
+            original.len().min(result.len()), original.len(), result.len()
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Lists the errors in a text file like compiler diagnostics, showing at most `max_errors` of them
+fn describe_text_errors(path: &str, errors: &TextErrors, max_errors: Option<usize>) -> String {
+    let shown = errors.0.len().min(max_errors.unwrap_or(usize::MAX));
+    let mut lines: Vec<String> = errors.0[..shown].iter().map(|err| format!("{path}:{err}")).collect();
+    if shown < errors.0.len() {
+        lines.push(format!("... and {} more (shown with a higher --max-errors)", errors.0.len() - shown));
+    }
+    match errors.0.len() {
+        1 => lines.join("\n\n"),
+        count => format!("{count} errors in {path}\n\n{}", lines.join("\n\n")),
+    }
+}
+
+/// The .uexp that holds the exports of a legacy .uasset
+fn uexp_path(uasset_path: &str) -> String {
+    let stem = uasset_path.strip_suffix(".uasset").unwrap_or(uasset_path);
+    format!("{stem}.uexp")
+}
+
+fn open_uexp(uasset_path: &str) -> Result<File, Box<dyn Error>> {
+    let path = uexp_path(uasset_path);
+    Ok(File::open(&path).map_err(|e| format!("Unable to open {path}, which holds the exports of the legacy package: {e}"))?)
+}
+
+/// Opens the .ucas partitions next to a .utoc - partition 0 is `{name}.ucas`, and partition n `{name}_s{n}.ucas`
+fn open_partitions(utoc_path: &str, partition_count: u32) -> Result<Vec<BufReader<File>>, Box<dyn Error>> {
+    let stem = utoc_path.strip_suffix(".utoc").unwrap_or(utoc_path);
+    (0..partition_count).map(|i| {
+        let path = match i {
+            0 => format!("{stem}.ucas"),
+            i => format!("{stem}_s{i}.ucas"),
+        };
+        let file = File::open(&path).map_err(|e| format!("Unable to open {path}, which holds the container's data: {e}"))?;
+        Ok(BufReader::new(file))
+    }).collect()
+}
+
+/// Where a file inside a Content folder goes under a container's ../../../ mount point, e.g.
+/// .../MyGame/Content/Data/MyTable.uasset is MyGame/Content/Data/MyTable.uasset
+fn container_path_from_path(path: &str) -> Result<String, Box<dyn Error>> {
+    let path = path.replace('\\', "/");
+    let (project_path, content_path) = path.rsplit_once("/Content/").ok_or(format!("Unable to find where {path} goes in the container outside a <Project>/Content folder"))?;
+    let project = project_path.rsplit('/').next().filter(|p| !p.is_empty() && *p != "." && *p != "..")
+        .ok_or(format!("Unable to find the project folder that {path}'s Content folder is in"))?;
+    Ok(format!("{project}/Content/{content_path}"))
+}
+
+/// The package path of a file inside a Content folder, e.g. .../Content/Data/MyTable.uasset is /Game/Data/MyTable
+fn package_name_from_path(path: &str) -> Result<String, Box<dyn Error>> {
+    let path = path.replace('\\', "/");
+    let (_, content_path) = path.rsplit_once("/Content/").ok_or(format!("Unable to find the package name of {path} outside a Content folder - pass one with --package-name"))?;
+    let content_path = content_path.strip_suffix(".uasset").unwrap_or(content_path);
+    Ok(format!("/Game/{content_path}"))
+}
@@ -0,0 +1,691 @@
+use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use crate::compression;
+use crate::error::ParseError;
+use crate::iostore_container::path_matches;
+use crate::legacy_uasset::{read_fstring, write_fstring};
+use crate::sha1::sha1;
+use std::{error::Error, io::{Cursor, Read, Seek, SeekFrom, Write}};
+
+const PAK_MAGIC: u32 = 0x5A6F12E1;
+/// FPakInfo versions that change the layout.  Older versions aren't supported.
+const PAK_VERSION_FNAME_BASED_COMPRESSION_METHOD: i32 = 8;
+const PAK_VERSION_FROZEN_INDEX: i32 = 9;
+const PAK_VERSION_PATH_HASH_INDEX: i32 = 10;
+const PAK_VERSION_FNV64_BUG_FIX: i32 = 11;
+const COMPRESSION_METHOD_NAME_LEN: usize = 32;
+const MAX_COMPRESSION_METHODS: usize = 5;
+/// UE4.22 wrote version 8 with room for only 4 compression methods, and a single byte method index in its entries
+const MAX_COMPRESSION_METHODS_4_22: usize = 4;
+/// FPakInfo up to the compression method names
+const PAK_INFO_BASE_SIZE: usize = 16 + 1 + 4 + 4 + 8 + 8 + 20;
+
+/// An FPakEntry - where a file's data is in the pak, and how it's compressed.  Compression blocks are relative to the
+/// entry's offset, where a copy of the entry is serialized ahead of the data.
+#[derive(Clone, PartialEq, Debug)]
+struct PakEntry {
+    offset: u64,
+    size: u64,
+    uncompressed_size: u64,
+    /// 0 for uncompressed files, otherwise 1 + an index into the pak's method names
+    method_index: u32,
+    compression_blocks: Vec<(u64, u64)>,
+    encrypted: bool,
+    compression_block_size: u32,
+}
+
+impl PakEntry {
+    fn from_buffer<R: Read>(reader: &mut R, byte_method_index: bool) -> Result<Self, Box<dyn Error>> {
+        let offset = reader.read_u64::<LE>()?;
+        let size = reader.read_u64::<LE>()?;
+        let uncompressed_size = reader.read_u64::<LE>()?;
+        let method_index = match byte_method_index {
+            true => reader.read_u8()? as u32,
+            false => reader.read_u32::<LE>()?,
+        };
+        let mut _hash = [0; 20];
+        reader.read_exact(&mut _hash)?;
+        let mut compression_blocks = vec![];
+        if method_index != 0 {
+            let block_count = reader.read_i32::<LE>()?;
+            for _ in 0..block_count {
+                compression_blocks.push((reader.read_u64::<LE>()?, reader.read_u64::<LE>()?));
+            }
+        }
+        let flags = reader.read_u8()?;
+        let compression_block_size = reader.read_u32::<LE>()?;
+        Ok(Self {
+            offset,
+            size,
+            uncompressed_size,
+            method_index,
+            compression_blocks,
+            encrypted: flags & 0x1 != 0,
+            compression_block_size,
+        })
+    }
+
+    /// Decodes an entry from the bit-packed EncodedPakEntries of v10+ indices (FPakFile::DecodePakEntry)
+    fn decode<R: Read>(reader: &mut R, byte_method_index: bool) -> Result<Self, Box<dyn Error>> {
+        let value = reader.read_u32::<LE>()?;
+        let compression_block_size = match value & 0x3f {
+            0x3f => reader.read_u32::<LE>()?,
+            size => size << 11,
+        };
+        let method_index = (value >> 23) & 0x3f;
+        let mut read_size = |is_32_bit: bool| match is_32_bit {
+            true => reader.read_u32::<LE>().map(u64::from),
+            false => reader.read_u64::<LE>(),
+        };
+        let offset = read_size(value & (1 << 31) != 0)?;
+        let uncompressed_size = read_size(value & (1 << 30) != 0)?;
+        let size = match method_index {
+            0 => uncompressed_size,
+            _ => read_size(value & (1 << 29) != 0)?,
+        };
+        let encrypted = value & (1 << 22) != 0;
+        let block_count = (value >> 6) & 0xffff;
+
+        let mut entry = Self {
+            offset,
+            size,
+            uncompressed_size,
+            method_index,
+            compression_blocks: vec![],
+            encrypted,
+            compression_block_size,
+        };
+        // Only the block sizes are stored, since the blocks follow the entry one after another
+        let mut block_start = entry.serialized_size(byte_method_index, block_count as usize);
+        if block_count == 1 && !encrypted {
+            entry.compression_blocks.push((block_start, block_start + size));
+        } else {
+            for _ in 0..block_count {
+                let block_size = reader.read_u32::<LE>()? as u64;
+                entry.compression_blocks.push((block_start, block_start + block_size));
+                block_start += match encrypted {
+                    true => block_size.next_multiple_of(16),
+                    false => block_size,
+                };
+            }
+        }
+        Ok(entry)
+    }
+
+    /// Size of the copy of the entry that's ahead of the file's data
+    fn serialized_size(&self, byte_method_index: bool, block_count: usize) -> u64 {
+        let mut size = 8 + 8 + 8 + 20 + 1 + 4;
+        size += if byte_method_index { 1 } else { 4 };
+        if self.method_index != 0 {
+            size += 4 + block_count as u64 * 16;
+        }
+        size
+    }
+}
+
+/// A .pak file's index, used to find and read files out of it
+pub struct PakFile {
+    version: i32,
+    byte_method_index: bool,
+    compression_methods: Vec<String>,
+    mount_point: String,
+    path_hash_seed: u64,
+    /// Path of each file (starting with the mount point) and its entry, when the pak has a full directory index
+    files: Vec<(String, PakEntry)>,
+    /// Entries by path hash, when the pak only has a path hash index
+    path_hashes: Vec<(u64, PakEntry)>,
+    /// Whether the index listed every file's path, filling in `files`
+    has_directory_index: bool,
+}
+
+impl PakFile {
+    /// Reads the footer and index of a .pak file, versions 8 to 11
+    pub fn from_buffer<R: Read + Seek>(reader: &mut R) -> Result<Self, Box<dyn Error>> {
+        let file_size = reader.seek(SeekFrom::End(0))?;
+        // The footer's size depends on the version it's meant to give, so each possible size is tried
+        let footer_sizes = [
+            (PAK_INFO_BASE_SIZE + COMPRESSION_METHOD_NAME_LEN * MAX_COMPRESSION_METHODS, false),
+            (PAK_INFO_BASE_SIZE + 1 + COMPRESSION_METHOD_NAME_LEN * MAX_COMPRESSION_METHODS, true),
+            (PAK_INFO_BASE_SIZE + COMPRESSION_METHOD_NAME_LEN * MAX_COMPRESSION_METHODS_4_22, false),
+        ];
+        let mut footer = None;
+        for (footer_size, frozen_index) in footer_sizes {
+            if (footer_size as u64) > file_size {
+                continue;
+            }
+            reader.seek(SeekFrom::End(-(footer_size as i64) + 17))?;
+            let magic = reader.read_u32::<LE>()?;
+            let version = reader.read_i32::<LE>()?;
+            let method_count = (footer_size - PAK_INFO_BASE_SIZE - frozen_index as usize) / COMPRESSION_METHOD_NAME_LEN;
+            let version_matches = match (frozen_index, method_count) {
+                (true, _) => version == PAK_VERSION_FROZEN_INDEX,
+                (false, MAX_COMPRESSION_METHODS_4_22) => version == PAK_VERSION_FNAME_BASED_COMPRESSION_METHOD,
+                (false, _) => version != PAK_VERSION_FROZEN_INDEX,
+            };
+            if magic == PAK_MAGIC && version_matches {
+                footer = Some((footer_size, frozen_index, method_count));
+                break;
+            }
+        }
+        let (footer_size, frozen_index, method_count) = footer.ok_or("Not a .pak file, or a version older than 8 - the footer magic is missing")?;
+
+        reader.seek(SeekFrom::End(-(footer_size as i64)))?;
+        let mut _encryption_key_guid = [0; 16];
+        reader.read_exact(&mut _encryption_key_guid)?;
+        let encrypted_index = reader.read_u8()? != 0;
+        reader.read_u32::<LE>()?;
+        let version = reader.read_i32::<LE>()?;
+        let index_offset = reader.read_u64::<LE>()?;
+        let index_size = reader.read_u64::<LE>()?;
+        let mut _index_hash = [0; 20];
+        reader.read_exact(&mut _index_hash)?;
+        if frozen_index && reader.read_u8()? != 0 {
+            Err("Frozen pak indices aren't supported")?;
+        }
+        let mut compression_methods = vec![];
+        for _ in 0..method_count {
+            let mut name = [0; COMPRESSION_METHOD_NAME_LEN];
+            reader.read_exact(&mut name)?;
+            compression_methods.push(name.iter().take_while(|b| **b != 0).map(|b| *b as char).collect());
+        }
+
+        if !(PAK_VERSION_FNAME_BASED_COMPRESSION_METHOD..=PAK_VERSION_FNV64_BUG_FIX).contains(&version) {
+            Err(format!("Pak version {version} isn't supported - only versions 8 to 11 are"))?;
+        }
+        if encrypted_index {
+            Err("Encrypted pak indices aren't supported")?;
+        }
+
+        let mut pak = Self {
+            version,
+            byte_method_index: method_count == MAX_COMPRESSION_METHODS_4_22,
+            compression_methods,
+            mount_point: String::new(),
+            path_hash_seed: 0,
+            files: vec![],
+            path_hashes: vec![],
+            has_directory_index: false,
+        };
+        let index = read_at(reader, index_offset, index_size)?;
+        match version >= PAK_VERSION_PATH_HASH_INDEX {
+            true => pak.read_index(reader, &mut Cursor::new(index))?,
+            false => pak.read_legacy_index(&mut Cursor::new(index))?,
+        }
+        pak.files.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(pak)
+    }
+
+    /// Reads the index of paks before v10 - the mount point, then every file's path and entry
+    fn read_legacy_index<R: Read + Seek>(&mut self, index: &mut R) -> Result<(), Box<dyn Error>> {
+        self.mount_point = read_fstring::<_, LE>(index)?.0;
+        let entry_count = index.read_i32::<LE>()?;
+        for _ in 0..entry_count {
+            let (path, _) = read_fstring::<_, LE>(index)?;
+            let entry = PakEntry::from_buffer(index, self.byte_method_index)?;
+            self.files.push((format!("{}{path}", self.mount_point), entry));
+        }
+        self.has_directory_index = true;
+        Ok(())
+    }
+
+    /// Reads a v10+ primary index, and whichever of the full directory index and path hash index it points to.  The
+    /// full directory index is preferred, since file names can't be found from the path hash index.
+    fn read_index<P: Read + Seek, R: Read + Seek>(&mut self, pak: &mut P, index: &mut R) -> Result<(), Box<dyn Error>> {
+        self.mount_point = read_fstring::<_, LE>(index)?.0;
+        let _entry_count = index.read_i32::<LE>()?;
+        self.path_hash_seed = index.read_u64::<LE>()?;
+        let mut read_secondary_index_location = || -> Result<Option<(u64, u64)>, Box<dyn Error>> {
+            match index.read_u32::<LE>()? {
+                0 => Ok(None),
+                _ => {
+                    let location = (index.read_u64::<LE>()?, index.read_u64::<LE>()?);
+                    let mut _hash = [0; 20];
+                    index.read_exact(&mut _hash)?;
+                    Ok(Some(location))
+                }
+            }
+        };
+        let path_hash_index = read_secondary_index_location()?;
+        let directory_index = read_secondary_index_location()?;
+
+        let encoded_entries_size = u64::try_from(index.read_i32::<LE>()?).map_err(|_| "Invalid encoded pak entries size")?;
+        // Read through read_at, so a size past the end of the index is an error rather than an allocation
+        let encoded_entries_offset = index.stream_position()?;
+        let encoded_entries = read_at(index, encoded_entries_offset, encoded_entries_size)?;
+        let file_count = index.read_i32::<LE>()?;
+        let mut unencoded_entries = vec![];
+        for _ in 0..file_count {
+            unencoded_entries.push(PakEntry::from_buffer(index, self.byte_method_index)?);
+        }
+        // An FPakEntryLocation - an offset into the encoded entries, or -(index + 1) into the unencoded ones
+        let entry = |location: i32| -> Result<PakEntry, Box<dyn Error>> {
+            match location {
+                0.. => {
+                    let mut reader = Cursor::new(&encoded_entries);
+                    reader.set_position(location as u64);
+                    PakEntry::decode(&mut reader, self.byte_method_index)
+                },
+                _ => Ok(unencoded_entries.get((-(location as i64) - 1) as usize).ok_or(format!("Invalid pak entry location {location}"))?.clone()),
+            }
+        };
+
+        if let Some((offset, size)) = directory_index {
+            let mut directory_index = Cursor::new(read_at(pak, offset, size)?);
+            let directory_count = directory_index.read_i32::<LE>()?;
+            for _ in 0..directory_count {
+                let (directory, _) = read_fstring::<_, LE>(&mut directory_index)?;
+                let directory = directory.trim_start_matches('/');
+                let file_count = directory_index.read_i32::<LE>()?;
+                for _ in 0..file_count {
+                    let (file, _) = read_fstring::<_, LE>(&mut directory_index)?;
+                    let location = directory_index.read_i32::<LE>()?;
+                    self.files.push((format!("{}{directory}{file}", self.mount_point), entry(location)?));
+                }
+            }
+            self.has_directory_index = true;
+        } else if let Some((offset, size)) = path_hash_index {
+            let mut path_hash_index = Cursor::new(read_at(pak, offset, size)?);
+            let hash_count = path_hash_index.read_i32::<LE>()?;
+            for _ in 0..hash_count {
+                let hash = path_hash_index.read_u64::<LE>()?;
+                let location = path_hash_index.read_i32::<LE>()?;
+                self.path_hashes.push((hash, entry(location)?));
+            }
+        }
+        Ok(())
+    }
+
+    /// Paths of the files in the pak, starting with its mount point.  Empty if the pak has no full directory index.
+    pub fn files(&self) -> impl Iterator<Item = &str> {
+        self.files.iter().map(|(path, _)| path.as_str())
+    }
+
+    /// Paths of the packages in the pak - .uasset and .umap files, rather than e.g. .uexp or bulk data
+    pub fn packages(&self) -> impl Iterator<Item = &str> {
+        self.files().filter(|path| path.ends_with(".uasset") || path.ends_with(".umap"))
+    }
+
+    /// Whether the pak's file names can be listed, rather than only looked up by path
+    pub fn has_directory_index(&self) -> bool {
+        self.has_directory_index
+    }
+
+    /// Reads a file out of the pak.  The path can be given with or without the mount point.
+    pub fn read_file<R: Read + Seek>(&self, path: &str, reader: &mut R) -> Result<Vec<u8>, Box<dyn Error>> {
+        let entry = match self.has_directory_index() {
+            true => self.files.iter().find(|(file, _)| path_matches(file, path)).map(|(_, entry)| entry),
+            false => {
+                if self.version < PAK_VERSION_FNV64_BUG_FIX {
+                    Err(format!("Files in a v{} pak without a full directory index can't be found by path", self.version))?;
+                }
+                let mount_point = self.mount_point.trim_start_matches("../").trim_start_matches('/');
+                let relative_path = path.trim_start_matches("../").trim_start_matches('/').strip_prefix(mount_point)
+                    .ok_or(format!("{path} isn't under the pak's mount point {}", self.mount_point))?;
+                let hash = path_hash(relative_path, self.path_hash_seed);
+                self.path_hashes.iter().find(|(h, _)| *h == hash).map(|(_, entry)| entry)
+            }
+        };
+        let entry = entry.ok_or(format!("{path} isn't in the pak"))?;
+        self.read_entry(entry, reader).map_err(|e| format!("Unable to read {path} from the pak: {e}").into())
+    }
+
+    fn read_entry<R: Read + Seek>(&self, entry: &PakEntry, reader: &mut R) -> Result<Vec<u8>, Box<dyn Error>> {
+        if entry.encrypted {
+            Err("Encrypted pak entries aren't supported")?;
+        }
+        if entry.method_index == 0 {
+            let data_offset = entry.offset + entry.serialized_size(self.byte_method_index, 0);
+            return read_at(reader, data_offset, entry.uncompressed_size);
+        }
+
+        let method = self.compression_methods.get(entry.method_index as usize - 1)
+            .ok_or(format!("Invalid compression method index {}", entry.method_index))?;
+        let mut bytes = vec![];
+        for (i, (start, end)) in entry.compression_blocks.iter().enumerate() {
+            let compressed = read_at(reader, entry.offset + start, end.checked_sub(*start).ok_or("Invalid compression block")?)?;
+            let uncompressed_size = match i + 1 == entry.compression_blocks.len() {
+                true => entry.uncompressed_size.checked_sub(bytes.len() as u64)
+                    .filter(|size| *size <= entry.compression_block_size as u64)
+                    .ok_or(ParseError::invalid(entry.offset + start, format!("Compression blocks don't hold the entry's {} bytes", entry.uncompressed_size)))?,
+                false => entry.compression_block_size as u64,
+            };
+            bytes.extend(compression::decompress(method, &compressed, uncompressed_size as usize)?);
+        }
+        if bytes.len() as u64 != entry.uncompressed_size {
+            Err(format!("Decompressed to {} bytes, expected {}", bytes.len(), entry.uncompressed_size))?;
+        }
+        Ok(bytes)
+    }
+}
+
+/// FPakFile::HashPath from v11 - FNV-1a 64 of the lowercase UTF-16 path under the mount point, with the seed added
+/// to the offset basis
+fn path_hash(path: &str, seed: u64) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64.wrapping_add(seed);
+    for byte in path.to_lowercase().encode_utf16().flat_map(u16::to_le_bytes) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+fn read_at<R: Read + Seek>(reader: &mut R, offset: u64, size: u64) -> Result<Vec<u8>, Box<dyn Error>> {
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut bytes = vec![];
+    reader.take(size).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != size {
+        Err(format!("Unexpected end of file reading {size} bytes at 0x{offset:x}"))?;
+    }
+    Ok(bytes)
+}
+
+/// Writes a .pak with no files, as goes alongside an IoStore container - the engine only mounts a .utoc if there's a
+/// .pak of the same name.  Both the path hash and full directory indices are written, each empty apart from the root.
+pub(crate) fn write_companion_pak<W: Write>(writer: &mut W, mount_point: &str) -> Result<(), Box<dyn Error>> {
+    // The seed only changes how file paths are hashed, and there are none
+    let path_hash_seed = 0u64;
+
+    let mut directory_index = vec![];
+    directory_index.write_i32::<LE>(1)?;
+    write_fstring::<_, LE>(&mut directory_index, "/")?;
+    directory_index.write_i32::<LE>(0)?;
+
+    let mut path_hash_index = vec![];
+    path_hash_index.write_i32::<LE>(0)?;
+    path_hash_index.write_all(&directory_index)?; // Pruned directory index
+
+    let primary_index_size = {
+        let mut primary_index = vec![];
+        write_primary_index(&mut primary_index, mount_point, path_hash_seed, (0, 0, [0; 20]), (0, 0, [0; 20]))?;
+        primary_index.len() as u64
+    };
+    let path_hash_index_offset = primary_index_size;
+    let directory_index_offset = path_hash_index_offset + path_hash_index.len() as u64;
+
+    let mut primary_index = vec![];
+    write_primary_index(
+        &mut primary_index,
+        mount_point,
+        path_hash_seed,
+        (path_hash_index_offset, path_hash_index.len() as u64, sha1(&path_hash_index)),
+        (directory_index_offset, directory_index.len() as u64, sha1(&directory_index)),
+    )?;
+    writer.write_all(&primary_index)?;
+    writer.write_all(&path_hash_index)?;
+    writer.write_all(&directory_index)?;
+
+    // FPakInfo
+    writer.write_all(&[0; 16])?; // Encryption key guid
+    writer.write_u8(0)?; // Encrypted index
+    writer.write_u32::<LE>(PAK_MAGIC)?;
+    writer.write_i32::<LE>(PAK_VERSION_FNV64_BUG_FIX)?;
+    writer.write_u64::<LE>(0)?; // Index offset
+    writer.write_u64::<LE>(primary_index.len() as u64)?;
+    writer.write_all(&sha1(&primary_index))?;
+    writer.write_all(&[0; COMPRESSION_METHOD_NAME_LEN * MAX_COMPRESSION_METHODS])?;
+    Ok(())
+}
+
+/// Writes the primary index of a pak with no files, pointing at its secondary indices by (offset, size, hash)
+fn write_primary_index<W: Write>(writer: &mut W, mount_point: &str, path_hash_seed: u64, path_hash_index: (u64, u64, [u8; 20]), directory_index: (u64, u64, [u8; 20])) -> Result<(), Box<dyn Error>> {
+    write_fstring::<_, LE>(writer, mount_point)?;
+    writer.write_i32::<LE>(0)?; // Entry count
+    writer.write_u64::<LE>(path_hash_seed)?;
+    for (offset, size, hash) in [path_hash_index, directory_index] {
+        writer.write_u32::<LE>(1)?;
+        writer.write_u64::<LE>(offset)?;
+        writer.write_u64::<LE>(size)?;
+        writer.write_all(&hash)?;
+    }
+    writer.write_i32::<LE>(0)?; // Encoded entries
+    writer.write_i32::<LE>(0)?; // Entries that couldn't be encoded
+    Ok(())
+}
+
+#[allow(dead_code)]
+#[allow(unused_imports)]
+mod test {
+    use byteorder::{WriteBytesExt, LE};
+    use flate2::{write::ZlibEncoder, Compression};
+    use std::io::{Cursor, Write};
+
+    use super::{path_hash, write_companion_pak, PakEntry, PakFile, PAK_MAGIC};
+    use crate::legacy_uasset::write_fstring;
+
+    const BLOCK_SIZE: usize = 0x20;
+
+    fn write_entry<W: Write>(writer: &mut W, entry: &PakEntry, byte_method_index: bool) {
+        writer.write_u64::<LE>(entry.offset).unwrap();
+        writer.write_u64::<LE>(entry.size).unwrap();
+        writer.write_u64::<LE>(entry.uncompressed_size).unwrap();
+        match byte_method_index {
+            true => writer.write_u8(entry.method_index as u8).unwrap(),
+            false => writer.write_u32::<LE>(entry.method_index).unwrap(),
+        }
+        writer.write_all(&[0; 20]).unwrap();
+        if entry.method_index != 0 {
+            writer.write_i32::<LE>(entry.compression_blocks.len() as i32).unwrap();
+            for (start, end) in &entry.compression_blocks {
+                writer.write_u64::<LE>(*start).unwrap();
+                writer.write_u64::<LE>(*end).unwrap();
+            }
+        }
+        writer.write_u8(0).unwrap();
+        writer.write_u32::<LE>(entry.compression_block_size).unwrap();
+    }
+
+    /// Writes an entry in the v10+ bit-packed form, with 32 bit sizes
+    fn encode_entry<W: Write>(writer: &mut W, entry: &PakEntry) {
+        let mut value = (1 << 31) | (1 << 30) | (1 << 29) | (entry.method_index << 23) | ((entry.compression_blocks.len() as u32) << 6);
+        value |= entry.compression_block_size >> 11;
+        writer.write_u32::<LE>(value).unwrap();
+        writer.write_u32::<LE>(entry.offset as u32).unwrap();
+        writer.write_u32::<LE>(entry.uncompressed_size as u32).unwrap();
+        if entry.method_index != 0 {
+            writer.write_u32::<LE>(entry.size as u32).unwrap();
+            if entry.compression_blocks.len() > 1 {
+                for (start, end) in &entry.compression_blocks {
+                    writer.write_u32::<LE>((end - start) as u32).unwrap();
+                }
+            }
+        }
+    }
+
+    fn get_test_files() -> Vec<(&'static str, Vec<u8>)> {
+        vec![
+            ("Data/DT_Test.uasset", (0..0x30).collect()),
+            ("Data/DT_Test.uexp", (0..0x50).map(|i| 0xff - i).collect()),
+            ("Maps/Test.umap", vec![7; 0x08]),
+        ]
+    }
+
+    /// A pak mounted at ../../../MyGame/Content/ holding the test files.  The .uexp is zlib compressed in blocks, and
+    /// the v10+ index keeps it unencoded, with the other entries encoded.
+    fn get_test_pak(version: i32, full_directory_index: bool) -> Vec<u8> {
+        let byte_method_index = false;
+        let mut pak = vec![];
+        let mut entries = vec![];
+        for (path, data) in get_test_files() {
+            let offset = pak.len() as u64;
+            let compressed = path.ends_with(".uexp");
+            let mut entry = PakEntry {
+                offset,
+                size: data.len() as u64,
+                uncompressed_size: data.len() as u64,
+                method_index: compressed as u32,
+                compression_blocks: vec![],
+                encrypted: false,
+                compression_block_size: if compressed { BLOCK_SIZE as u32 } else { 0 },
+            };
+            let mut blocks = vec![];
+            if compressed {
+                let mut block_start = entry.serialized_size(byte_method_index, data.chunks(BLOCK_SIZE).count());
+                for block in data.chunks(BLOCK_SIZE) {
+                    let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+                    encoder.write_all(block).unwrap();
+                    let block = encoder.finish().unwrap();
+                    entry.compression_blocks.push((block_start, block_start + block.len() as u64));
+                    block_start += block.len() as u64;
+                    blocks.extend(block);
+                }
+                entry.size = blocks.len() as u64;
+            } else {
+                blocks = data;
+            }
+            write_entry(&mut pak, &PakEntry { offset: 0, ..entry.clone() }, byte_method_index);
+            pak.extend(blocks);
+            entries.push((path, entry));
+        }
+
+        let index_offset = pak.len() as u64;
+        let mut index = vec![];
+        write_fstring::<_, LE>(&mut index, "../../../MyGame/Content/").unwrap();
+        index.write_i32::<LE>(entries.len() as i32).unwrap();
+        if version < 10 {
+            for (path, entry) in &entries {
+                write_fstring::<_, LE>(&mut index, path).unwrap();
+                write_entry(&mut index, entry, byte_method_index);
+            }
+        } else {
+            let seed = 0x1234;
+            let mut encoded_entries = vec![];
+            let mut locations = vec![];
+            for (_, entry) in &entries {
+                if entry.method_index == 0 {
+                    locations.push(encoded_entries.len() as i32);
+                    encode_entry(&mut encoded_entries, entry);
+                } else {
+                    locations.push(-1);
+                }
+            }
+
+            let mut secondary_index = vec![];
+            if full_directory_index {
+                secondary_index.write_i32::<LE>(2).unwrap();
+                for directory in ["Data/", "Maps/"] {
+                    write_fstring::<_, LE>(&mut secondary_index, directory).unwrap();
+                    let files: Vec<_> = entries.iter().zip(&locations).filter(|((path, _), _)| path.starts_with(directory)).collect();
+                    secondary_index.write_i32::<LE>(files.len() as i32).unwrap();
+                    for ((path, _), location) in files {
+                        write_fstring::<_, LE>(&mut secondary_index, &path[directory.len()..]).unwrap();
+                        secondary_index.write_i32::<LE>(*location).unwrap();
+                    }
+                }
+            } else {
+                secondary_index.write_i32::<LE>(entries.len() as i32).unwrap();
+                for ((path, _), location) in entries.iter().zip(&locations) {
+                    secondary_index.write_u64::<LE>(path_hash(path, seed)).unwrap();
+                    secondary_index.write_i32::<LE>(*location).unwrap();
+                }
+                secondary_index.write_i32::<LE>(0).unwrap();
+            }
+
+            index.write_u64::<LE>(seed).unwrap();
+            // Only the index that's written is pointed to - the other is skipped
+            let index_size = index.len() + 4 + (8 + 8 + 20) + 4 + 4 + encoded_entries.len() + 4 + {
+                let mut unencoded = vec![];
+                write_entry(&mut unencoded, &entries[1].1, byte_method_index);
+                unencoded.len()
+            };
+            let secondary_index_offset = index_offset + index_size as u64;
+            for has_index in [!full_directory_index, full_directory_index] {
+                index.write_u32::<LE>(has_index as u32).unwrap();
+                if has_index {
+                    index.write_u64::<LE>(secondary_index_offset).unwrap();
+                    index.write_u64::<LE>(secondary_index.len() as u64).unwrap();
+                    index.write_all(&[0; 20]).unwrap();
+                }
+            }
+            index.write_i32::<LE>(encoded_entries.len() as i32).unwrap();
+            index.write_all(&encoded_entries).unwrap();
+            index.write_i32::<LE>(1).unwrap();
+            write_entry(&mut index, &entries[1].1, byte_method_index);
+            assert_eq!(index.len(), index_size);
+            index.extend(secondary_index);
+        }
+        let index_size = index.len();
+        pak.extend(index);
+
+        pak.write_all(&[0; 16]).unwrap();
+        pak.write_u8(0).unwrap();
+        pak.write_u32::<LE>(PAK_MAGIC).unwrap();
+        pak.write_i32::<LE>(version).unwrap();
+        pak.write_u64::<LE>(index_offset).unwrap();
+        pak.write_u64::<LE>(index_size as u64).unwrap();
+        pak.write_all(&[0; 20]).unwrap();
+        if version == 9 {
+            pak.write_u8(0).unwrap();
+        }
+        let mut method_name = b"Zlib".to_vec();
+        method_name.resize(32 * 5, 0);
+        pak.write_all(&method_name).unwrap();
+        pak
+    }
+
+    fn assert_test_files_read(pak: &PakFile, bytes: Vec<u8>) {
+        let mut reader = Cursor::new(bytes);
+        for (path, data) in get_test_files() {
+            assert_eq!(pak.read_file(&format!("MyGame/Content/{path}"), &mut reader).unwrap(), data, "{path}");
+        }
+        assert!(pak.read_file("MyGame/Content/Data/Missing.uasset", &mut reader).is_err());
+    }
+
+    #[test]
+    fn legacy_pak_index_is_read() {
+        for version in [8, 9] {
+            let bytes = get_test_pak(version, true);
+            let pak = PakFile::from_buffer(&mut Cursor::new(&bytes)).unwrap();
+            assert_eq!(pak.packages().collect::<Vec<_>>(), vec!["../../../MyGame/Content/Data/DT_Test.uasset", "../../../MyGame/Content/Maps/Test.umap"]);
+            assert_test_files_read(&pak, bytes);
+        }
+    }
+
+    #[test]
+    fn full_directory_index_is_read() {
+        for version in [10, 11] {
+            let bytes = get_test_pak(version, true);
+            let pak = PakFile::from_buffer(&mut Cursor::new(&bytes)).unwrap();
+            assert!(pak.has_directory_index());
+            assert_eq!(pak.files().count(), 3);
+            assert_eq!(pak.packages().collect::<Vec<_>>(), vec!["../../../MyGame/Content/Data/DT_Test.uasset", "../../../MyGame/Content/Maps/Test.umap"]);
+            assert_test_files_read(&pak, bytes);
+        }
+    }
+
+    #[test]
+    fn path_hash_index_is_read() {
+        let bytes = get_test_pak(11, false);
+        let pak = PakFile::from_buffer(&mut Cursor::new(&bytes)).unwrap();
+        assert!(!pak.has_directory_index());
+        assert_eq!(pak.files().count(), 0);
+        assert_test_files_read(&pak, bytes);
+    }
+
+    #[test]
+    fn oversized_compression_blocks_are_errors() {
+        let bytes = get_test_pak(11, true);
+        let pak = PakFile::from_buffer(&mut Cursor::new(&bytes)).unwrap();
+        let (_, entry) = pak.files.iter().find(|(path, _)| path.ends_with(".uexp")).unwrap();
+        let entry = PakEntry { uncompressed_size: BLOCK_SIZE as u64, ..entry.clone() };
+        assert!(pak.read_entry(&entry, &mut Cursor::new(&bytes)).is_err());
+    }
+
+    #[test]
+    fn oversized_encoded_entries_are_errors() {
+        let mut bytes = get_test_pak(11, true);
+        let footer = bytes.len() - 32 * 5 - 20 - 8 - 8;
+        let index_offset = u64::from_le_bytes(bytes[footer..footer + 8].try_into().unwrap()) as usize;
+        // Past the mount point, entry count, seed and the two secondary index locations
+        let encoded_entries_size = index_offset + (4 + 25) + 4 + 8 + 4 + (4 + 8 + 8 + 20);
+        bytes[encoded_entries_size..encoded_entries_size + 4].copy_from_slice(&i32::MAX.to_le_bytes());
+        assert!(PakFile::from_buffer(&mut Cursor::new(&bytes)).is_err());
+    }
+
+    #[test]
+    fn companion_pak_is_empty() {
+        let mut bytes = vec![];
+        write_companion_pak(&mut bytes, "../../../").unwrap();
+        let pak = PakFile::from_buffer(&mut Cursor::new(bytes)).unwrap();
+        assert!(pak.has_directory_index());
+        assert_eq!(pak.files().count(), 0);
+    }
+}
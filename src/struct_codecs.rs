@@ -0,0 +1,272 @@
+use byteorder::WriteBytesExt;
+use crate::error::{EncodeError, ParseError, ParseRead};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, error::Error, io::{Read, Seek, Write}, sync::{OnceLock, RwLock}};
+
+/// Primitive types that native struct fields are built from.  Schema files name them by their Rust type.
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize)]
+pub enum StructFieldType {
+    #[serde(rename = "u8")]  UInt8,
+    #[serde(rename = "i8")]  Int8,
+    #[serde(rename = "u16")] UInt16,
+    #[serde(rename = "i16")] Int16,
+    #[serde(rename = "i32")] Int32,
+    #[serde(rename = "u32")] UInt32,
+    #[serde(rename = "i64")] Int64,
+    #[serde(rename = "u64")] UInt64,
+    #[serde(rename = "f32")] Float,
+    #[serde(rename = "f64")] Double,
+}
+
+impl StructFieldType {
+    pub fn byte_len(&self) -> usize {
+        match self {
+            Self::UInt8 | Self::Int8 => 1,
+            Self::UInt16 | Self::Int16 => 2,
+            Self::Int32 | Self::UInt32 | Self::Float => 4,
+            Self::Int64 | Self::UInt64 | Self::Double => 8,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum StructFieldValue {
+    UInt8(u8),
+    Int8(i8),
+    UInt16(u16),
+    Int16(i16),
+    Int32(i32),
+    UInt32(u32),
+    Int64(i64),
+    UInt64(u64),
+    Float(f32),
+    Double(f64),
+}
+
+impl StructFieldValue {
+    pub fn from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R, r#type: StructFieldType) -> Result<Self, ParseError> {
+        Ok(match r#type {
+            StructFieldType::UInt8 => Self::UInt8(reader.parse_u8()?),
+            StructFieldType::Int8 => Self::Int8(reader.parse_i8()?),
+            StructFieldType::UInt16 => Self::UInt16(reader.parse_u16::<E>()?),
+            StructFieldType::Int16 => Self::Int16(reader.parse_i16::<E>()?),
+            StructFieldType::Int32 => Self::Int32(reader.parse_i32::<E>()?),
+            StructFieldType::UInt32 => Self::UInt32(reader.parse_u32::<E>()?),
+            StructFieldType::Int64 => Self::Int64(reader.parse_i64::<E>()?),
+            StructFieldType::UInt64 => Self::UInt64(reader.parse_u64::<E>()?),
+            StructFieldType::Float => Self::Float(reader.parse_f32::<E>()?),
+            StructFieldType::Double => Self::Double(reader.parse_f64::<E>()?),
+        })
+    }
+
+    pub fn to_bytes<W: Write, E: byteorder::ByteOrder>(self, writer: &mut W) -> Result<usize, EncodeError> {
+        match self {
+            Self::UInt8(val) => writer.write_u8(val)?,
+            Self::Int8(val) => writer.write_i8(val)?,
+            Self::UInt16(val) => writer.write_u16::<E>(val)?,
+            Self::Int16(val) => writer.write_i16::<E>(val)?,
+            Self::Int32(val) => writer.write_i32::<E>(val)?,
+            Self::UInt32(val) => writer.write_u32::<E>(val)?,
+            Self::Int64(val) => writer.write_i64::<E>(val)?,
+            Self::UInt64(val) => writer.write_u64::<E>(val)?,
+            Self::Float(val) => writer.write_f32::<E>(val)?,
+            Self::Double(val) => writer.write_f64::<E>(val)?,
+        }
+        Ok(self.get_type().byte_len())
+    }
+
+    pub fn from_string(val: &str, r#type: StructFieldType) -> Result<Self, Box<dyn Error>> {
+        let val = val.trim();
+        Ok(match r#type {
+            StructFieldType::UInt8 => Self::UInt8(val.parse()?),
+            StructFieldType::Int8 => Self::Int8(val.parse()?),
+            StructFieldType::UInt16 => Self::UInt16(val.parse()?),
+            StructFieldType::Int16 => Self::Int16(val.parse()?),
+            StructFieldType::Int32 => Self::Int32(val.parse()?),
+            StructFieldType::UInt32 => Self::UInt32(val.parse()?),
+            StructFieldType::Int64 => Self::Int64(val.parse()?),
+            StructFieldType::UInt64 => Self::UInt64(val.parse()?),
+            StructFieldType::Float => Self::Float(val.parse()?),
+            StructFieldType::Double => Self::Double(val.parse()?),
+        })
+    }
+
+    pub fn get_type(self) -> StructFieldType {
+        match self {
+            Self::UInt8(_) => StructFieldType::UInt8,
+            Self::Int8(_) => StructFieldType::Int8,
+            Self::UInt16(_) => StructFieldType::UInt16,
+            Self::Int16(_) => StructFieldType::Int16,
+            Self::Int32(_) => StructFieldType::Int32,
+            Self::UInt32(_) => StructFieldType::UInt32,
+            Self::Int64(_) => StructFieldType::Int64,
+            Self::UInt64(_) => StructFieldType::UInt64,
+            Self::Float(_) => StructFieldType::Float,
+            Self::Double(_) => StructFieldType::Double,
+        }
+    }
+}
+
+impl std::fmt::Display for StructFieldValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UInt8(val) => write!(f, "{val}"),
+            Self::Int8(val) => write!(f, "{val}"),
+            Self::UInt16(val) => write!(f, "{val}"),
+            Self::Int16(val) => write!(f, "{val}"),
+            Self::Int32(val) => write!(f, "{val}"),
+            Self::UInt32(val) => write!(f, "{val}"),
+            Self::Int64(val) => write!(f, "{val}"),
+            Self::UInt64(val) => write!(f, "{val}"),
+            // Debug formatting keeps the decimal point on whole numbers, and round trips exactly
+            Self::Float(val) => write!(f, "{val:?}"),
+            Self::Double(val) => write!(f, "{val:?}"),
+        }
+    }
+}
+
+/// The binary layout of a native struct - one that serializes its fields one after another, instead of as tagged
+/// properties
+#[derive(Clone, PartialEq, Debug)]
+pub struct StructCodec {
+    pub fields: Vec<(String, StructFieldType)>,
+}
+
+impl StructCodec {
+    pub fn new(fields: &[(&str, StructFieldType)]) -> Self {
+        Self {
+            fields: fields.iter().map(|(name, r#type)| (name.to_string(), *r#type)).collect(),
+        }
+    }
+
+    pub fn byte_len(&self) -> usize {
+        self.fields.iter().map(|(_, r#type)| r#type.byte_len()).sum()
+    }
+
+    pub fn read_values<R: Read + Seek, E: byteorder::ByteOrder>(&self, reader: &mut R) -> Result<Vec<StructFieldValue>, ParseError> {
+        self.fields.iter().map(|(name, r#type)| StructFieldValue::from_buffer::<R,E>(reader, *r#type).map_err(|err| err.in_property(name))).collect()
+    }
+
+    pub fn write_values<W: Write, E: byteorder::ByteOrder>(values: &[StructFieldValue], writer: &mut W) -> Result<usize, EncodeError> {
+        values.iter().map(|v| v.to_bytes::<W,E>(writer)).sum()
+    }
+
+    /// Formats the values as `{x: 1.0, y: 2.0}`
+    pub fn format_values(&self, values: &[StructFieldValue]) -> String {
+        let fields: Vec<String> = self.fields.iter().zip(values).map(|((name, _), val)| format!("{name}: {val}")).collect();
+        format!("{{{}}}", fields.join(", "))
+    }
+
+    /// Reads values written by [`Self::format_values`].  Every field must be present, but they can be in any order.
+    pub fn parse_values(&self, struct_type: &str, val: &str) -> Result<Vec<StructFieldValue>, Box<dyn Error>> {
+        let val = val.trim();
+        let inner = val.strip_prefix('{').and_then(|v| v.strip_suffix('}')).ok_or(format!("{struct_type} should use format {{field: value, ...}}, but got {val}"))?;
+
+        let mut values: Vec<Option<StructFieldValue>> = vec![None; self.fields.len()];
+        for field in inner.split(',').filter(|f| !f.trim().is_empty()) {
+            let (name, field_val) = field.split_once(':').ok_or(format!("{struct_type} field [{}] should use format 'field: value'", field.trim()))?;
+            let index = self.fields.iter().position(|(n, _)| n == name.trim()).ok_or(format!("{struct_type} has no field named [{}]", name.trim()))?;
+            let parsed = StructFieldValue::from_string(field_val, self.fields[index].1).map_err(|e| format!("{struct_type} field [{}]: {e}", name.trim()))?;
+            if values[index].replace(parsed).is_some() {
+                Err(format!("{struct_type} field [{}] is given more than once", name.trim()))?;
+            }
+        }
+
+        values.into_iter().zip(&self.fields).map(|(v, (name, _))| v.ok_or(format!("{struct_type} is missing field [{name}]").into())).collect()
+    }
+}
+
+fn registry() -> &'static RwLock<HashMap<String, StructCodec>> {
+    static REGISTRY: OnceLock<RwLock<HashMap<String, StructCodec>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(native_struct_codecs()))
+}
+
+/// Codecs for the engine's native structs, as serialized by UE4.27
+fn native_struct_codecs() -> HashMap<String, StructCodec> {
+    use StructFieldType::*;
+    let codecs = [
+        ("Vector", StructCodec::new(&[("x", Float), ("y", Float), ("z", Float)])),
+        ("Vector2D", StructCodec::new(&[("x", Float), ("y", Float)])),
+        ("Rotator", StructCodec::new(&[("pitch", Float), ("yaw", Float), ("roll", Float)])),
+        ("Quat", StructCodec::new(&[("x", Float), ("y", Float), ("z", Float), ("w", Float)])),
+        ("Color", StructCodec::new(&[("b", UInt8), ("g", UInt8), ("r", UInt8), ("a", UInt8)])),
+        ("LinearColor", StructCodec::new(&[("r", Float), ("g", Float), ("b", Float), ("a", Float)])),
+        ("Guid", StructCodec::new(&[("a", UInt32), ("b", UInt32), ("c", UInt32), ("d", UInt32)])),
+        ("IntPoint", StructCodec::new(&[("x", Int32), ("y", Int32)])),
+        ("Box", StructCodec::new(&[
+            ("min_x", Float), ("min_y", Float), ("min_z", Float),
+            ("max_x", Float), ("max_y", Float), ("max_z", Float),
+            ("is_valid", UInt8),
+        ])),
+        ("DateTime", StructCodec::new(&[("ticks", Int64)])),
+        ("Timespan", StructCodec::new(&[("ticks", Int64)])),
+    ];
+    codecs.into_iter().map(|(name, codec)| (name.to_string(), codec)).collect()
+}
+
+/// Returns the codec for the given struct type, if it has one
+pub fn find_struct_codec(struct_type: &str) -> Option<StructCodec> {
+    registry().read().unwrap().get(struct_type).cloned()
+}
+
+/// A schema file describing the layouts of a game's own binary-serialized structs, e.g.
+///
+/// ```toml
+/// [[struct]]
+/// name = "MyRowStruct"
+/// fields = [
+///     { name = "health", type = "f32" },
+///     { name = "level", type = "i32" },
+/// ]
+/// ```
+#[derive(Deserialize)]
+struct Schema {
+    #[serde(rename = "struct", default)]
+    structs: Vec<SchemaStruct>,
+}
+
+#[derive(Deserialize)]
+struct SchemaStruct {
+    name: String,
+    fields: Vec<SchemaField>,
+}
+
+#[derive(Deserialize)]
+struct SchemaField {
+    name: String,
+    r#type: StructFieldType,
+}
+
+/// Registers every struct in a schema file (see [`Schema`]), so their data is decoded into fields instead of raw
+/// bytes.  Structs already in the registry are replaced.  Returns the number of structs registered.
+pub fn register_schema(contents: &str) -> Result<usize, Box<dyn Error>> {
+    let schema: Schema = toml::from_str(contents).map_err(|e| format!("Invalid struct schema: {e}"))?;
+
+    let mut codecs = Vec::with_capacity(schema.structs.len());
+    for schema_struct in schema.structs {
+        let name = &schema_struct.name;
+        if name.is_empty() || name.contains(|c: char| c.is_whitespace() || "{}".contains(c)) {
+            Err(format!("Invalid struct schema: [{name}] isn't a valid struct name"))?;
+        }
+        if schema_struct.fields.is_empty() {
+            Err(format!("Invalid struct schema: struct [{name}] has no fields"))?;
+        }
+        for (i, field) in schema_struct.fields.iter().enumerate() {
+            if field.name.is_empty() || field.name.contains(|c: char| c.is_whitespace() || "{},:".contains(c)) {
+                Err(format!("Invalid struct schema: struct [{name}] field [{}] isn't a valid field name", field.name))?;
+            }
+            if schema_struct.fields[..i].iter().any(|f| f.name == field.name) {
+                Err(format!("Invalid struct schema: struct [{name}] has more than one field named [{}]", field.name))?;
+            }
+        }
+
+        let codec = StructCodec {
+            fields: schema_struct.fields.into_iter().map(|f| (f.name, f.r#type)).collect(),
+        };
+        codecs.push((schema_struct.name, codec));
+    }
+
+    let count = codecs.len();
+    registry().write().unwrap().extend(codecs);
+    Ok(count)
+}
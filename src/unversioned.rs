@@ -0,0 +1,395 @@
+use byteorder::WriteBytesExt;
+use crate::error::{EncodeError, ParseError, ParseRead};
+use crate::iostore_uasset::{ObjectReference, UObjectProperty, UObjectPropertyData, UObjectPropertyHeader, UObjectPropertyMetadata, UObjectSummary};
+use crate::struct_codecs::{find_struct_codec, StructCodec};
+use crate::usmap::{MappedType, Mappings};
+use std::io::{self, Read, Seek, Write};
+
+/// One run of the unversioned property header: `skip_num` properties that aren't serialized, followed by
+/// `value_num` that are.  Values in fragments with zeroes each have a bit in the zero mask.
+#[derive(Default, Clone, Copy, PartialEq, Debug)]
+struct Fragment {
+    skip_num: u8,
+    has_any_zeroes: bool,
+    is_last: bool,
+    value_num: u8,
+}
+
+impl Fragment {
+    const SKIP_MAX: u8 = 0x7f;
+    const VALUE_MAX: u8 = 0x7f;
+
+    fn unpack(packed: u16) -> Self {
+        Self {
+            skip_num: (packed & 0x7f) as u8,
+            has_any_zeroes: packed & 0x80 != 0,
+            is_last: packed & 0x100 != 0,
+            value_num: (packed >> 9) as u8,
+        }
+    }
+
+    fn pack(&self) -> u16 {
+        self.skip_num as u16 | (self.has_any_zeroes as u16) << 7 | (self.is_last as u16) << 8 | (self.value_num as u16) << 9
+    }
+}
+
+/// Builds the fragments and zero mask the same way the engine does, so re-encoded headers are byte-exact
+#[derive(Default)]
+struct HeaderBuilder {
+    fragments: Vec<Fragment>,
+    zero_mask: Vec<bool>,
+}
+
+impl HeaderBuilder {
+    fn new() -> Self {
+        Self { fragments: vec![Fragment::default()], zero_mask: vec![] }
+    }
+
+    fn include_property(&mut self, is_zero: bool) {
+        if self.fragments.last().unwrap().value_num == Fragment::VALUE_MAX {
+            self.trim_zero_mask();
+            self.fragments.push(Fragment::default());
+        }
+        let fragment = self.fragments.last_mut().unwrap();
+        fragment.value_num += 1;
+        fragment.has_any_zeroes |= is_zero;
+        self.zero_mask.push(is_zero);
+    }
+
+    fn exclude_property(&mut self) {
+        let fragment = self.fragments.last().unwrap();
+        if fragment.value_num > 0 || fragment.skip_num == Fragment::SKIP_MAX {
+            self.trim_zero_mask();
+            self.fragments.push(Fragment::default());
+        }
+        self.fragments.last_mut().unwrap().skip_num += 1;
+    }
+
+    /// Fragments without zeroes don't need their bits in the zero mask
+    fn trim_zero_mask(&mut self) {
+        let fragment = self.fragments.last().unwrap();
+        if !fragment.has_any_zeroes {
+            self.zero_mask.truncate(self.zero_mask.len() - fragment.value_num as usize);
+        }
+    }
+
+    fn finish<W: Write, E: byteorder::ByteOrder>(mut self, writer: &mut W) -> io::Result<usize> {
+        self.trim_zero_mask();
+        while self.fragments.len() > 1 && self.fragments.last().unwrap().value_num == 0 {
+            self.fragments.pop();
+        }
+        self.fragments.last_mut().unwrap().is_last = true;
+
+        for fragment in &self.fragments {
+            writer.write_u16::<E>(fragment.pack())?;
+        }
+        Ok(2 * self.fragments.len() + write_zero_mask::<W,E>(writer, &self.zero_mask)?)
+    }
+}
+
+fn write_zero_mask<W: Write, E: byteorder::ByteOrder>(writer: &mut W, zero_mask: &[bool]) -> io::Result<usize> {
+    let words: Vec<u32> = zero_mask.chunks(32).map(|bits| bits.iter().enumerate().fold(0, |word, (i, bit)| word | (*bit as u32) << i)).collect();
+    Ok(match zero_mask.len() {
+        0 => 0,
+        1..=8 => { writer.write_u8(words[0] as u8)?; 1 },
+        9..=16 => { writer.write_u16::<E>(words[0] as u16)?; 2 },
+        _ => words.iter().map(|word| writer.write_u32::<E>(*word).map(|_| 4)).sum::<io::Result<usize>>()?,
+    })
+}
+
+fn read_zero_mask<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R, num_bits: usize) -> Result<Vec<bool>, ParseError> {
+    let words = match num_bits {
+        0 => vec![],
+        1..=8 => vec![reader.parse_u8()? as u32],
+        9..=16 => vec![reader.parse_u16::<E>()? as u32],
+        _ => (0..num_bits.div_ceil(32)).map(|_| reader.parse_u32::<E>()).collect::<Result<_,_>>()?,
+    };
+    Ok((0..num_bits).map(|i| words[i / 32] & (1 << (i % 32)) != 0).collect())
+}
+
+/// Reads the unversioned properties of a struct, as laid out in the mappings.  Values in the zero mask become
+/// `UObjectPropertyData::Zero`, since they're serialized as 'present, but zero'.
+pub fn properties_from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R, struct_name: &str, mappings: &Mappings, summary: &UObjectSummary) -> Result<Vec<UObjectProperty>, ParseError> {
+    let schema = mappings.schema(struct_name).map_err(|err| ParseError::invalid(reader.offset(), err.to_string()))?;
+
+    let mut fragments = vec![];
+    loop {
+        let fragment = Fragment::unpack(reader.parse_u16::<E>()?);
+        fragments.push(fragment);
+        if fragment.is_last {
+            break;
+        }
+    }
+    let zero_mask_bits = fragments.iter().filter(|f| f.has_any_zeroes).map(|f| f.value_num as usize).sum();
+    let zero_mask = read_zero_mask::<R,E>(reader, zero_mask_bits)?;
+
+    let mut properties = vec![];
+    let mut schema_index = 0;
+    let mut zero_mask = zero_mask.into_iter();
+    for fragment in fragments {
+        schema_index += fragment.skip_num as usize;
+        for _ in 0..fragment.value_num {
+            let (mapped, arr_index) = schema.get(schema_index).copied().flatten()
+                .ok_or(ParseError::invalid(reader.offset(), format!("Unversioned property {schema_index} of {struct_name} isn't in the mappings")))?;
+            let is_zero = fragment.has_any_zeroes && zero_mask.next().unwrap_or(false);
+            let (data, metadata) = if is_zero {
+                (UObjectPropertyData::Zero(mapped.r#type.type_name().to_owned()), UObjectPropertyMetadata::None)
+            } else {
+                value_from_buffer::<R,E>(reader, &mapped.r#type, false, mappings, summary)
+                    .map_err(|err| match arr_index {
+                        0 => err.in_property(&mapped.name),
+                        arr_index => err.in_property(&format!("{}[{arr_index}]", mapped.name)),
+                    })?
+            };
+            properties.push(UObjectProperty {
+                header: UObjectPropertyHeader {
+                    name: mapped.name.clone(),
+                    r#type: data.get_string_type().to_owned(),
+                    arr_index,
+                },
+                metadata,
+                data,
+            });
+            schema_index += 1;
+        }
+    }
+    Ok(properties)
+}
+
+/// Writes properties as the unversioned properties of a struct.  Properties are written in schema order, whatever
+/// order they're listed in.
+pub fn properties_to_bytes<W: Write, E: byteorder::ByteOrder>(properties: &[UObjectProperty], struct_name: &str, mappings: &Mappings, summary: &UObjectSummary, writer: &mut W) -> Result<usize, EncodeError> {
+    let schema = mappings.schema(struct_name).map_err(|err| EncodeError::unsupported(err.to_string()))?;
+
+    let mut by_index: Vec<Option<&UObjectProperty>> = vec![None; schema.len()];
+    for prop in properties {
+        let schema_index = schema.iter().position(|slot| matches!(slot, Some((mapped, arr_index)) if mapped.name == prop.header.name && *arr_index == prop.header.arr_index))
+            .ok_or_else(|| EncodeError::unsupported(format!("{struct_name} has no property with this name in the mappings")).in_property(&prop.header.path_segment()))?;
+        if by_index[schema_index].replace(prop).is_some() {
+            Err(EncodeError::unsupported("Property is given more than once").in_property(&prop.header.path_segment()))?;
+        }
+    }
+
+    let mut header = HeaderBuilder::new();
+    let mut values = vec![];
+    for (slot, prop) in schema.iter().zip(&by_index) {
+        match prop {
+            Some(prop) if matches!(prop.data, UObjectPropertyData::Zero(_)) => header.include_property(true),
+            Some(prop) => {
+                header.include_property(false);
+                let (mapped, _) = slot.unwrap();
+                value_to_bytes::<_,E>(&mut values, &prop.data, &prop.metadata, &mapped.r#type, mappings, summary)
+                    .map_err(|err| err.in_property(&prop.header.path_segment()))?;
+            },
+            None => header.exclude_property(),
+        }
+    }
+
+    let header_len = header.finish::<W,E>(writer)?;
+    writer.write_all(&values)?;
+    Ok(header_len + values.len())
+}
+
+/// Adds the names unversioned properties need in the name map - unlike tagged properties, only names stored as
+/// values (and not property names or types)
+pub fn collect_names<'a>(properties: &'a [UObjectProperty], names: &mut Vec<&'a str>) {
+    for prop in properties {
+        collect_value_names(&prop.data, names);
+    }
+}
+
+fn collect_value_names<'a>(data: &'a UObjectPropertyData, names: &mut Vec<&'a str>) {
+    match data {
+        UObjectPropertyData::Name(name) => names.push(name),
+        UObjectPropertyData::SoftObject(asset_path, _) | UObjectPropertyData::SoftClass(asset_path, _) => names.push(asset_path),
+        UObjectPropertyData::Struct(props, _) => collect_names(props, names),
+        UObjectPropertyData::Array(items, _) => items.iter().for_each(|item| collect_value_names(item, names)),
+        UObjectPropertyData::Set(items_to_remove, items) => items_to_remove.iter().chain(items).for_each(|item| collect_value_names(item, names)),
+        UObjectPropertyData::Map(entries) => entries.iter().for_each(|(key, val)| {
+            collect_value_names(key, names);
+            collect_value_names(val, names);
+        }),
+        _ => {}
+    }
+}
+
+/// Container items have no metadata of their own, so enums inside containers are kept as their underlying value
+fn container_type_name(r#type: &MappedType) -> &'static str {
+    match r#type {
+        MappedType::Enum(inner, _) => inner.type_name(),
+        other => other.type_name(),
+    }
+}
+
+/// Types that can be written as map keys in the text format
+fn is_printable_map_key(r#type: &MappedType) -> bool {
+    matches!(container_type_name(r#type), "IntProperty" | "UInt16Property" | "StrProperty" | "FloatProperty" | "ByteProperty" | "BoolProperty" | "Int64Property" | "UInt64Property" | "DoubleProperty" | "NameProperty")
+}
+
+fn value_from_buffer<R: Read + Seek, E: byteorder::ByteOrder>(reader: &mut R, r#type: &MappedType, in_container: bool, mappings: &Mappings, summary: &UObjectSummary) -> Result<(UObjectPropertyData, UObjectPropertyMetadata), ParseError> {
+    let offset = reader.offset();
+    let items = |reader: &mut R, len: usize, inner: &MappedType| -> Result<Vec<UObjectPropertyData>, ParseError> {
+        (0..len).map(|i| value_from_buffer::<R,E>(reader, inner, true, mappings, summary).map(|v| v.0).map_err(|err| err.in_property(&format!("[{i}]")))).collect()
+    };
+    let leaf = |reader: &mut R, type_name: &str| UObjectPropertyData::from_buffer::<R,E>(reader, type_name, &UObjectPropertyMetadata::Struct(String::new(), [0;16]), summary, 0);
+    Ok(match r#type {
+        MappedType::Bool => {
+            let val = reader.parse_u8()? != 0;
+            if in_container {
+                (UObjectPropertyData::BoolValue(val), UObjectPropertyMetadata::None)
+            } else {
+                (UObjectPropertyData::Bool, UObjectPropertyMetadata::Bool(val))
+            }
+        },
+        MappedType::Byte => (UObjectPropertyData::Byte(reader.parse_u8()?), UObjectPropertyMetadata::Byte(0, 0)),
+        MappedType::Enum(inner, enum_name) => {
+            let (data, metadata) = value_from_buffer::<R,E>(reader, inner, in_container, mappings, summary)?;
+            if in_container {
+                (data, metadata)
+            } else {
+                let value = enum_value(&data).ok_or(ParseError::invalid(offset, format!("Enum {enum_name} has unsupported underlying type {}", inner.type_name())))?;
+                let entry = mappings.enum_entry_name(enum_name, value).ok_or(ParseError::invalid(offset, format!("Enum {enum_name} has no entry with value {value}")))?;
+                (UObjectPropertyData::Enum(entry.to_owned()), UObjectPropertyMetadata::Enum(enum_name.clone()))
+            }
+        },
+        MappedType::Int | MappedType::Float | MappedType::Double | MappedType::Name | MappedType::Str | MappedType::Text
+            | MappedType::UInt64 | MappedType::UInt32 | MappedType::UInt16 | MappedType::Int64 | MappedType::Int16 | MappedType::Int8
+            | MappedType::WeakObject | MappedType::LazyObject | MappedType::SoftObject => {
+            // The non-None metadata keeps text from being read as a tagged TextProperty of a known size
+            (leaf(reader, r#type.type_name())?, UObjectPropertyMetadata::None)
+        },
+        MappedType::Object => (UObjectPropertyData::Object(ObjectReference::from_buffer::<R,E>(reader, summary)?), UObjectPropertyMetadata::None),
+        MappedType::AssetObject => (leaf(reader, "SoftObjectProperty")?, UObjectPropertyMetadata::None),
+        MappedType::Struct(struct_type) => {
+            let data = match find_struct_codec(struct_type) {
+                Some(codec) => UObjectPropertyData::NativeStruct(struct_type.clone(), codec.read_values::<R,E>(reader)?),
+                None => UObjectPropertyData::Struct(properties_from_buffer::<R,E>(reader, struct_type, mappings, summary)?, vec![]),
+            };
+            (data, UObjectPropertyMetadata::Struct(struct_type.clone(), [0;16]))
+        },
+        MappedType::Array(inner) => {
+            let len = reader.parse_u32::<E>()? as usize;
+            (UObjectPropertyData::Array(items(reader, len, inner)?, None), UObjectPropertyMetadata::Array(container_type_name(inner).to_owned()))
+        },
+        MappedType::Set(inner) => {
+            let num_to_remove = reader.parse_u32::<E>()? as usize;
+            let items_to_remove = items(reader, num_to_remove, inner).map_err(|err| err.in_property("removed"))?;
+            let len = reader.parse_u32::<E>()? as usize;
+            (UObjectPropertyData::Set(items_to_remove, items(reader, len, inner)?), UObjectPropertyMetadata::Set(container_type_name(inner).to_owned()))
+        },
+        MappedType::Map(key_type, value_type) => {
+            if !is_printable_map_key(key_type) {
+                Err(ParseError::invalid(offset, format!("Maps with {} keys aren't supported", key_type.type_name())))?;
+            }
+            let num_to_remove = reader.parse_u32::<E>()?;
+            if num_to_remove != 0 {
+                Err(ParseError::invalid(offset, format!("Maps with keys to remove aren't supported (found {num_to_remove})")))?;
+            }
+            let len = reader.parse_u32::<E>()? as usize;
+            let mut entries = vec![];
+            for i in 0..len {
+                let key = value_from_buffer::<R,E>(reader, key_type, true, mappings, summary).map_err(|err| err.in_property(&format!("[{i}].key")))?.0;
+                let value = value_from_buffer::<R,E>(reader, value_type, true, mappings, summary).map_err(|err| err.in_property(&format!("[{i}].value")))?.0;
+                entries.push((key, value));
+            }
+            (UObjectPropertyData::Map(entries), UObjectPropertyMetadata::Map(container_type_name(key_type).to_owned(), container_type_name(value_type).to_owned()))
+        },
+        other => Err(ParseError::invalid(offset, format!("Unversioned {} values aren't supported", other.type_name())))?,
+    })
+}
+
+fn value_to_bytes<W: Write, E: byteorder::ByteOrder>(writer: &mut W, data: &UObjectPropertyData, metadata: &UObjectPropertyMetadata, r#type: &MappedType, mappings: &Mappings, summary: &UObjectSummary) -> Result<usize, EncodeError> {
+    let mismatch = || EncodeError::type_mismatch(format!("{} data", r#type.type_name()), data.get_string_type());
+    Ok(match (r#type, data) {
+        (MappedType::Bool, UObjectPropertyData::Bool) => match metadata {
+            UObjectPropertyMetadata::Bool(val) => { writer.write_u8(*val as u8)?; 1 },
+            _ => Err(EncodeError::unsupported("Bool property has no value"))?,
+        },
+        (MappedType::Bool, UObjectPropertyData::BoolValue(val)) => { writer.write_u8(*val as u8)?; 1 },
+        (MappedType::Enum(inner, enum_name), UObjectPropertyData::Enum(entry)) => {
+            let value = mappings.enum_entry_value(enum_name, entry).ok_or_else(|| EncodeError::unsupported(format!("Enum {enum_name} has no entry named {entry}")))?;
+            let data = match inner.as_ref() {
+                MappedType::Byte => UObjectPropertyData::Byte(value as u8),
+                MappedType::Int8 => UObjectPropertyData::Int8(value as i8),
+                MappedType::Int16 => UObjectPropertyData::Int16(value as i16),
+                MappedType::UInt16 => UObjectPropertyData::UInt16(value as u16),
+                MappedType::Int => UObjectPropertyData::Int32(value as i32),
+                MappedType::UInt32 => UObjectPropertyData::UInt32(value as u32),
+                MappedType::Int64 => UObjectPropertyData::Int64(value),
+                MappedType::UInt64 => UObjectPropertyData::UInt64(value as u64),
+                other => Err(EncodeError::unsupported(format!("Enum {enum_name} has unsupported underlying type {}", other.type_name())))?,
+            };
+            data.to_bytes::<W,E>(writer, summary)?
+        },
+        (MappedType::Enum(inner, _), data) => value_to_bytes::<W,E>(writer, data, metadata, inner, mappings, summary)?,
+        (MappedType::Struct(struct_type), UObjectPropertyData::NativeStruct(data_type, values)) => {
+            if data_type != struct_type {
+                Err(EncodeError::type_mismatch(format!("{struct_type} struct"), data_type.as_str()))?;
+            }
+            let codec = find_struct_codec(struct_type).ok_or_else(|| EncodeError::unsupported(format!("Struct type [{struct_type}] has no codec")))?;
+            if values.len() != codec.fields.len() || values.iter().zip(&codec.fields).any(|(v, (_, t))| v.get_type() != *t) {
+                Err(EncodeError::unsupported(format!("{struct_type} values don't match its codec")))?;
+            }
+            StructCodec::write_values::<W,E>(values, writer)?
+        },
+        (MappedType::Struct(struct_type), UObjectPropertyData::Struct(props, raw)) => {
+            if !raw.is_empty() {
+                Err(EncodeError::unsupported(format!("{struct_type} struct has raw data, which unversioned properties can't hold")))?;
+            }
+            properties_to_bytes::<W,E>(props, struct_type, mappings, summary, writer)?
+        },
+        (MappedType::Array(inner), UObjectPropertyData::Array(items, _)) => {
+            writer.write_u32::<E>(items.len() as u32)?;
+            let mut len = 4;
+            for (i, item) in items.iter().enumerate() {
+                len += value_to_bytes::<W,E>(writer, item, &UObjectPropertyMetadata::None, inner, mappings, summary).map_err(|err| err.in_property(&format!("[{i}]")))?;
+            }
+            len
+        },
+        (MappedType::Set(inner), UObjectPropertyData::Set(items_to_remove, items)) => {
+            let mut len = 0;
+            for (list, segment) in [(items_to_remove, "removed"), (items, "")] {
+                writer.write_u32::<E>(list.len() as u32)?;
+                len += 4;
+                for (i, item) in list.iter().enumerate() {
+                    len += value_to_bytes::<W,E>(writer, item, &UObjectPropertyMetadata::None, inner, mappings, summary).map_err(|err| err.in_property(&format!("{segment}[{i}]")))?;
+                }
+            }
+            len
+        },
+        (MappedType::Map(key_type, value_type), UObjectPropertyData::Map(entries)) => {
+            writer.write_u32::<E>(0)?; // Keys to remove
+            writer.write_u32::<E>(entries.len() as u32)?;
+            let mut len = 8;
+            for (i, (key, val)) in entries.iter().enumerate() {
+                len += value_to_bytes::<W,E>(writer, key, &UObjectPropertyMetadata::None, key_type, mappings, summary).map_err(|err| err.in_property(&format!("[{i}].key")))?;
+                len += value_to_bytes::<W,E>(writer, val, &UObjectPropertyMetadata::None, value_type, mappings, summary).map_err(|err| err.in_property(&format!("[{i}].value")))?;
+            }
+            len
+        },
+        (MappedType::Object, UObjectPropertyData::Object(_) | UObjectPropertyData::Class(_))
+            | (MappedType::SoftObject | MappedType::AssetObject, UObjectPropertyData::SoftObject(_, _) | UObjectPropertyData::SoftClass(_, _)) => {
+            data.to_bytes::<W,E>(writer, summary)?
+        },
+        (MappedType::Str, UObjectPropertyData::String(_) | UObjectPropertyData::StringUtf16(_)) => data.to_bytes::<W,E>(writer, summary)?,
+        (r#type, data) if r#type.type_name() == data.get_string_type() && !matches!(data, UObjectPropertyData::Zero(_) | UObjectPropertyData::Struct(_, _)) => {
+            data.to_bytes::<W,E>(writer, summary)?
+        },
+        _ => Err(mismatch())?,
+    })
+}
+
+/// The integer value of data read for an enum's underlying type
+fn enum_value(data: &UObjectPropertyData) -> Option<i64> {
+    match data {
+        UObjectPropertyData::Byte(v) => Some(*v as i64),
+        UObjectPropertyData::Int8(v) => Some(*v as i64),
+        UObjectPropertyData::Int16(v) => Some(*v as i64),
+        UObjectPropertyData::UInt16(v) => Some(*v as i64),
+        UObjectPropertyData::Int32(v) => Some(*v as i64),
+        UObjectPropertyData::UInt32(v) => Some(*v as i64),
+        UObjectPropertyData::Int64(v) => Some(*v),
+        UObjectPropertyData::UInt64(v) => Some(*v as i64),
+        _ => None,
+    }
+}
@@ -0,0 +1,325 @@
+use byteorder::{ReadBytesExt, LE};
+use crate::cityhash::city_hash_64;
+use std::{collections::HashMap, error::Error, io::Read};
+
+const USMAP_MAGIC: u16 = 0x30C4;
+const USMAP_LATEST_VERSION: u8 = 4;
+
+// Versions that changed the file layout
+const USMAP_VERSION_PACKAGE_VERSIONING: u8 = 1;
+const USMAP_VERSION_LONG_FNAME: u8 = 2;
+const USMAP_VERSION_LARGE_ENUMS: u8 = 3;
+const USMAP_VERSION_EXPLICIT_ENUM_VALUES: u8 = 4;
+
+/// Script modules searched when resolving a script class import to one of the mapped structs.  Game modules can be
+/// added with [`Mappings::add_script_module`].
+const DEFAULT_SCRIPT_MODULES: [&str; 2] = ["CoreUObject", "Engine"];
+
+/// A mapped property, and which element of its static array a schema index refers to
+pub type SchemaSlot<'a> = (&'a MappedProperty, usize);
+
+/// The type of a mapped property, including the types it contains
+#[derive(Clone, PartialEq, Debug)]
+pub enum MappedType {
+    Byte,
+    Bool,
+    Int,
+    Float,
+    Object,
+    Name,
+    Delegate,
+    Double,
+    Array(Box<MappedType>),
+    Struct(String),
+    Str,
+    Text,
+    Interface,
+    MulticastDelegate,
+    WeakObject,
+    LazyObject,
+    AssetObject,
+    SoftObject,
+    UInt64,
+    UInt32,
+    UInt16,
+    Int64,
+    Int16,
+    Int8,
+    Map(Box<MappedType>, Box<MappedType>),
+    Set(Box<MappedType>),
+    Enum(Box<MappedType>, String), // Underlying type, enum name
+    FieldPath,
+    Optional(Box<MappedType>),
+    Unknown(u8),
+}
+
+impl MappedType {
+    fn from_buffer<R: Read>(reader: &mut R, names: &[String]) -> Result<Self, Box<dyn Error>> {
+        Ok(match reader.read_u8()? {
+            0 => Self::Byte,
+            1 => Self::Bool,
+            2 => Self::Int,
+            3 => Self::Float,
+            4 => Self::Object,
+            5 => Self::Name,
+            6 => Self::Delegate,
+            7 => Self::Double,
+            8 => Self::Array(Box::new(Self::from_buffer(reader, names)?)),
+            9 => Self::Struct(read_name(reader, names)?.ok_or("Mapped struct property has no struct type")?),
+            10 => Self::Str,
+            11 => Self::Text,
+            12 => Self::Interface,
+            13 => Self::MulticastDelegate,
+            14 => Self::WeakObject,
+            15 => Self::LazyObject,
+            16 => Self::AssetObject,
+            17 => Self::SoftObject,
+            18 => Self::UInt64,
+            19 => Self::UInt32,
+            20 => Self::UInt16,
+            21 => Self::Int64,
+            22 => Self::Int16,
+            23 => Self::Int8,
+            24 => {
+                let key = Self::from_buffer(reader, names)?;
+                let value = Self::from_buffer(reader, names)?;
+                Self::Map(Box::new(key), Box::new(value))
+            },
+            25 => Self::Set(Box::new(Self::from_buffer(reader, names)?)),
+            26 => {
+                let inner = Self::from_buffer(reader, names)?;
+                let enum_name = read_name(reader, names)?.ok_or("Mapped enum property has no enum name")?;
+                Self::Enum(Box::new(inner), enum_name)
+            },
+            27 => Self::FieldPath,
+            28 => Self::Optional(Box::new(Self::from_buffer(reader, names)?)),
+            other => Self::Unknown(other),
+        })
+    }
+
+    /// The property type name, as it would appear in a property tag
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Byte => "ByteProperty",
+            Self::Bool => "BoolProperty",
+            Self::Int => "IntProperty",
+            Self::Float => "FloatProperty",
+            Self::Object => "ObjectProperty",
+            Self::Name => "NameProperty",
+            Self::Delegate => "DelegateProperty",
+            Self::Double => "DoubleProperty",
+            Self::Array(_) => "ArrayProperty",
+            Self::Struct(_) => "StructProperty",
+            Self::Str => "StrProperty",
+            Self::Text => "TextProperty",
+            Self::Interface => "InterfaceProperty",
+            Self::MulticastDelegate => "MulticastDelegateProperty",
+            Self::WeakObject => "WeakObjectProperty",
+            Self::LazyObject => "LazyObjectProperty",
+            Self::AssetObject => "AssetObjectProperty",
+            Self::SoftObject => "SoftObjectProperty",
+            Self::UInt64 => "UInt64Property",
+            Self::UInt32 => "UInt32Property",
+            Self::UInt16 => "UInt16Property",
+            Self::Int64 => "Int64Property",
+            Self::Int16 => "Int16Property",
+            Self::Int8 => "Int8Property",
+            Self::Map(_, _) => "MapProperty",
+            Self::Set(_) => "SetProperty",
+            Self::Enum(_, _) => "EnumProperty",
+            Self::FieldPath => "FieldPathProperty",
+            Self::Optional(_) => "OptionalProperty",
+            Self::Unknown(_) => "UnknownProperty",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct MappedProperty {
+    pub index: usize,
+    pub array_dim: usize,
+    pub name: String,
+    pub r#type: MappedType,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct MappedStruct {
+    pub super_type: Option<String>,
+    pub property_count: usize,
+    pub properties: Vec<MappedProperty>,
+}
+
+/// The contents of a .usmap mappings file - the property layout of every class and struct in a game, which
+/// unversioned property serialization leaves out of the packages themselves
+#[derive(Clone, PartialEq, Debug)]
+pub struct Mappings {
+    pub structs: HashMap<String, MappedStruct>,
+    pub enums: HashMap<String, Vec<(i64, String)>>,
+    script_modules: Vec<String>,
+}
+
+impl Mappings {
+    pub fn from_buffer<R: Read>(reader: &mut R) -> Result<Self, Box<dyn Error>> {
+        let magic = reader.read_u16::<LE>()?;
+        if magic != USMAP_MAGIC {
+            Err(format!("Not a usmap file - expected magic 0x{USMAP_MAGIC:x}, but got 0x{magic:x}"))?;
+        }
+        let version = reader.read_u8()?;
+        if version > USMAP_LATEST_VERSION {
+            Err(format!("Unsupported usmap version {version} - the latest supported version is {USMAP_LATEST_VERSION}"))?;
+        }
+
+        if version >= USMAP_VERSION_PACKAGE_VERSIONING && reader.read_i32::<LE>()? != 0 {
+            let _file_version_ue4 = reader.read_i32::<LE>()?;
+            let _file_version_ue5 = reader.read_i32::<LE>()?;
+            let custom_version_count = reader.read_i32::<LE>()?;
+            for _ in 0..custom_version_count {
+                let mut _custom_version = [0;20]; // Guid, version
+                reader.read_exact(&mut _custom_version)?;
+            }
+            let _net_cl = reader.read_u32::<LE>()?;
+        }
+
+        let compression = reader.read_u8()?;
+        let compressed_size = reader.read_u32::<LE>()? as usize;
+        let decompressed_size = reader.read_u32::<LE>()? as usize;
+        if compression != 0 {
+            Err(format!("Unsupported usmap compression method {compression} - only uncompressed mappings can be read"))?;
+        }
+        if compressed_size != decompressed_size {
+            Err(format!("Uncompressed usmap data has a compressed size of {compressed_size}, but a decompressed size of {decompressed_size}"))?;
+        }
+        // The sizes and counts come from the file, so nothing is preallocated from them
+        let mut data = vec![];
+        reader.take(decompressed_size as u64).read_to_end(&mut data)?;
+        if data.len() != decompressed_size {
+            Err(format!("Usmap data is truncated - expected {decompressed_size} bytes"))?;
+        }
+        let reader = &mut data.as_slice();
+
+        let name_count = reader.read_u32::<LE>()? as usize;
+        let mut names = vec![];
+        for _ in 0..name_count {
+            let len = if version >= USMAP_VERSION_LONG_FNAME { reader.read_u16::<LE>()? as usize } else { reader.read_u8()? as usize };
+            let mut name = vec![0;len];
+            reader.read_exact(&mut name)?;
+            names.push(String::from_utf8(name)?);
+        }
+
+        let enum_count = reader.read_u32::<LE>()? as usize;
+        let mut enums = HashMap::new();
+        for _ in 0..enum_count {
+            let enum_name = read_name(reader, &names)?.ok_or("Mapped enum has no name")?;
+            let entry_count = if version >= USMAP_VERSION_LARGE_ENUMS { reader.read_u16::<LE>()? as usize } else { reader.read_u8()? as usize };
+            let mut entries = vec![];
+            for i in 0..entry_count {
+                let value = if version >= USMAP_VERSION_EXPLICIT_ENUM_VALUES { reader.read_i64::<LE>()? } else { i as i64 };
+                entries.push((value, read_name(reader, &names)?.ok_or(format!("Enum {enum_name} has an entry with no name"))?));
+            }
+            enums.insert(enum_name, entries);
+        }
+
+        let struct_count = reader.read_u32::<LE>()? as usize;
+        let mut structs = HashMap::new();
+        for _ in 0..struct_count {
+            let struct_name = read_name(reader, &names)?.ok_or("Mapped struct has no name")?;
+            let super_type = read_name(reader, &names)?;
+            let property_count = reader.read_u16::<LE>()? as usize;
+            let serializable_property_count = reader.read_u16::<LE>()? as usize;
+            let mut properties = vec![];
+            for _ in 0..serializable_property_count {
+                let index = reader.read_u16::<LE>()? as usize;
+                let array_dim = reader.read_u8()? as usize;
+                let name = read_name(reader, &names)?.ok_or(format!("Struct {struct_name} has a property with no name"))?;
+                let r#type = MappedType::from_buffer(reader, &names)?;
+                properties.push(MappedProperty { index, array_dim, name, r#type });
+            }
+            structs.insert(struct_name, MappedStruct { super_type, property_count, properties });
+        }
+
+        Ok(Self {
+            structs,
+            enums,
+            script_modules: DEFAULT_SCRIPT_MODULES.iter().map(|m| m.to_string()).collect(),
+        })
+    }
+
+    /// Adds a script module (e.g. the game's module) to search when resolving script class imports
+    pub fn add_script_module(&mut self, module: &str) {
+        self.script_modules.push(module.to_owned());
+    }
+
+    /// Finds the mapped struct whose script path (/Script/<Module>.<Name>) hashes to the given script import
+    pub fn find_script_struct(&self, import_hash: u64) -> Option<&str> {
+        self.structs.keys().find(|name| {
+            self.script_modules.iter().any(|module| script_import_hash(&format!("/Script/{module}.{name}")) == import_hash)
+        }).map(String::as_str)
+    }
+
+    /// Finds the script object path that hashes to a script import - one of the mapped structs, or the class
+    /// default object of one
+    pub fn find_script_object(&self, import_hash: u64) -> Option<String> {
+        self.structs.keys().flat_map(|name| {
+            self.script_modules.iter().flat_map(move |module| [format!("/Script/{module}.{name}"), format!("/Script/{module}.Default__{name}")])
+        }).find(|path| script_import_hash(path) == import_hash)
+    }
+
+    /// Whether a mapped struct is a class, which all derive from Object
+    pub fn is_class(&self, struct_name: &str) -> bool {
+        let mut next_struct = Some(struct_name);
+        while let Some(struct_name) = next_struct {
+            if struct_name == "Object" {
+                return true;
+            }
+            next_struct = self.structs.get(struct_name).and_then(|s| s.super_type.as_deref());
+        }
+        false
+    }
+
+    /// Lists every property slot of a struct, including its super structs', by schema index.  Slots with no
+    /// serializable property are None, and properties with an array dimension take one slot per element.
+    pub fn schema(&self, struct_name: &str) -> Result<Vec<Option<SchemaSlot<'_>>>, Box<dyn Error>> {
+        let mut schema = vec![];
+        let mut next_struct = Some(struct_name);
+        while let Some(struct_name) = next_struct {
+            let mapped = self.structs.get(struct_name).ok_or(format!("Struct {struct_name} isn't in the mappings"))?;
+            let offset = schema.len();
+            schema.resize(offset + mapped.property_count, None);
+            for prop in &mapped.properties {
+                for arr_index in 0..prop.array_dim {
+                    let slot = schema.get_mut(offset + prop.index + arr_index).ok_or(format!("Struct {struct_name} property {} is outside its property count", prop.name))?;
+                    *slot = Some((prop, arr_index));
+                }
+            }
+            next_struct = mapped.super_type.as_deref();
+        }
+        Ok(schema)
+    }
+
+    pub fn enum_entry_name(&self, enum_name: &str, value: i64) -> Option<&str> {
+        self.enums.get(enum_name)?.iter().find(|(v, _)| *v == value).map(|(_, name)| name.as_str())
+    }
+
+    pub fn enum_entry_value(&self, enum_name: &str, entry_name: &str) -> Option<i64> {
+        self.enums.get(enum_name)?.iter().find(|(_, name)| name == entry_name).map(|(v, _)| *v)
+    }
+}
+
+fn read_name<R: Read>(reader: &mut R, names: &[String]) -> Result<Option<String>, Box<dyn Error>> {
+    match reader.read_i32::<LE>()? {
+        -1 => Ok(None),
+        index => Ok(Some(names.get(index as usize).ok_or(format!("Usmap name index {index} is out of range"))?.clone())),
+    }
+}
+
+/// Hashes a script object path the way IoStore script imports do - lowercased, with '.' and ':' replaced by '/',
+/// as UTF-16
+pub fn script_import_hash(object_path: &str) -> u64 {
+    let path: Vec<u8> = object_path.chars()
+        .map(|c| if c == '.' || c == ':' { '/' } else { c.to_ascii_lowercase() })
+        .collect::<String>()
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    city_hash_64(&path) & !(3 << 62)
+}
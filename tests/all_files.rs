@@ -8,6 +8,7 @@ mod test_files {
 
     test_each_path!{ for ["uasset"] in "./test_files" => test }
     
+    #[allow(dead_code)] // Unused until sample files are added to test_files
     fn test(path: [&Path; 1]) {
         let mut original_file_bytes = {
             let mut file_bytes = vec![];
@@ -34,10 +35,21 @@ mod test_files {
         };
         
         let mut final_bytes = vec![];
-        deserialized_string.to_bytes::<_, LE>(&mut final_bytes);
+        deserialized_string.to_bytes::<_, LE>(&mut final_bytes).unwrap();
 
-        for (i, byte) in original_file_bytes.into_inner().iter().enumerate() {
+        for (i, byte) in original_file_bytes.get_ref().iter().enumerate() {
             assert_eq!(byte, &final_bytes[i], "File bytes differ at 0x{i:x}");
         }
+
+        let mut yaml = vec![];
+        deserialized_file.to_yaml(&mut yaml).unwrap();
+        let deserialized_yaml = match IoUObject::from_yaml(yaml.as_slice()) {
+            Ok(deserialized) => deserialized,
+            Err(err) => panic!("{err}"),
+        };
+
+        let mut yaml_bytes = vec![];
+        deserialized_yaml.to_bytes::<_, LE>(&mut yaml_bytes).unwrap();
+        assert_eq!(&yaml_bytes, original_file_bytes.get_ref(), "Bytes encoded from YAML differ");
     }
 }
\ No newline at end of file